@@ -33,7 +33,8 @@ pub struct AbacusAgentCore {
     pub interchain_gas_paymaster: Option<Arc<CachingInterchainGasPaymaster>>,
     /// A map of boxed Inbox contracts
     pub inboxes: HashMap<String, InboxContracts>,
-    /// A persistent KV Store (currently implemented as rocksdb)
+    /// A KV Store, persisted with RocksDB unless the `memory` backend is
+    /// configured
     pub db: DB,
     /// Prometheus metrics
     pub metrics: Arc<CoreMetrics>,
@@ -0,0 +1,116 @@
+use abacus_core::OutboxIndexer;
+
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::{debug, info, info_span, instrument::Instrumented, warn, Instrument};
+
+use std::cmp::min;
+
+use crate::{
+    contract_sync::{chunk::AdaptiveChunkSize, schema::OutboxContractSyncDB},
+    ContractSync,
+};
+
+const CACHED_CHECKPOINTS_LABEL: &str = "cached_checkpoints";
+
+impl<I> ContractSync<I>
+where
+    I: OutboxIndexer + 'static,
+{
+    /// Sync the checkpoints cached on the outbox. Only blocks which have
+    /// reached finality are indexed, so cached checkpoints are never rolled
+    /// back.
+    pub fn sync_cached_checkpoints(&self) -> Instrumented<JoinHandle<eyre::Result<()>>> {
+        let span = info_span!("CachedCheckpointContractSync");
+
+        let db = self.db.clone();
+        let indexer = self.indexer.clone();
+
+        let indexed_height = self
+            .metrics
+            .indexed_height
+            .with_label_values(&[CACHED_CHECKPOINTS_LABEL, &self.chain_name]);
+
+        let stored_checkpoints = self
+            .metrics
+            .stored_events
+            .with_label_values(&[CACHED_CHECKPOINTS_LABEL, &self.chain_name]);
+
+        let config_from = self.index_settings.from();
+        let mut chunk_size = AdaptiveChunkSize::new(
+            self.index_settings.chunk_size(),
+            self.metrics
+                .chunk_size
+                .with_label_values(&[CACHED_CHECKPOINTS_LABEL, &self.chain_name]),
+        );
+
+        tokio::spawn(async move {
+            let mut from = db
+                .retrieve_latest_indexed_cached_checkpoint_block()
+                .map_or_else(|| config_from, |b| b + 1);
+
+            info!(from = from, "[CachedCheckpoints]: resuming indexer from {from}");
+
+            loop {
+                indexed_height.set(from.into());
+
+                // If there's an error getting the block number, just start the loop over
+                let tip = if let Ok(num) = indexer.get_finalized_block_number().await {
+                    num
+                } else {
+                    continue;
+                };
+                if tip <= from {
+                    debug!(tip=?tip, from=?from, "[CachedCheckpoints]: caught up to tip, waiting for new block");
+                    // Wait for a new block if caught up to tip
+                    indexer.wait_for_finalized_block_after(from).await;
+                    continue;
+                }
+
+                let candidate = from + chunk_size.get();
+                let to = min(tip, candidate);
+
+                // Retry with fewer blocks if the provider rejects the range
+                let checkpoints = match indexer.fetch_sorted_cached_checkpoints(from, to).await {
+                    Ok(checkpoints) => checkpoints,
+                    Err(err) if chunk_size.shrink_for(&err) => {
+                        warn!(
+                            from = from,
+                            to = to,
+                            chunk_size = chunk_size.get(),
+                            error = ?err,
+                            "[CachedCheckpoints]: Provider rejected block range, shrinking chunk size",
+                        );
+                        continue;
+                    }
+                    Err(err) => {
+                        let backoff = chunk_size.record_failure();
+                        warn!(
+                            from = from,
+                            to = to,
+                            error = ?err,
+                            backoff = ?backoff,
+                            "[CachedCheckpoints]: Failed to fetch cached checkpoints, retrying",
+                        );
+                        sleep(backoff).await;
+                        continue;
+                    }
+                };
+                chunk_size.record_window(checkpoints.len());
+
+                info!(
+                    from = from,
+                    to = to,
+                    cached_checkpoints_count = checkpoints.len(),
+                    "[CachedCheckpoints]: indexed block heights {from}...{to}"
+                );
+
+                db.store_cached_checkpoints(&checkpoints)?;
+                stored_checkpoints.add(checkpoints.len().try_into()?);
+
+                db.store_latest_indexed_cached_checkpoint_block(to)?;
+                from = to + 1;
+            }
+        })
+        .instrument(span)
+    }
+}
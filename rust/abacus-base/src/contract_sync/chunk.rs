@@ -0,0 +1,178 @@
+use std::{cmp::min, time::Duration};
+
+use prometheus::IntGauge;
+
+/// Windows returning fewer events than this are considered sparse
+const SPARSE_WINDOW_EVENTS: usize = 100;
+/// Consecutive sparse windows after which the chunk size grows
+const SPARSE_WINDOWS_BEFORE_GROWING: u32 = 10;
+/// The longest to wait before retrying a range query which failed for a
+/// reason unrelated to its size
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Fragments of the errors returned when a range query spans too many
+/// blocks, matches too many logs or takes too long to serve. Timeouts count
+/// too, as log queries over large ranges are the likeliest to time out.
+const RANGE_ERROR_PATTERNS: &[&str] = &[
+    "query returned more than",
+    "too many results",
+    "log response size exceeded",
+    "block range is too wide",
+    "block range too wide",
+    "maximum block range",
+    "range too large",
+    "range is too large",
+    "range limit",
+    "requested too many blocks",
+    "timed out",
+    "timeout",
+];
+
+/// Whether `err` suggests the range query should be retried over fewer blocks
+fn is_range_error(err: &eyre::Report) -> bool {
+    let err = format!("{:#}", err).to_lowercase();
+    RANGE_ERROR_PATTERNS
+        .iter()
+        .any(|pattern| err.contains(pattern))
+}
+
+/// The number of blocks to query at once. Halves whenever the provider
+/// rejects a range query as too large and grows back towards the configured
+/// chunk size while windows are sparse. Range queries failing for other
+/// reasons are retried after a growing backoff.
+#[derive(Debug)]
+pub(crate) struct AdaptiveChunkSize {
+    max: u32,
+    current: u32,
+    sparse_windows: u32,
+    /// Consecutive range queries which failed for other reasons
+    failures: u32,
+    gauge: IntGauge,
+}
+
+impl AdaptiveChunkSize {
+    /// Start at the configured chunk size, which is never exceeded
+    pub(crate) fn new(max: u32, gauge: IntGauge) -> Self {
+        gauge.set(max.into());
+        Self {
+            max,
+            current: max,
+            sparse_windows: 0,
+            failures: 0,
+            gauge,
+        }
+    }
+
+    /// The current chunk size
+    pub(crate) fn get(&self) -> u32 {
+        self.current
+    }
+
+    /// Record that a window was indexed, returning `events` events
+    pub(crate) fn record_window(&mut self, events: usize) {
+        self.failures = 0;
+        if events >= SPARSE_WINDOW_EVENTS {
+            self.sparse_windows = 0;
+            return;
+        }
+        self.sparse_windows += 1;
+        if self.sparse_windows >= SPARSE_WINDOWS_BEFORE_GROWING && self.current < self.max {
+            self.set(min(self.max, self.current + self.current / 2 + 1));
+        }
+    }
+
+    /// Shrink the chunk size if `err` suggests the range query was too large
+    /// for the provider. Returns false if the error is unrelated to the size
+    /// of the range, or the range can't get any smaller.
+    pub(crate) fn shrink_for(&mut self, err: &eyre::Report) -> bool {
+        if self.current == 0 || !is_range_error(err) {
+            return false;
+        }
+        self.set(self.current / 2);
+        true
+    }
+
+    /// Record that a range query failed for a reason unrelated to its size,
+    /// returning how long to wait before retrying it. The wait doubles with
+    /// each consecutive failure.
+    pub(crate) fn record_failure(&mut self) -> Duration {
+        self.failures += 1;
+        min(
+            MAX_RETRY_BACKOFF,
+            Duration::from_secs(1 << min(self.failures, 6)),
+        )
+    }
+
+    fn set(&mut self, chunk_size: u32) {
+        self.current = chunk_size;
+        self.sparse_windows = 0;
+        self.gauge.set(chunk_size.into());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eyre::eyre;
+    use prometheus::IntGauge;
+
+    use super::*;
+
+    #[test]
+    fn it_shrinks_on_range_errors_and_grows_when_sparse() {
+        let gauge = IntGauge::new("chunk_size", "chunk size").unwrap();
+        let mut chunk_size = AdaptiveChunkSize::new(2000, gauge.clone());
+
+        assert!(!chunk_size.shrink_for(&eyre!("connection refused")));
+        assert!(!chunk_size.shrink_for(&eyre!("invalid block range params")));
+        assert!(chunk_size.shrink_for(&eyre!("query returned more than 10000 results")));
+        assert!(chunk_size.shrink_for(&eyre!("operation timed out")));
+        assert_eq!(chunk_size.get(), 500);
+        assert_eq!(gauge.get(), 500);
+
+        // Busy windows don't grow the chunk size
+        for _ in 0..SPARSE_WINDOWS_BEFORE_GROWING {
+            chunk_size.record_window(SPARSE_WINDOW_EVENTS);
+        }
+        assert_eq!(chunk_size.get(), 500);
+
+        for _ in 0..SPARSE_WINDOWS_BEFORE_GROWING {
+            chunk_size.record_window(0);
+        }
+        assert_eq!(chunk_size.get(), 751);
+
+        // Never beyond the configured chunk size
+        for _ in 0..SPARSE_WINDOWS_BEFORE_GROWING * 10 {
+            chunk_size.record_window(0);
+        }
+        assert_eq!(chunk_size.get(), 2000);
+        assert_eq!(gauge.get(), 2000);
+    }
+
+    #[test]
+    fn it_stops_shrinking_at_a_single_block() {
+        let gauge = IntGauge::new("chunk_size", "chunk size").unwrap();
+        let mut chunk_size = AdaptiveChunkSize::new(3, gauge);
+
+        assert!(chunk_size.shrink_for(&eyre!("block range too wide")));
+        assert!(chunk_size.shrink_for(&eyre!("block range too wide")));
+        assert!(!chunk_size.shrink_for(&eyre!("block range too wide")));
+        assert_eq!(chunk_size.get(), 0);
+    }
+
+    #[test]
+    fn it_backs_off_on_other_errors_until_a_window_is_indexed() {
+        let gauge = IntGauge::new("chunk_size", "chunk size").unwrap();
+        let mut chunk_size = AdaptiveChunkSize::new(2000, gauge);
+
+        assert_eq!(chunk_size.record_failure(), Duration::from_secs(2));
+        assert_eq!(chunk_size.record_failure(), Duration::from_secs(4));
+        for _ in 0..10 {
+            chunk_size.record_failure();
+        }
+        assert_eq!(chunk_size.record_failure(), MAX_RETRY_BACKOFF);
+
+        chunk_size.record_window(0);
+        assert_eq!(chunk_size.record_failure(), Duration::from_secs(2));
+        assert_eq!(chunk_size.get(), 2000);
+    }
+}
@@ -0,0 +1,329 @@
+use std::cmp::{max, min};
+use std::time::Duration;
+
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::{info, info_span, instrument::Instrumented, warn, Instrument};
+
+use abacus_core::{AbacusError, Decode, Encode, OutboxIndexer, RawCommittedMessageWithMeta};
+
+use crate::{
+    contract_sync::{chunk::AdaptiveChunkSize, schema::OutboxContractSyncDB},
+    ContractSync,
+};
+
+const MESSAGE_GAPS_LABEL: &str = "message_gaps";
+
+/// How often to check for a gap to heal
+const HEAL_INTERVAL: Duration = Duration::from_secs(5);
+/// The most leaf indices to filter for in a single query
+const MAX_LEAVES_PER_QUERY: u32 = 100;
+
+/// Messages which range queries have repeatedly missed, found by observing
+/// later leaf indices. The missing messages succeed the latest stored one,
+/// precede `end_leaf_index`, and were dispatched between `from_block` and
+/// `to_block`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MessageGap {
+    /// The highest leaf index observed past the gap
+    pub(crate) end_leaf_index: u32,
+    /// The first block the missing messages may have been dispatched in
+    pub(crate) from_block: u32,
+    /// The last block the missing messages may have been dispatched in
+    pub(crate) to_block: u32,
+}
+
+impl MessageGap {
+    /// A gap covering both `self` and `other`
+    pub(crate) fn merge(self, other: MessageGap) -> MessageGap {
+        MessageGap {
+            end_leaf_index: max(self.end_leaf_index, other.end_leaf_index),
+            from_block: min(self.from_block, other.from_block),
+            to_block: max(self.to_block, other.to_block),
+        }
+    }
+}
+
+impl Encode for MessageGap {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = 0;
+        written += self.end_leaf_index.write_to(writer)?;
+        written += self.from_block.write_to(writer)?;
+        written += self.to_block.write_to(writer)?;
+        Ok(written)
+    }
+}
+
+impl Decode for MessageGap {
+    fn read_from<R>(reader: &mut R) -> Result<Self, AbacusError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            end_leaf_index: u32::read_from(reader)?,
+            from_block: u32::read_from(reader)?,
+            to_block: u32::read_from(reader)?,
+        })
+    }
+}
+
+impl<I> ContractSync<I>
+where
+    I: OutboxIndexer + 'static,
+{
+    /// Heal the gaps message syncing finds in the leaf index sequence by
+    /// fetching the missing messages by their leaf index, rather than
+    /// waiting for a range query to return them. Only blocks which have
+    /// reached finality are searched, so healed messages are never rolled
+    /// back.
+    pub fn heal_message_gaps(&self) -> Instrumented<JoinHandle<eyre::Result<()>>> {
+        let span = info_span!("MessageGapHealer");
+
+        let db = self.db.clone();
+        let indexer = self.indexer.clone();
+
+        let healed_messages = self
+            .metrics
+            .stored_events
+            .with_label_values(&[MESSAGE_GAPS_LABEL, &self.chain_name]);
+
+        let healed_gaps = self
+            .metrics
+            .healed_gaps
+            .with_label_values(&[MESSAGE_GAPS_LABEL, &self.chain_name]);
+
+        let mut chunk_size = AdaptiveChunkSize::new(
+            self.index_settings.chunk_size(),
+            self.metrics
+                .chunk_size
+                .with_label_values(&[MESSAGE_GAPS_LABEL, &self.chain_name]),
+        );
+
+        tokio::spawn(async move {
+            loop {
+                sleep(HEAL_INTERVAL).await;
+
+                let gap = match db.retrieve_message_gap() {
+                    Some(gap) => gap,
+                    None => continue,
+                };
+
+                // The gap may have been filled by syncing in the meantime
+                let first = db.retrieve_latest_leaf_index()?.map_or(0, |i| i + 1);
+                if first >= gap.end_leaf_index {
+                    info!(gap = ?gap, "[MessageGaps]: Gap was filled by syncing");
+                    db.store_message_gap(None)?;
+                    continue;
+                }
+
+                let finalized_tip = if let Ok(num) = indexer.get_finalized_block_number().await {
+                    num
+                } else {
+                    continue;
+                };
+
+                let leaf_indices: Vec<u32> =
+                    (first..min(gap.end_leaf_index, first + MAX_LEAVES_PER_QUERY)).collect();
+                let to = min(gap.to_block, finalized_tip);
+                let messages = match fetch_messages_by_leaf_index(
+                    indexer.as_ref(),
+                    &mut chunk_size,
+                    &leaf_indices,
+                    gap.from_block,
+                    to,
+                )
+                .await
+                {
+                    Ok(messages) => messages,
+                    Err(err) => {
+                        warn!(gap = ?gap, error = ?err, "[MessageGaps]: Failed to fetch missing messages, retrying");
+                        continue;
+                    }
+                };
+
+                // Only store messages continuing the latest stored one, so that
+                // the stored leaf indices remain contiguous
+                let healed: Vec<_> = messages
+                    .into_iter()
+                    .zip(first..)
+                    .take_while(|(m, leaf_index)| m.message.leaf_index == *leaf_index)
+                    .map(|(m, _)| m)
+                    .collect();
+                if healed.is_empty() {
+                    warn!(
+                        leaf_index = first,
+                        from = gap.from_block,
+                        to = to,
+                        "[MessageGaps]: Missing message not found in blocks {}...{to}, retrying",
+                        gap.from_block,
+                    );
+                    continue;
+                }
+
+                db.store_messages(&healed)?;
+                healed_messages.add(healed.len().try_into()?);
+                let healed_through = first + healed.len() as u32 - 1;
+                info!(
+                    from_leaf_index = first,
+                    to_leaf_index = healed_through,
+                    "[MessageGaps]: Healed messages {first}...{healed_through}"
+                );
+
+                if healed_through + 1 >= gap.end_leaf_index {
+                    healed_gaps.inc();
+                    db.store_message_gap(None)?;
+                    info!(gap = ?gap, "[MessageGaps]: Healed gap");
+                }
+            }
+        })
+        .instrument(span)
+    }
+}
+
+/// Fetch the messages with `leaf_indices` dispatched between blocks `from`
+/// and `to`, sorted and deduplicated by leaf index
+async fn fetch_messages_by_leaf_index<I: OutboxIndexer>(
+    indexer: &I,
+    chunk_size: &mut AdaptiveChunkSize,
+    leaf_indices: &[u32],
+    mut from: u32,
+    to: u32,
+) -> eyre::Result<Vec<RawCommittedMessageWithMeta>> {
+    let mut messages = vec![];
+    while from <= to {
+        let chunk_to = min(to, from + chunk_size.get());
+        match indexer
+            .fetch_messages_by_leaf_index(leaf_indices, from, chunk_to)
+            .await
+        {
+            Ok(found) => {
+                chunk_size.record_window(found.len());
+                messages.extend(found);
+                from = chunk_to + 1;
+            }
+            // Retry with fewer blocks if the provider rejects the range
+            Err(err) if chunk_size.shrink_for(&err) => {
+                warn!(
+                    from = from,
+                    to = chunk_to,
+                    chunk_size = chunk_size.get(),
+                    error = ?err,
+                    "[MessageGaps]: Provider rejected block range, shrinking chunk size",
+                );
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    messages.sort_by_key(|m| m.message.leaf_index);
+    messages.dedup_by_key(|m| m.message.leaf_index);
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ethers::core::types::{H256, U256};
+    use tokio::time::{interval, timeout};
+
+    use abacus_core::{
+        db::AbacusDB, AbacusMessage, Encode, MessageDispatchMeta, RawCommittedMessage,
+        RawCommittedMessageWithMeta,
+    };
+    use abacus_test::mocks::indexer::MockAbacusIndexer;
+    use abacus_test::test_utils;
+
+    use super::MessageGap;
+    use crate::contract_sync::schema::OutboxContractSyncDB;
+    use crate::ContractSync;
+    use crate::{settings::IndexSettings, ContractSyncMetrics, CoreMetrics};
+
+    #[tokio::test]
+    async fn heals_gaps_by_leaf_index() {
+        test_utils::run_test_db(|db| async move {
+            let committed_message =
+                |leaf_index: u32, block_number: u64| RawCommittedMessageWithMeta {
+                    message: RawCommittedMessage {
+                        leaf_index,
+                        message: AbacusMessage::default().to_vec(),
+                    },
+                    meta: MessageDispatchMeta {
+                        block_number,
+                        block_hash: H256::zero(),
+                        timestamp: 0,
+                        transaction_hash: H256::from_low_u64_be(leaf_index.into()),
+                        log_index: U256::zero(),
+                    },
+                };
+
+            let abacus_db = AbacusDB::new("outbox_1", db);
+            abacus_db
+                .store_messages(&[committed_message(0, 100)])
+                .unwrap();
+            abacus_db
+                .store_message_gap(Some(MessageGap {
+                    end_leaf_index: 3,
+                    from_block: 100,
+                    to_block: 120,
+                }))
+                .unwrap();
+
+            // Messages 1 and 2 were dispatched in blocks 105 and 115
+            let mut mock_indexer = MockAbacusIndexer::new();
+            mock_indexer
+                .expect__get_finalized_block_number()
+                .returning(|| Ok(200));
+            mock_indexer
+                .expect__fetch_messages_by_leaf_index()
+                .withf(|leaf_indices, _, _| leaf_indices.to_vec() == vec![1, 2])
+                .returning(move |_, from, to| {
+                    Ok([committed_message(1, 105), committed_message(2, 115)]
+                        .into_iter()
+                        .filter(|m| (from..=to).contains(&(m.meta.block_number as u32)))
+                        .collect())
+                });
+
+            let metrics = Arc::new(
+                CoreMetrics::new("contract_sync_test", None, prometheus::Registry::new())
+                    .expect("could not make metrics"),
+            );
+
+            let contract_sync = ContractSync::new(
+                "outbox_1".into(),
+                abacus_db.clone(),
+                Arc::new(mock_indexer),
+                IndexSettings {
+                    from: Some("0".to_string()),
+                    chunk: Some("9".to_string()),
+                    optimistic: None,
+                    checkpoints: None,
+                },
+                ContractSyncMetrics::new(metrics),
+            );
+
+            let heal_task = contract_sync.heal_message_gaps();
+            let healed = timeout(Duration::from_secs(30), async move {
+                let mut interval = interval(Duration::from_millis(20));
+                while abacus_db.retrieve_message_gap().is_some() {
+                    interval.tick().await;
+                }
+                abacus_db
+            })
+            .await;
+            heal_task.into_inner().abort();
+
+            let abacus_db = healed.expect("timed out");
+            assert_eq!(abacus_db.retrieve_latest_leaf_index().unwrap(), Some(2));
+            let meta = abacus_db
+                .message_dispatch_meta_by_leaf_index(2)
+                .unwrap()
+                .unwrap();
+            assert_eq!(meta.block_number, 115);
+        })
+        .await
+    }
+}
@@ -0,0 +1,65 @@
+use abacus_core::InboxIndexer;
+
+use tokio::task::JoinHandle;
+use tracing::{info_span, instrument::Instrumented, Instrument};
+
+use crate::{
+    contract_sync::{chunk::AdaptiveChunkSize, range_sync::RangeSync, schema::InboxContractSyncDB},
+    ContractSync,
+};
+
+const DELIVERED_MESSAGES_LABEL: &str = "delivered_messages";
+
+impl<I> ContractSync<I>
+where
+    I: InboxIndexer + 'static,
+{
+    /// Sync the messages delivered to the inbox
+    pub fn sync_delivered_messages(&self) -> Instrumented<JoinHandle<eyre::Result<()>>> {
+        let span = info_span!("DeliveredMessageContractSync");
+
+        let db = self.db.clone();
+        let indexer = self.indexer.clone();
+
+        let sync = RangeSync {
+            name: "DeliveredMessages",
+            indexer: indexer.clone(),
+            db: db.clone(),
+            from: db
+                .retrieve_latest_indexed_delivered_message_block()
+                .map_or_else(|| self.index_settings.from(), |b| b + 1),
+            optimistic: self.index_settings.optimistic(),
+            chunk_size: AdaptiveChunkSize::new(
+                self.index_settings.chunk_size(),
+                self.metrics
+                    .chunk_size
+                    .with_label_values(&[DELIVERED_MESSAGES_LABEL, &self.chain_name]),
+            ),
+            indexed_height: self
+                .metrics
+                .indexed_height
+                .with_label_values(&[DELIVERED_MESSAGES_LABEL, &self.chain_name]),
+            stored_events: self
+                .metrics
+                .stored_events
+                .with_label_values(&[DELIVERED_MESSAGES_LABEL, &self.chain_name]),
+        };
+
+        tokio::spawn(async move {
+            // Ranges indexed before they reached finality, which may yet be reorged out
+            let unfinalized = db.retrieve_unfinalized_delivered_message_ranges();
+            sync.run(
+                unfinalized,
+                |from, to| {
+                    let indexer = indexer.clone();
+                    async move { indexer.fetch_processed_messages(from, to).await }
+                },
+                |leaf, _, finalized| Ok(db.store_delivered_message(*leaf, finalized)?),
+                |leaf| Ok(db.finalize_delivered_message(*leaf)?),
+                |leaf| Ok(db.remove_delivered_message(*leaf)?),
+            )
+            .await
+        })
+        .instrument(span)
+    }
+}
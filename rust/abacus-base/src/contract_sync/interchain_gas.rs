@@ -1,12 +1,15 @@
 use abacus_core::InterchainGasPaymasterIndexer;
 
-use tokio::{task::JoinHandle, time::sleep};
-use tracing::{debug, info, info_span, instrument::Instrumented, Instrument};
+use tokio::task::JoinHandle;
+use tracing::{info_span, instrument::Instrumented, Instrument};
 
-use std::cmp::min;
-use std::time::Duration;
-
-use crate::{contract_sync::schema::InterchainGasPaymasterContractSyncDB, ContractSync};
+use crate::{
+    contract_sync::{
+        chunk::AdaptiveChunkSize, range_sync::RangeSync,
+        schema::InterchainGasPaymasterContractSyncDB,
+    },
+    ContractSync,
+};
 
 const GAS_PAYMENTS_LABEL: &str = "gas_payments";
 
@@ -21,63 +24,48 @@ where
         let db = self.db.clone();
         let indexer = self.indexer.clone();
 
-        let indexed_height = self
-            .metrics
-            .indexed_height
-            .with_label_values(&[GAS_PAYMENTS_LABEL, &self.chain_name]);
-
-        let stored_messages = self
-            .metrics
-            .stored_events
-            .with_label_values(&[GAS_PAYMENTS_LABEL, &self.chain_name]);
-
-        let config_from = self.index_settings.from();
-        let chunk_size = self.index_settings.chunk_size();
-
-        tokio::spawn(async move {
-            let mut from = db
+        let sync = RangeSync {
+            name: "GasPayments",
+            indexer: indexer.clone(),
+            db: db.clone(),
+            from: db
                 .retrieve_latest_indexed_gas_payment_block()
-                .map_or_else(|| config_from, |b| b + 1);
-
-            info!(from = from, "[GasPayments]: resuming indexer from {from}");
-
-            loop {
-                indexed_height.set(from.into());
+                .map_or_else(|| self.index_settings.from(), |b| b + 1),
+            optimistic: self.index_settings.optimistic(),
+            chunk_size: AdaptiveChunkSize::new(
+                self.index_settings.chunk_size(),
+                self.metrics
+                    .chunk_size
+                    .with_label_values(&[GAS_PAYMENTS_LABEL, &self.chain_name]),
+            ),
+            indexed_height: self
+                .metrics
+                .indexed_height
+                .with_label_values(&[GAS_PAYMENTS_LABEL, &self.chain_name]),
+            stored_events: self
+                .metrics
+                .stored_events
+                .with_label_values(&[GAS_PAYMENTS_LABEL, &self.chain_name]),
+        };
 
-                // Only index blocks considered final.
-                // If there's an error getting the block number, just start the loop over
-                let tip = if let Ok(num) = indexer.get_finalized_block_number().await {
-                    num
-                } else {
-                    continue;
-                };
-                if tip <= from {
-                    debug!(tip=?tip, from=?from, "[GasPayments]: caught up to tip, waiting for new block");
-                    // Sleep if caught up to tip
-                    sleep(Duration::from_secs(1)).await;
-                    continue;
-                }
-
-                let candidate = from + chunk_size;
-                let to = min(tip, candidate);
-
-                let gas_payments = indexer.fetch_gas_payments(from, to).await?;
-
-                info!(
-                    from = from,
-                    to = to,
-                    gas_payments_count = gas_payments.len(),
-                    "[GasPayments]: indexed block heights {from}...{to}"
-                );
-
-                for gas_payment in gas_payments.iter() {
-                    db.process_gas_payment(gas_payment)?;
-                }
-                stored_messages.add(gas_payments.len().try_into()?);
-
-                db.store_latest_indexed_gas_payment_block(to)?;
-                from = to + 1;
-            }
+        tokio::spawn(async move {
+            // Ranges indexed before they reached finality, which may yet be reorged out
+            let unfinalized = db.retrieve_unfinalized_gas_payment_ranges();
+            sync.run(
+                unfinalized,
+                |from, to| {
+                    let indexer = indexer.clone();
+                    async move { indexer.fetch_gas_payments(from, to).await }
+                },
+                |gas_payment, indexed_through, _| {
+                    Ok(db.process_gas_payment(gas_payment, indexed_through)?)
+                },
+                // Payments from unfinalized blocks are told apart by the
+                // unfinalized ranges they're stored in
+                |_| Ok(()),
+                |gas_payment| Ok(db.rollback_gas_payment(gas_payment)?),
+            )
+            .await
         })
         .instrument(span)
     }
@@ -1,4 +1,4 @@
-use abacus_core::{ListValidity, RawCommittedMessage};
+use abacus_core::{ListValidity, RawCommittedMessageWithMeta};
 
 /// Optional latest leaf index struct. Optional struct to account for
 /// possibility that ContractSync is still yet to see it's first message. We
@@ -28,7 +28,10 @@ impl AsRef<Option<u32>> for OptLatestLeafIndex {
 
 impl OptLatestLeafIndex {
     /// Check if the list of sorted messages is a valid continuation of the OptLatestLeafIndex. If self is Some, check the validity of the list in continuation of self. If self is None, check the validity of just the list.
-    pub fn valid_continuation(&self, sorted_messages: &[RawCommittedMessage]) -> ListValidity {
+    pub fn valid_continuation(
+        &self,
+        sorted_messages: &[RawCommittedMessageWithMeta],
+    ) -> ListValidity {
         if sorted_messages.is_empty() {
             return ListValidity::Empty;
         }
@@ -38,7 +41,7 @@ impl OptLatestLeafIndex {
         if let Some(last_seen) = self.as_ref() {
             let has_desired_message = sorted_messages
                 .iter()
-                .any(|message| *last_seen == message.leaf_index - 1);
+                .any(|m| *last_seen == m.message.leaf_index - 1);
             if !has_desired_message {
                 return ListValidity::InvalidContinuation;
             }
@@ -46,7 +49,7 @@ impl OptLatestLeafIndex {
 
         // Ensure no gaps in new batch of leaves
         for pair in sorted_messages.windows(2) {
-            if pair[0].leaf_index != pair[1].leaf_index - 1 {
+            if pair[0].message.leaf_index != pair[1].message.leaf_index - 1 {
                 return ListValidity::ContainsGaps;
             }
         }
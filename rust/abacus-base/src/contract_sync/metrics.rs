@@ -13,8 +13,14 @@ pub struct ContractSyncMetrics {
     /// Unique occasions when agent missed an event (label values
     /// differentiate checkpoints vs. messages)
     pub missed_events: IntCounterVec,
+    /// Gaps in the events stored into DB which were healed by fetching the
+    /// missing events directly
+    pub healed_gaps: IntCounterVec,
     /// A gauge for tracking the latest message leafs that are being indexed
     pub message_leaf_index: IntGaugeVec,
+    /// Number of blocks currently queried at once (label values
+    /// differentiate checkpoints vs. messages)
+    pub chunk_size: IntGaugeVec,
 }
 
 impl ContractSyncMetrics {
@@ -44,13 +50,31 @@ impl ContractSyncMetrics {
             )
             .expect("failed to register missed_events metric");
 
+        let healed_gaps = metrics
+            .new_int_counter(
+                "contract_sync_healed_gaps",
+                "Number of gaps in stored events healed by fetching the missing events directly",
+                &["data_type", "chain"],
+            )
+            .expect("failed to register healed_gaps metric");
+
         let message_leaf_index = metrics.last_known_message_leaf_index();
 
+        let chunk_size = metrics
+            .new_int_gauge(
+                "contract_sync_chunk_size",
+                "Number of blocks currently queried at once",
+                &["data_type", "chain"],
+            )
+            .expect("failed to register chunk_size metric");
+
         ContractSyncMetrics {
             indexed_height,
             stored_events,
             missed_events,
+            healed_gaps,
             message_leaf_index,
+            chunk_size,
         }
     }
 }
@@ -6,15 +6,29 @@ use std::sync::Arc;
 use crate::settings::IndexSettings;
 use abacus_core::db::AbacusDB;
 
+mod cached_checkpoints;
+mod chunk;
+mod gaps;
+mod inbox;
 mod interchain_gas;
 mod last_message;
 mod metrics;
 mod outbox;
+mod range_sync;
+mod reorg;
 mod schema;
 
+pub use cached_checkpoints::*;
+pub use gaps::*;
+pub use inbox::*;
 pub use interchain_gas::*;
 pub use metrics::ContractSyncMetrics;
 pub use outbox::*;
+pub(crate) use schema::{
+    finalized_gas_payments, gas_payments_reindexed_from, LATEST_INDEXED_GAS_PAYMENT_BLOCK,
+    LATEST_VALID_MESSAGE_RANGE_START_BLOCK,
+};
+pub use schema::{store_sync_cursor, sync_cursors};
 
 /// Entity that drives the syncing of an agent's db with on-chain data.
 /// Extracts chain-specific data (emitted checkpoints, messages, etc) from an
@@ -1,14 +1,22 @@
 use std::cmp::min;
-use std::time::Duration;
 
 use tokio::time::sleep;
 use tracing::{debug, info, info_span, warn};
 use tracing::{instrument::Instrumented, Instrument};
 
-use abacus_core::{chain_from_domain, CommittedMessage, ListValidity, OutboxIndexer};
+use abacus_core::{
+    chain_from_domain, db::AbacusDB, CommittedMessage, ListValidity, OutboxIndexer,
+    RawCommittedMessageWithMeta,
+};
 
 use crate::{
-    contract_sync::{last_message::OptLatestLeafIndex, schema::OutboxContractSyncDB},
+    contract_sync::{
+        chunk::AdaptiveChunkSize,
+        gaps::MessageGap,
+        last_message::OptLatestLeafIndex,
+        reorg::{can_detect_reorgs, UnfinalizedRange, UnfinalizedRanges},
+        schema::OutboxContractSyncDB,
+    },
     ContractSync,
 };
 
@@ -43,7 +51,13 @@ where
         let chain_name = self.chain_name.clone();
 
         let config_from = self.index_settings.from();
-        let chunk_size = self.index_settings.chunk_size();
+        let optimistic = self.index_settings.optimistic();
+        let mut chunk_size = AdaptiveChunkSize::new(
+            self.index_settings.chunk_size(),
+            self.metrics
+                .chunk_size
+                .with_label_values(&[MESSAGES_LABEL, &self.chain_name]),
+        );
 
         // Indexes messages by fetching messages in ranges of blocks.
         // We've observed occasional flakiness with providers where some events in
@@ -87,28 +101,102 @@ where
 
             let mut last_valid_range_start_block = from;
 
-            info!(from = from, "[Messages]: resuming indexer from latest valid message range start block");
+            // Ranges indexed before they reached finality, which may yet be reorged out
+            let mut unfinalized = db.retrieve_unfinalized_message_ranges();
+            let optimistic = optimistic && can_detect_reorgs(indexer.as_ref()).await;
+
+            info!(from = from, optimistic = optimistic, "[Messages]: resuming indexer from latest valid message range start block");
 
             loop {
                 indexed_height.set(from as i64);
 
-                // Only index blocks considered final.
                 // If there's an error getting the block number, just start the loop over
-                let tip = if let Ok(num) = indexer.get_finalized_block_number().await {
+                let finalized_tip = if let Ok(num) = indexer.get_finalized_block_number().await {
+                    num
+                } else {
+                    continue;
+                };
+
+                // Roll back messages from any blocks which were reorged out, and stop
+                // tracking blocks which have reached finality.
+                match reconcile_unfinalized_messages(&db, indexer.as_ref(), &mut unfinalized, finalized_tip).await {
+                    Ok(Some(reorged_from)) => {
+                        from = min(from, reorged_from);
+                        last_valid_range_start_block = min(last_valid_range_start_block, from);
+                        db.store_latest_valid_message_range_start_block(last_valid_range_start_block)?;
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        warn!(error = ?err, "[Messages]: Failed to check for reorgs");
+                        continue;
+                    }
+                }
+
+                // Only index blocks considered final, unless indexing optimistically.
+                let tip = if !optimistic {
+                    finalized_tip
+                } else if let Ok(num) = indexer.get_latest_block_number().await {
                     num
                 } else {
                     continue;
                 };
                 if tip <= from {
-                    // Sleep if caught up to tip
-                    sleep(Duration::from_secs(1)).await;
+                    // Wait for a new block if caught up to tip
+                    if optimistic {
+                        indexer.wait_for_block_after(from).await;
+                    } else {
+                        indexer.wait_for_finalized_block_after(from).await;
+                    }
                     continue;
                 }
 
                 // Index the chunk_size, capping at the tip.
-                let to = min(tip, from + chunk_size);
+                let to = min(tip, from + chunk_size.get());
 
-                let mut sorted_messages = indexer.fetch_sorted_messages(from, to).await?;
+                // Note the hash of the range's last block if it may yet be reorged out
+                let hash = if to > finalized_tip {
+                    match indexer.get_block_hash(to).await {
+                        Ok(Some(hash)) => Some(hash),
+                        _ => continue,
+                    }
+                } else {
+                    None
+                };
+
+                // Retry with fewer blocks if the provider rejects the range
+                let mut sorted_messages = match indexer.fetch_sorted_messages(from, to).await {
+                    Ok(messages) => messages,
+                    Err(err) if chunk_size.shrink_for(&err) => {
+                        warn!(
+                            from = from,
+                            to = to,
+                            chunk_size = chunk_size.get(),
+                            error = ?err,
+                            "[Messages]: Provider rejected block range, shrinking chunk size",
+                        );
+                        continue;
+                    }
+                    Err(err) => {
+                        let backoff = chunk_size.record_failure();
+                        warn!(
+                            from = from,
+                            to = to,
+                            error = ?err,
+                            backoff = ?backoff,
+                            "[Messages]: Failed to fetch messages, retrying",
+                        );
+                        sleep(backoff).await;
+                        continue;
+                    }
+                };
+                chunk_size.record_window(sorted_messages.len());
+
+                // Index the range again if it was reorged while being indexed
+                if let Some(hash) = hash {
+                    if !matches!(indexer.get_block_hash(to).await, Ok(Some(latest)) if latest == hash) {
+                        continue;
+                    }
+                }
 
                 info!(
                     from = from,
@@ -124,7 +212,7 @@ where
                 // Filter out any messages that have already been successfully indexed and stored.
                 // This is necessary if we're re-indexing blocks in hope of finding missing messages.
                 if let Some(min_index) = last_leaf_index.as_ref() {
-                    sorted_messages = sorted_messages.into_iter().filter(|m| m.leaf_index > *min_index).collect();
+                    sorted_messages = sorted_messages.into_iter().filter(|m| m.message.leaf_index > *min_index).collect();
                 }
 
                 debug!(
@@ -139,6 +227,10 @@ where
                 // if the range was correctly indexed if there are no messages to observe their
                 // indices.
                 if sorted_messages.is_empty() {
+                    if let Some(hash) = hash {
+                        let range = UnfinalizedRange { from, to, hash, events: vec![] };
+                        record_unfinalized_messages(&db, &mut unfinalized, range)?;
+                    }
                     from = to + 1;
                     continue;
                 }
@@ -146,6 +238,14 @@ where
                 // Ensure the sorted messages are a valid continuation of last_leaf_index
                 match &last_leaf_index.valid_continuation(&sorted_messages) {
                     ListValidity::Valid => {
+                        // Note the messages may be reorged out before storing them, so that
+                        // they're rolled back even if the agent stops in between
+                        if let Some(hash) = hash {
+                            let events = sorted_messages.iter().map(|m| m.message.leaf_index).collect();
+                            let range = UnfinalizedRange { from, to, hash, events };
+                            record_unfinalized_messages(&db, &mut unfinalized, range)?;
+                        }
+
                         // Store messages
                         let max_leaf_index_of_batch = db.store_messages(&sorted_messages)?;
 
@@ -154,7 +254,7 @@ where
 
                         // Report latest leaf index to gauge by dst
                         for raw_msg in sorted_messages.iter() {
-                            let dst = CommittedMessage::try_from(raw_msg)
+                            let dst = CommittedMessage::try_from(&raw_msg.message)
                                 .ok()
                                 .and_then(|msg| chain_from_domain(msg.message.destination))
                                 .unwrap_or("unknown");
@@ -183,6 +283,7 @@ where
                             "[Messages]: Found invalid continuation in range. Re-indexing from the start block of the last successful range.",
                         );
 
+                        record_message_gap(&db, &last_leaf_index, &sorted_messages, config_from, to)?;
                         from = last_valid_range_start_block;
                     }
                     ListValidity::ContainsGaps => {
@@ -194,6 +295,7 @@ where
                             end_block = to,
                             "[Messages]: Found gaps in the messages in range, re-indexing the same range.",
                         );
+                        record_message_gap(&db, &last_leaf_index, &sorted_messages, config_from, to)?;
                     }
                     ListValidity::Empty => unreachable!("Tried to validate empty list of messages"),
                 };
@@ -203,18 +305,98 @@ where
     }
 }
 
+/// Roll back the messages stored from ranges which were reorged out, and
+/// stop tracking ranges which have reached finality. Returns the block to
+/// resume indexing from if any range was reorged out.
+async fn reconcile_unfinalized_messages<I: OutboxIndexer>(
+    db: &AbacusDB,
+    indexer: &I,
+    unfinalized: &mut UnfinalizedRanges<u32>,
+    finalized_block: u32,
+) -> eyre::Result<Option<u32>> {
+    let reorged = match unfinalized.take_reorged(indexer).await? {
+        Some(reorged) => reorged,
+        None => return Ok(None),
+    };
+    if let Some(leaf_index) = reorged.iter().flat_map(|r| r.events.iter()).min() {
+        db.rollback_messages(*leaf_index)?;
+    }
+    if !unfinalized.finalize(finalized_block).is_empty() || !reorged.is_empty() {
+        db.store_unfinalized_message_ranges(unfinalized)?;
+        db.store_lowest_unfinalized_leaf_index(unfinalized.events().min().copied())?;
+    }
+
+    let reorged_from = reorged.iter().map(|r| r.from).min();
+    if let Some(from) = reorged_from {
+        warn!(
+            from = from,
+            ranges = reorged.len(),
+            "[Messages]: Indexed blocks were reorged out, re-indexing from {from}"
+        );
+    }
+    Ok(reorged_from)
+}
+
+/// Record that messages succeeding `last_leaf_index` and preceding the last
+/// of `sorted_messages` were missed, so that they're fetched by leaf index.
+/// They must have been dispatched after the latest stored message, and no
+/// later than `to`.
+fn record_message_gap(
+    db: &AbacusDB,
+    last_leaf_index: &OptLatestLeafIndex,
+    sorted_messages: &[RawCommittedMessageWithMeta],
+    config_from: u32,
+    to: u32,
+) -> eyre::Result<()> {
+    let end_leaf_index = match sorted_messages.last() {
+        Some(m) => m.message.leaf_index,
+        None => return Ok(()),
+    };
+    let from_block = match last_leaf_index.as_ref() {
+        Some(leaf_index) => db
+            .message_dispatch_meta_by_leaf_index(*leaf_index)?
+            .map_or(config_from, |meta| meta.block_number as u32),
+        None => config_from,
+    };
+    let gap = MessageGap {
+        end_leaf_index,
+        from_block,
+        to_block: to,
+    };
+    let gap = db
+        .retrieve_message_gap()
+        .map_or(gap, |recorded| recorded.merge(gap));
+    db.store_message_gap(Some(gap))?;
+    Ok(())
+}
+
+/// Track a range, and the messages stored from it, until it reaches finality
+fn record_unfinalized_messages(
+    db: &AbacusDB,
+    unfinalized: &mut UnfinalizedRanges<u32>,
+    range: UnfinalizedRange<u32>,
+) -> eyre::Result<()> {
+    unfinalized.push(range);
+    db.store_unfinalized_message_ranges(unfinalized)?;
+    db.store_lowest_unfinalized_leaf_index(unfinalized.events().min().copied())?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
     use std::time::Duration;
 
-    use ethers::core::types::H256;
+    use ethers::core::types::{H256, U256};
     use eyre::eyre;
     use mockall::*;
     use tokio::select;
     use tokio::time::{interval, timeout};
 
-    use abacus_core::{db::AbacusDB, AbacusMessage, Encode, RawCommittedMessage};
+    use abacus_core::{
+        db::AbacusDB, AbacusMessage, Encode, MessageDispatchMeta, RawCommittedMessage,
+        RawCommittedMessageWithMeta,
+    };
     use abacus_test::mocks::indexer::MockAbacusIndexer;
     use abacus_test::test_utils;
     use mockall::predicate::eq;
@@ -237,35 +419,26 @@ mod test {
             .write_to(&mut message_vec)
             .expect("!write_to");
 
-            let m0 = RawCommittedMessage {
-                leaf_index: 0,
-                message: message_vec.clone(),
-            };
-
-            let m1 = RawCommittedMessage {
-                leaf_index: 1,
-                message: message_vec.clone(),
-            };
-
-            let m2 = RawCommittedMessage {
-                leaf_index: 2,
-                message: message_vec.clone(),
-            };
-
-            let m3 = RawCommittedMessage {
-                leaf_index: 3,
-                message: message_vec.clone(),
-            };
-
-            let m4 = RawCommittedMessage {
-                leaf_index: 4,
-                message: message_vec.clone(),
+            let committed_message = |leaf_index| RawCommittedMessageWithMeta {
+                message: RawCommittedMessage {
+                    leaf_index,
+                    message: message_vec.clone(),
+                },
+                meta: MessageDispatchMeta {
+                    block_number: 100,
+                    block_hash: H256::zero(),
+                    timestamp: 0,
+                    transaction_hash: H256::from_low_u64_be(leaf_index.into()),
+                    log_index: U256::zero(),
+                },
             };
 
-            let m5 = RawCommittedMessage {
-                leaf_index: 5,
-                message: message_vec.clone(),
-            };
+            let m0 = committed_message(0);
+            let m1 = committed_message(1);
+            let m2 = committed_message(2);
+            let m3 = committed_message(3);
+            let m4 = committed_message(4);
+            let m5 = committed_message(5);
 
             let latest_valid_message_range_start_block = 100;
 
@@ -444,6 +617,8 @@ mod test {
                 IndexSettings {
                     from: Some("0".to_string()),
                     chunk: Some("19".to_string()),
+                    optimistic: None,
+                    checkpoints: None,
                 },
                 sync_metrics,
             );
@@ -0,0 +1,405 @@
+use std::{cmp::min, future::Future, sync::Arc};
+
+use prometheus::IntGauge;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use abacus_core::{db::DbError, Indexer};
+
+use crate::contract_sync::{
+    chunk::AdaptiveChunkSize,
+    reorg::{can_detect_reorgs, UnfinalizedRange, UnfinalizedRanges},
+};
+
+/// Where a [`RangeSync`] records how far it has synced
+pub(crate) trait RangeSyncDB<E> {
+    /// Store the last block indexed, which syncing resumes after
+    fn store_latest_indexed_block(&self, block: u32) -> Result<(), DbError>;
+
+    /// Store the ranges indexed before they reached finality
+    fn store_unfinalized_ranges(&self, ranges: &UnfinalizedRanges<E>) -> Result<(), DbError>;
+}
+
+/// Syncs events from ranges of blocks up to the chain's tip, rolling back
+/// the events of ranges which are reorged out before reaching finality.
+/// How events are fetched and stored is left to the closures passed to
+/// [`RangeSync::run`].
+pub(crate) struct RangeSync<I, D> {
+    /// The name of the events, for logs
+    pub(crate) name: &'static str,
+    pub(crate) indexer: Arc<I>,
+    pub(crate) db: D,
+    /// The first block to index
+    pub(crate) from: u32,
+    /// Whether to index blocks before they reach finality
+    pub(crate) optimistic: bool,
+    pub(crate) chunk_size: AdaptiveChunkSize,
+    pub(crate) indexed_height: IntGauge,
+    pub(crate) stored_events: IntGauge,
+}
+
+impl<I, D> RangeSync<I, D>
+where
+    I: Indexer,
+{
+    /// Sync events forever, resuming with the `unfinalized` ranges stored by
+    /// a previous run.
+    ///
+    /// - `fetch` fetches the events emitted from a range of blocks
+    /// - `store` stores an event fetched from a range ending at the given
+    ///   block, noting whether its block has reached finality
+    /// - `finalize` records that an event's block has reached finality
+    /// - `rollback` forgets an event whose block was reorged out
+    pub(crate) async fn run<E, Fetch, FetchFut, Store, Finalize, Rollback>(
+        mut self,
+        mut unfinalized: UnfinalizedRanges<E>,
+        fetch: Fetch,
+        store: Store,
+        finalize: Finalize,
+        rollback: Rollback,
+    ) -> eyre::Result<()>
+    where
+        E: Clone,
+        D: RangeSyncDB<E>,
+        Fetch: Fn(u32, u32) -> FetchFut,
+        FetchFut: Future<Output = eyre::Result<Vec<E>>>,
+        Store: Fn(&E, u32, bool) -> eyre::Result<()>,
+        Finalize: Fn(&E) -> eyre::Result<()>,
+        Rollback: Fn(&E) -> eyre::Result<()>,
+    {
+        let name = self.name;
+        let indexer = self.indexer.clone();
+        let mut from = self.from;
+        let optimistic = self.optimistic && can_detect_reorgs(indexer.as_ref()).await;
+
+        info!(
+            events = name,
+            from, optimistic, "Resuming indexer from {from}"
+        );
+
+        loop {
+            self.indexed_height.set(from.into());
+
+            // If there's an error getting the block number, just start the loop over
+            let finalized_tip = if let Ok(num) = indexer.get_finalized_block_number().await {
+                num
+            } else {
+                continue;
+            };
+
+            // Roll back events from any blocks which were reorged out, and stop
+            // tracking blocks which have reached finality.
+            match self
+                .reconcile(&mut unfinalized, finalized_tip, &finalize, &rollback)
+                .await
+            {
+                Ok(Some(reorged_from)) if reorged_from < from => {
+                    from = reorged_from;
+                    self.db.store_latest_indexed_block(from.saturating_sub(1))?;
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    warn!(events = name, error = ?err, "Failed to check for reorgs");
+                    continue;
+                }
+            }
+
+            // Only index blocks considered final, unless indexing optimistically.
+            let tip = if !optimistic {
+                finalized_tip
+            } else if let Ok(num) = indexer.get_latest_block_number().await {
+                num
+            } else {
+                continue;
+            };
+            if tip <= from {
+                debug!(
+                    events = name,
+                    tip, from, "Caught up to tip, waiting for new block"
+                );
+                // Wait for a new block if caught up to tip
+                if optimistic {
+                    indexer.wait_for_block_after(from).await;
+                } else {
+                    indexer.wait_for_finalized_block_after(from).await;
+                }
+                continue;
+            }
+
+            let to = min(tip, from + self.chunk_size.get());
+
+            // Note the hash of the range's last block if it may yet be reorged out
+            let hash = if to > finalized_tip {
+                match indexer.get_block_hash(to).await {
+                    Ok(Some(hash)) => Some(hash),
+                    _ => continue,
+                }
+            } else {
+                None
+            };
+
+            // Retry with fewer blocks if the provider rejects the range
+            let events = match fetch(from, to).await {
+                Ok(events) => events,
+                Err(err) if self.chunk_size.shrink_for(&err) => {
+                    warn!(
+                        events = name,
+                        from,
+                        to,
+                        chunk_size = self.chunk_size.get(),
+                        error = ?err,
+                        "Provider rejected block range, shrinking chunk size",
+                    );
+                    continue;
+                }
+                Err(err) => {
+                    let backoff = self.chunk_size.record_failure();
+                    warn!(
+                        events = name,
+                        from,
+                        to,
+                        error = ?err,
+                        backoff = ?backoff,
+                        "Failed to fetch events, retrying",
+                    );
+                    sleep(backoff).await;
+                    continue;
+                }
+            };
+            self.chunk_size.record_window(events.len());
+
+            // Index the range again if it was reorged while being indexed
+            if let Some(hash) = hash {
+                if !matches!(indexer.get_block_hash(to).await, Ok(Some(latest)) if latest == hash) {
+                    continue;
+                }
+            }
+
+            info!(
+                events = name,
+                from,
+                to,
+                count = events.len(),
+                "Indexed block heights {from}...{to}"
+            );
+
+            // Note the events may be reorged out before storing them, so that
+            // they're rolled back even if the agent stops in between
+            if let Some(hash) = hash {
+                unfinalized.push(UnfinalizedRange {
+                    from,
+                    to,
+                    hash,
+                    events: events.clone(),
+                });
+                self.db.store_unfinalized_ranges(&unfinalized)?;
+            }
+            for event in events.iter() {
+                store(event, to, hash.is_none())?;
+            }
+            self.stored_events.add(events.len().try_into()?);
+
+            self.db.store_latest_indexed_block(to)?;
+            from = to + 1;
+        }
+    }
+
+    /// Roll back the events stored from ranges which were reorged out, and
+    /// finalize those from ranges which have reached finality. Returns the
+    /// block to resume indexing from if any range was reorged out.
+    async fn reconcile<E>(
+        &self,
+        unfinalized: &mut UnfinalizedRanges<E>,
+        finalized_block: u32,
+        finalize: impl Fn(&E) -> eyre::Result<()>,
+        rollback: impl Fn(&E) -> eyre::Result<()>,
+    ) -> eyre::Result<Option<u32>>
+    where
+        D: RangeSyncDB<E>,
+    {
+        let reorged = match unfinalized.take_reorged(self.indexer.as_ref()).await? {
+            Some(reorged) => reorged,
+            None => return Ok(None),
+        };
+        for event in reorged.iter().flat_map(|r| r.events.iter()) {
+            rollback(event)?;
+        }
+        let finalized = unfinalized.finalize(finalized_block);
+        for event in finalized.iter().flat_map(|r| r.events.iter()) {
+            finalize(event)?;
+        }
+        if !finalized.is_empty() || !reorged.is_empty() {
+            self.db.store_unfinalized_ranges(unfinalized)?;
+        }
+
+        let reorged_from = reorged.iter().map(|r| r.from).min();
+        if let Some(from) = reorged_from {
+            warn!(
+                events = self.name,
+                from,
+                ranges = reorged.len(),
+                "Indexed blocks were reorged out, re-indexing from {from}"
+            );
+        }
+        Ok(reorged_from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use async_trait::async_trait;
+    use ethers::core::types::H256;
+
+    use super::*;
+
+    /// A chain whose blocks each emit one event, the block's hash
+    #[derive(Debug, Default)]
+    struct TestChain {
+        finalized: Mutex<u32>,
+        hashes: Mutex<Vec<H256>>,
+    }
+
+    impl TestChain {
+        fn events(&self, from: u32, to: u32) -> Vec<H256> {
+            self.hashes.lock().unwrap()[from as usize..=to as usize].to_vec()
+        }
+
+        /// Replace the blocks from `from` onwards with `len` blocks of a fork
+        fn reorg(&self, from: u32, len: u32, fork: u64) {
+            let mut hashes = self.hashes.lock().unwrap();
+            hashes.truncate(from as usize);
+            hashes
+                .extend((from..from + len).map(|n| H256::from_low_u64_be((fork << 32) | n as u64)));
+        }
+    }
+
+    #[async_trait]
+    impl Indexer for TestChain {
+        async fn get_finalized_block_number(&self) -> eyre::Result<u32> {
+            Ok(*self.finalized.lock().unwrap())
+        }
+
+        async fn get_latest_block_number(&self) -> eyre::Result<u32> {
+            Ok(self.hashes.lock().unwrap().len() as u32 - 1)
+        }
+
+        async fn get_block_hash(&self, block_number: u32) -> eyre::Result<Option<H256>> {
+            Ok(self
+                .hashes
+                .lock()
+                .unwrap()
+                .get(block_number as usize)
+                .copied())
+        }
+    }
+
+    /// The events stored, and whether their blocks have reached finality
+    #[derive(Debug, Default)]
+    struct TestDB {
+        events: Mutex<HashMap<H256, bool>>,
+        unfinalized_events: Mutex<usize>,
+    }
+
+    impl RangeSyncDB<H256> for Arc<TestDB> {
+        fn store_latest_indexed_block(&self, _block: u32) -> Result<(), DbError> {
+            Ok(())
+        }
+
+        fn store_unfinalized_ranges(
+            &self,
+            ranges: &UnfinalizedRanges<H256>,
+        ) -> Result<(), DbError> {
+            *self.unfinalized_events.lock().unwrap() = ranges.events().count();
+            Ok(())
+        }
+    }
+
+    impl TestDB {
+        /// Assert the events stored are those of the chain, noting whether
+        /// their blocks have reached finality
+        fn assert_synced(&self, chain: &TestChain) {
+            let finalized = *chain.finalized.lock().unwrap() as usize;
+            let events = self.events.lock().unwrap();
+            let hashes = chain.hashes.lock().unwrap();
+            assert_eq!(events.len(), hashes.len());
+            for (block, hash) in hashes.iter().enumerate() {
+                let finalized_event = events[hash];
+                if block > finalized {
+                    assert!(!finalized_event, "block {block} is yet to reach finality");
+                }
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_rolls_back_and_finalizes_events() {
+        let chain = Arc::new(TestChain::default());
+        chain.reorg(0, 21, 0);
+        *chain.finalized.lock().unwrap() = 10;
+        let db = Arc::new(TestDB::default());
+
+        let sync = RangeSync {
+            name: "test_events",
+            indexer: chain.clone(),
+            db: db.clone(),
+            from: 0,
+            optimistic: true,
+            chunk_size: AdaptiveChunkSize::new(5, IntGauge::new("chunk", "chunk").unwrap()),
+            indexed_height: IntGauge::new("height", "height").unwrap(),
+            stored_events: IntGauge::new("events", "events").unwrap(),
+        };
+        let handle = {
+            let chain = chain.clone();
+            let (store_db, finalize_db, rollback_db) = (db.clone(), db.clone(), db.clone());
+            tokio::spawn(sync.run(
+                UnfinalizedRanges::default(),
+                move |from, to| {
+                    let events = chain.events(from, to);
+                    async move { Ok(events) }
+                },
+                move |event, _, finalized| {
+                    store_db.events.lock().unwrap().insert(*event, finalized);
+                    Ok(())
+                },
+                move |event| {
+                    *finalize_db.events.lock().unwrap().get_mut(event).unwrap() = true;
+                    Ok(())
+                },
+                move |event| {
+                    rollback_db.events.lock().unwrap().remove(event);
+                    Ok(())
+                },
+            ))
+        };
+
+        // Blocks are indexed before they reach finality
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        db.assert_synced(&chain);
+        assert!(*db.unfinalized_events.lock().unwrap() > 0);
+
+        // The events of blocks reorged out are replaced
+        chain.reorg(15, 8, 1);
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        db.assert_synced(&chain);
+
+        // Every event is finalized once its block reaches finality
+        *chain.finalized.lock().unwrap() = 22;
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        db.assert_synced(&chain);
+        assert!(db
+            .events
+            .lock()
+            .unwrap()
+            .values()
+            .all(|finalized| *finalized));
+        assert_eq!(*db.unfinalized_events.lock().unwrap(), 0);
+
+        handle.abort();
+    }
+}
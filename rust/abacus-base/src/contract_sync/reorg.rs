@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use ethers::core::types::H256;
+use tokio::time::sleep;
+use tracing::warn;
+
+use abacus_core::{AbacusError, Decode, Encode, Indexer};
+
+/// Whether `indexer` reports block hashes, which indexing blocks before they
+/// reach finality relies on to detect reorgs
+pub(crate) async fn can_detect_reorgs<I: Indexer>(indexer: &I) -> bool {
+    loop {
+        match indexer.get_block_hash(0).await {
+            Ok(Some(_)) => return true,
+            Ok(None) => {
+                warn!("Indexer can't detect reorgs, so only finalized blocks will be indexed");
+                return false;
+            }
+            Err(err) => {
+                warn!(error = ?err, "Failed to get a block hash, retrying");
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// A range of blocks indexed before it reached finality, with the events
+/// stored from it so that they can be rolled back if it's reorged out
+#[derive(Debug)]
+pub(crate) struct UnfinalizedRange<E> {
+    /// The first block of the range
+    pub(crate) from: u32,
+    /// The last block of the range
+    pub(crate) to: u32,
+    /// The hash of block `to` when the range was indexed
+    pub(crate) hash: H256,
+    /// The events stored from the range
+    pub(crate) events: Vec<E>,
+}
+
+/// The ranges of blocks indexed before they reached finality, ordered by
+/// their last block
+#[derive(Debug)]
+pub(crate) struct UnfinalizedRanges<E>(Vec<UnfinalizedRange<E>>);
+
+impl<E> Default for UnfinalizedRanges<E> {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl<E> UnfinalizedRanges<E> {
+    /// Record a range indexed before it reached finality
+    pub(crate) fn push(&mut self, range: UnfinalizedRange<E>) {
+        let position = self.0.partition_point(|r| r.to <= range.to);
+        self.0.insert(position, range);
+    }
+
+    /// Forget the ranges which have reached finality, returning them
+    pub(crate) fn finalize(&mut self, finalized_block: u32) -> Vec<UnfinalizedRange<E>> {
+        let position = self.0.partition_point(|r| r.to <= finalized_block);
+        self.0.drain(..position).collect()
+    }
+
+    /// The first block of the earliest range which has yet to reach
+    /// finality
+    pub(crate) fn first_block(&self) -> Option<u32> {
+        self.0.first().map(|r| r.from)
+    }
+
+    /// The events stored from every range which has yet to reach finality
+    pub(crate) fn events(&self) -> impl Iterator<Item = &E> {
+        self.0.iter().flat_map(|r| r.events.iter())
+    }
+
+    /// Remove and return the ranges which are no longer on the canonical
+    /// chain, latest first. As block hashes commit to their ancestors, the
+    /// chain only needs to be walked back until a range's last block
+    /// matches.
+    ///
+    /// Returns `None` if the chain has yet to reach the last block of the
+    /// latest range, so it can't yet be told whether it was reorged out.
+    pub(crate) async fn take_reorged<I>(
+        &mut self,
+        indexer: &I,
+    ) -> eyre::Result<Option<Vec<UnfinalizedRange<E>>>>
+    where
+        I: Indexer,
+    {
+        let mut reorged = vec![];
+        while let Some(&UnfinalizedRange { to, hash, .. }) = self.0.last() {
+            match indexer.get_block_hash(to).await? {
+                Some(canonical) if canonical == hash => break,
+                None if reorged.is_empty() => return Ok(None),
+                _ => reorged.extend(self.0.pop()),
+            }
+        }
+        Ok(Some(reorged))
+    }
+}
+
+impl<E: Encode> Encode for UnfinalizedRanges<E> {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = (self.0.len() as u32).write_to(writer)?;
+        for range in self.0.iter() {
+            written += range.from.write_to(writer)?;
+            written += range.to.write_to(writer)?;
+            written += range.hash.write_to(writer)?;
+            written += (range.events.len() as u32).write_to(writer)?;
+            for event in range.events.iter() {
+                written += event.write_to(writer)?;
+            }
+        }
+        Ok(written)
+    }
+}
+
+impl<E: Decode> Decode for UnfinalizedRanges<E> {
+    fn read_from<R>(reader: &mut R) -> Result<Self, AbacusError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        let count = u32::read_from(reader)?;
+        let mut ranges = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let from = u32::read_from(reader)?;
+            let to = u32::read_from(reader)?;
+            let hash = H256::read_from(reader)?;
+            let event_count = u32::read_from(reader)?;
+            let events = (0..event_count)
+                .map(|_| E::read_from(reader))
+                .collect::<Result<_, _>>()?;
+            ranges.push(UnfinalizedRange {
+                from,
+                to,
+                hash,
+                events,
+            });
+        }
+        Ok(Self(ranges))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use async_trait::async_trait;
+
+    use super::*;
+
+    /// An indexer serving the hashes of the blocks on the canonical chain
+    #[derive(Debug, Default)]
+    struct CanonicalChain(HashMap<u32, H256>);
+
+    #[async_trait]
+    impl Indexer for CanonicalChain {
+        async fn get_finalized_block_number(&self) -> eyre::Result<u32> {
+            Ok(0)
+        }
+
+        async fn get_block_hash(&self, block_number: u32) -> eyre::Result<Option<H256>> {
+            Ok(self.0.get(&block_number).copied())
+        }
+    }
+
+    fn range(from: u32, to: u32, hash: u64) -> UnfinalizedRange<u32> {
+        UnfinalizedRange {
+            from,
+            to,
+            hash: H256::from_low_u64_be(hash),
+            events: (from..=to).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_takes_the_ranges_reorged_out() {
+        let mut ranges = UnfinalizedRanges::default();
+        ranges.push(range(11, 20, 20));
+        ranges.push(range(1, 10, 10));
+        ranges.push(range(21, 30, 30));
+        assert_eq!(ranges.first_block(), Some(1));
+
+        // The latest range is still canonical, so neither are its ancestors
+        let mut chain = CanonicalChain::default();
+        chain.0.insert(30, H256::from_low_u64_be(30));
+        let reorged = ranges.take_reorged(&chain).await.unwrap().unwrap();
+        assert!(reorged.is_empty());
+        assert_eq!(ranges.events().count(), 30);
+
+        // Blocks 20 and 30 were replaced
+        chain.0.insert(10, H256::from_low_u64_be(10));
+        chain.0.insert(20, H256::from_low_u64_be(21));
+        chain.0.insert(30, H256::from_low_u64_be(31));
+        let reorged = ranges.take_reorged(&chain).await.unwrap().unwrap();
+        assert_eq!(
+            reorged.iter().map(|r| r.from).collect::<Vec<_>>(),
+            vec![21, 11]
+        );
+        assert_eq!(ranges.events().copied().max(), Some(10));
+
+        // The chain may be shorter than the ranges after a reorg
+        ranges.push(range(11, 20, 21));
+        chain.0.remove(&20);
+        assert!(ranges.take_reorged(&chain).await.unwrap().is_none());
+        assert_eq!(ranges.first_block(), Some(1));
+
+        // Ranges reaching finality are forgotten
+        let finalized = ranges.finalize(10);
+        assert_eq!(
+            finalized.iter().map(|r| r.from).collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(ranges.first_block(), Some(11));
+        assert!(ranges.finalize(10).is_empty());
+    }
+}
@@ -1,18 +1,116 @@
 use abacus_core::db::AbacusDB;
 use abacus_core::db::DbError;
+use abacus_core::{InterchainGasPaymentMeta, InterchainGasPaymentWithMeta};
+use ethers::core::types::{H256, U256};
 use eyre::Result;
 
+use crate::contract_sync::{gaps::MessageGap, range_sync::RangeSyncDB, reorg::UnfinalizedRanges};
+
 /// The start block number of the latest "valid" message block range.
 /// This is an interval of block indexes where > 0 messages were indexed,
 /// all of which had a contiguous sequence of messages based off their indices,
 /// and the lowest index is the successor to the highest index of the prior
 /// valid range.
-static LATEST_VALID_MESSAGE_RANGE_START_BLOCK: &str = "latest_valid_message_range_start_block";
-static LATEST_INDEXED_GAS_PAYMENT_BLOCK: &str = "latest_indexed_gas_payment_block";
+pub(crate) static LATEST_VALID_MESSAGE_RANGE_START_BLOCK: &str =
+    "latest_valid_message_range_start_block";
+static LATEST_INDEXED_CACHED_CHECKPOINT_BLOCK: &str = "latest_indexed_cached_checkpoint_block";
+pub(crate) static LATEST_INDEXED_GAS_PAYMENT_BLOCK: &str = "latest_indexed_gas_payment_block";
+static LATEST_INDEXED_DELIVERED_MESSAGE_BLOCK: &str = "latest_indexed_delivered_message_block";
+/// Block ranges indexed before they reached finality, along with the leaf
+/// indices or gas payments stored from them
+static UNFINALIZED_MESSAGE_RANGES: &str = "unfinalized_message_ranges";
+static UNFINALIZED_GAS_PAYMENT_RANGES: &str = "unfinalized_gas_payment_ranges";
+static UNFINALIZED_DELIVERED_MESSAGE_RANGES: &str = "unfinalized_delivered_message_ranges";
+/// Messages which range queries missed, to be fetched by their leaf index
+static MESSAGE_GAP: &str = "message_gap";
+
+/// The names of the blocks syncing resumes from
+const SYNC_CURSORS: &[&str] = &[
+    LATEST_VALID_MESSAGE_RANGE_START_BLOCK,
+    LATEST_INDEXED_CACHED_CHECKPOINT_BLOCK,
+    LATEST_INDEXED_GAS_PAYMENT_BLOCK,
+    LATEST_INDEXED_DELIVERED_MESSAGE_BLOCK,
+];
+
+/// The blocks syncing resumes from, by name, for inspecting a DB. Each is
+/// `None` if that sync has yet to run.
+pub fn sync_cursors(db: &AbacusDB) -> Result<Vec<(&'static str, Option<u32>)>, DbError> {
+    SYNC_CURSORS
+        .iter()
+        .map(|&name| Ok((name, db.retrieve_decodable("", name)?)))
+        .collect()
+}
+
+/// Set the block a sync resumes from, e.g. when restoring a DB from a
+/// snapshot
+pub fn store_sync_cursor(db: &AbacusDB, name: &str, block: u32) -> Result<()> {
+    if !SYNC_CURSORS.contains(&name) {
+        eyre::bail!("Unknown sync cursor {}", name);
+    }
+    db.store_encodable("", name, &block)?;
+    Ok(())
+}
+
+/// The gas payments stored from blocks which have reached finality
+pub(crate) struct FinalizedGasPayments {
+    /// The total gas payment for each leaf index
+    pub(crate) totals: Vec<(u32, U256)>,
+    /// The metadata of each gas payment processed
+    pub(crate) processed: Vec<InterchainGasPaymentMeta>,
+    /// The first block which hasn't reached finality, if gas payments were
+    /// stored from any
+    pub(crate) unfinalized_from: Option<u32>,
+}
+
+/// The gas payments stored from blocks which have reached finality
+pub(crate) fn finalized_gas_payments(db: &AbacusDB) -> Result<FinalizedGasPayments, DbError> {
+    let unfinalized = db.retrieve_unfinalized_gas_payment_ranges();
+    let mut totals = db.retrieve_gas_payments_by_leaf()?;
+    let mut processed = db.retrieve_processed_gas_payment_metas()?;
+    for event in unfinalized.events() {
+        if let Some((_, total)) = totals
+            .iter_mut()
+            .find(|(leaf_index, _)| *leaf_index == event.payment.leaf_index)
+        {
+            *total = total.saturating_sub(event.payment.amount);
+        }
+        processed.retain(|meta| *meta != event.meta);
+    }
+    totals.retain(|(_, total)| !total.is_zero());
+    Ok(FinalizedGasPayments {
+        totals,
+        processed,
+        unfinalized_from: unfinalized.first_block(),
+    })
+}
+
+/// The first block whose gas payments may be indexed again, because it
+/// hasn't been indexed yet or may still be reorged out, if any gas payments
+/// were indexed
+pub(crate) fn gas_payments_reindexed_from(db: &AbacusDB) -> Option<u32> {
+    let next = db.retrieve_latest_indexed_gas_payment_block()? + 1;
+    Some(
+        db.retrieve_unfinalized_gas_payment_ranges()
+            .first_block()
+            .map_or(next, |first| first.min(next)),
+    )
+}
 
 pub(crate) trait OutboxContractSyncDB {
     fn store_latest_valid_message_range_start_block(&self, block_num: u32) -> Result<(), DbError>;
     fn retrieve_latest_valid_message_range_start_block(&self) -> Option<u32>;
+    fn store_unfinalized_message_ranges(
+        &self,
+        ranges: &UnfinalizedRanges<u32>,
+    ) -> Result<(), DbError>;
+    fn retrieve_unfinalized_message_ranges(&self) -> UnfinalizedRanges<u32>;
+    fn store_latest_indexed_cached_checkpoint_block(
+        &self,
+        latest_block: u32,
+    ) -> Result<(), DbError>;
+    fn retrieve_latest_indexed_cached_checkpoint_block(&self) -> Option<u32>;
+    fn store_message_gap(&self, gap: Option<MessageGap>) -> Result<(), DbError>;
+    fn retrieve_message_gap(&self) -> Option<MessageGap>;
 }
 
 impl OutboxContractSyncDB for AbacusDB {
@@ -24,11 +122,55 @@ impl OutboxContractSyncDB for AbacusDB {
         self.retrieve_decodable("", LATEST_VALID_MESSAGE_RANGE_START_BLOCK)
             .expect("db failure")
     }
+
+    fn store_unfinalized_message_ranges(
+        &self,
+        ranges: &UnfinalizedRanges<u32>,
+    ) -> Result<(), DbError> {
+        self.store_encodable("", UNFINALIZED_MESSAGE_RANGES, ranges)
+    }
+
+    fn retrieve_unfinalized_message_ranges(&self) -> UnfinalizedRanges<u32> {
+        self.retrieve_decodable("", UNFINALIZED_MESSAGE_RANGES)
+            .expect("db failure")
+            .unwrap_or_default()
+    }
+
+    fn store_latest_indexed_cached_checkpoint_block(
+        &self,
+        latest_block: u32,
+    ) -> Result<(), DbError> {
+        self.store_encodable("", LATEST_INDEXED_CACHED_CHECKPOINT_BLOCK, &latest_block)
+    }
+
+    fn retrieve_latest_indexed_cached_checkpoint_block(&self) -> Option<u32> {
+        self.retrieve_decodable("", LATEST_INDEXED_CACHED_CHECKPOINT_BLOCK)
+            .expect("db failure")
+    }
+
+    fn store_message_gap(&self, gap: Option<MessageGap>) -> Result<(), DbError> {
+        match gap {
+            Some(gap) => self.store_encodable("", MESSAGE_GAP, &gap),
+            None => self.delete("", MESSAGE_GAP),
+        }
+    }
+
+    fn retrieve_message_gap(&self) -> Option<MessageGap> {
+        self.retrieve_decodable("", MESSAGE_GAP)
+            .expect("db failure")
+    }
 }
 
 pub(crate) trait InterchainGasPaymasterContractSyncDB {
     fn store_latest_indexed_gas_payment_block(&self, latest_block: u32) -> Result<(), DbError>;
     fn retrieve_latest_indexed_gas_payment_block(&self) -> Option<u32>;
+    fn store_unfinalized_gas_payment_ranges(
+        &self,
+        ranges: &UnfinalizedRanges<InterchainGasPaymentWithMeta>,
+    ) -> Result<(), DbError>;
+    fn retrieve_unfinalized_gas_payment_ranges(
+        &self,
+    ) -> UnfinalizedRanges<InterchainGasPaymentWithMeta>;
 }
 
 impl InterchainGasPaymasterContractSyncDB for AbacusDB {
@@ -40,4 +182,84 @@ impl InterchainGasPaymasterContractSyncDB for AbacusDB {
         self.retrieve_decodable("", LATEST_INDEXED_GAS_PAYMENT_BLOCK)
             .expect("db failure")
     }
+
+    fn store_unfinalized_gas_payment_ranges(
+        &self,
+        ranges: &UnfinalizedRanges<InterchainGasPaymentWithMeta>,
+    ) -> Result<(), DbError> {
+        self.store_encodable("", UNFINALIZED_GAS_PAYMENT_RANGES, ranges)
+    }
+
+    fn retrieve_unfinalized_gas_payment_ranges(
+        &self,
+    ) -> UnfinalizedRanges<InterchainGasPaymentWithMeta> {
+        self.retrieve_decodable("", UNFINALIZED_GAS_PAYMENT_RANGES)
+            .expect("db failure")
+            .unwrap_or_default()
+    }
+}
+
+/// The gas payment sync's progress
+impl RangeSyncDB<InterchainGasPaymentWithMeta> for AbacusDB {
+    fn store_latest_indexed_block(&self, block: u32) -> Result<(), DbError> {
+        self.store_latest_indexed_gas_payment_block(block)
+    }
+
+    fn store_unfinalized_ranges(
+        &self,
+        ranges: &UnfinalizedRanges<InterchainGasPaymentWithMeta>,
+    ) -> Result<(), DbError> {
+        self.store_unfinalized_gas_payment_ranges(ranges)
+    }
+}
+
+pub(crate) trait InboxContractSyncDB {
+    fn store_latest_indexed_delivered_message_block(
+        &self,
+        latest_block: u32,
+    ) -> Result<(), DbError>;
+    fn retrieve_latest_indexed_delivered_message_block(&self) -> Option<u32>;
+    fn store_unfinalized_delivered_message_ranges(
+        &self,
+        ranges: &UnfinalizedRanges<H256>,
+    ) -> Result<(), DbError>;
+    fn retrieve_unfinalized_delivered_message_ranges(&self) -> UnfinalizedRanges<H256>;
+}
+
+impl InboxContractSyncDB for AbacusDB {
+    fn store_latest_indexed_delivered_message_block(
+        &self,
+        latest_block: u32,
+    ) -> Result<(), DbError> {
+        self.store_encodable("", LATEST_INDEXED_DELIVERED_MESSAGE_BLOCK, &latest_block)
+    }
+
+    fn retrieve_latest_indexed_delivered_message_block(&self) -> Option<u32> {
+        self.retrieve_decodable("", LATEST_INDEXED_DELIVERED_MESSAGE_BLOCK)
+            .expect("db failure")
+    }
+
+    fn store_unfinalized_delivered_message_ranges(
+        &self,
+        ranges: &UnfinalizedRanges<H256>,
+    ) -> Result<(), DbError> {
+        self.store_encodable("", UNFINALIZED_DELIVERED_MESSAGE_RANGES, ranges)
+    }
+
+    fn retrieve_unfinalized_delivered_message_ranges(&self) -> UnfinalizedRanges<H256> {
+        self.retrieve_decodable("", UNFINALIZED_DELIVERED_MESSAGE_RANGES)
+            .expect("db failure")
+            .unwrap_or_default()
+    }
+}
+
+/// The delivered message sync's progress
+impl RangeSyncDB<H256> for AbacusDB {
+    fn store_latest_indexed_block(&self, block: u32) -> Result<(), DbError> {
+        self.store_latest_indexed_delivered_message_block(block)
+    }
+
+    fn store_unfinalized_ranges(&self, ranges: &UnfinalizedRanges<H256>) -> Result<(), DbError> {
+        self.store_unfinalized_delivered_message_ranges(ranges)
+    }
 }
@@ -0,0 +1,114 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use abacus_core::db::{AbacusDB, DB};
+use eyre::Result;
+use prometheus::IntCounter;
+use tokio::{
+    task::{spawn_blocking, JoinHandle},
+    time::{interval, MissedTickBehavior},
+};
+use tracing::{info, info_span, instrument::Instrumented, Instrument};
+
+use crate::{contract_sync::gas_payments_reindexed_from, CoreMetrics, RetentionSettings};
+
+/// Periodically prunes the message bodies and proofs of leaves which won't
+/// be relayed again, and the gas payments which won't be indexed again, from
+/// the DB, then compacts it to reclaim the space they took.
+#[derive(Debug)]
+pub struct DbPruner {
+    db: AbacusDB,
+    retained_leaves: u32,
+    relayed_destinations: Arc<HashSet<u32>>,
+    interval: Duration,
+    pruned_leaves: IntCounter,
+    reclaimed_bytes: IntCounter,
+}
+
+impl DbPruner {
+    /// Create a pruner for the data `db` stores for an outbox, whose
+    /// messages are relayed to `relayed_destinations`, or `None` if the
+    /// retention settings don't prune anything
+    pub fn new(
+        db: AbacusDB,
+        outbox_name: &str,
+        relayed_destinations: HashSet<u32>,
+        settings: &RetentionSettings,
+        metrics: &CoreMetrics,
+    ) -> Option<Self> {
+        let retained_leaves = settings.retained_leaves()?;
+        let pruned_leaves = metrics
+            .new_int_counter(
+                "db_pruned_leaves",
+                "Number of leaves whose message bodies and proofs were pruned",
+                &["chain"],
+            )
+            .expect("failed to register db_pruned_leaves metric")
+            .with_label_values(&[outbox_name]);
+        let reclaimed_bytes = metrics
+            .new_int_counter(
+                "db_reclaimed_bytes",
+                "Number of bytes of disk space reclaimed by pruning and compacting the db",
+                &["chain"],
+            )
+            .expect("failed to register db_reclaimed_bytes metric")
+            .with_label_values(&[outbox_name]);
+        Some(Self {
+            db,
+            retained_leaves,
+            relayed_destinations: Arc::new(relayed_destinations),
+            interval: settings.interval(),
+            pruned_leaves,
+            reclaimed_bytes,
+        })
+    }
+
+    /// Spawn the pruning task
+    pub fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
+        let span = info_span!("DbPruner", retained_leaves = self.retained_leaves);
+        tokio::spawn(self.main_loop()).instrument(span)
+    }
+
+    async fn main_loop(self) -> Result<()> {
+        let mut interval = interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            let db = self.db.clone();
+            let retained_leaves = self.retained_leaves;
+            let relayed_destinations = self.relayed_destinations.clone();
+            // Compacting can take a while, so keep it off the async workers
+            let (pruned, reclaimed) =
+                spawn_blocking(move || prune(&db, retained_leaves, &relayed_destinations))
+                    .await??;
+            self.pruned_leaves.inc_by(pruned.into());
+            self.reclaimed_bytes.inc_by(reclaimed);
+        }
+    }
+}
+
+/// Prune the DB and compact it if anything was pruned, returning the number
+/// of leaves pruned and the bytes of disk space reclaimed
+fn prune(
+    db: &AbacusDB,
+    retained_leaves: u32,
+    relayed_destinations: &HashSet<u32>,
+) -> Result<(u32, u64)> {
+    let pruned = db.prune_processed_leaves(retained_leaves, relayed_destinations)?;
+    let pruned_gas_payments = match gas_payments_reindexed_from(db) {
+        Some(block) => db.prune_processed_gas_payment_metas(block)?,
+        None => 0,
+    };
+    if pruned == 0 && pruned_gas_payments == 0 {
+        return Ok((0, 0));
+    }
+
+    let raw: &DB = db.as_ref();
+    let size_before = raw.size_on_disk()?;
+    raw.compact();
+    let reclaimed = size_before.saturating_sub(raw.size_on_disk()?);
+    info!(
+        pruned,
+        pruned_gas_payments, reclaimed, "Compacted db after pruning"
+    );
+    Ok((pruned, reclaimed))
+}
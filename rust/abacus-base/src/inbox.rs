@@ -8,13 +8,20 @@ use ethers::core::types::H256;
 use eyre::Result;
 
 use abacus_ethereum::EthereumInbox;
+use futures_util::future::select_all;
 use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::instrument::Instrumented;
+use tracing::{info_span, Instrument};
+
+use crate::{ContractSync, ContractSyncMetrics, InboxIndexers, IndexSettings};
 
 /// Caching inbox type
 #[derive(Debug)]
 pub struct CachingInbox {
     inbox: Inboxes,
     db: AbacusDB,
+    indexer: Option<Arc<InboxIndexers>>,
 }
 
 impl std::fmt::Display for CachingInbox {
@@ -24,9 +31,10 @@ impl std::fmt::Display for CachingInbox {
 }
 
 impl CachingInbox {
-    /// Instantiate new CachingInbox
-    pub fn new(inbox: Inboxes, db: AbacusDB) -> Self {
-        Self { inbox, db }
+    /// Instantiate new CachingInbox. Without an indexer the messages
+    /// delivered to the inbox are not synced to the db.
+    pub fn new(inbox: Inboxes, db: AbacusDB, indexer: Option<Arc<InboxIndexers>>) -> Self {
+        Self { inbox, db, indexer }
     }
 
     /// Return handle on inbox object
@@ -38,6 +46,52 @@ impl CachingInbox {
     pub fn db(&self) -> AbacusDB {
         self.db.clone()
     }
+
+    /// Whether the message with leaf `leaf` is known to have been delivered,
+    /// according to the synced `Process` events. Messages delivered in
+    /// blocks which have yet to be synced are not known to be delivered.
+    pub fn message_delivered(&self, leaf: H256) -> Result<bool> {
+        Ok(self.db.message_delivered(leaf)?)
+    }
+
+    /// Whether the message with leaf `leaf` is known to have been delivered
+    /// in a block which has reached finality, so that the delivery can't be
+    /// reorged out
+    pub fn message_delivery_finalized(&self, leaf: H256) -> Result<bool> {
+        Ok(self.db.message_delivery_finalized(leaf)?)
+    }
+
+    /// Spawn a task that syncs the CachingInbox's db with the on-chain event
+    /// data. Returns `None` if the inbox has no indexer.
+    pub fn sync(
+        &self,
+        index_settings: IndexSettings,
+        metrics: ContractSyncMetrics,
+    ) -> Option<Instrumented<JoinHandle<Result<()>>>> {
+        let span = info_span!("InboxContractSync", self = %self);
+
+        let sync = ContractSync::new(
+            self.inbox.chain_name().into(),
+            self.db.clone(),
+            self.indexer.clone()?,
+            index_settings,
+            metrics,
+        );
+
+        Some(
+            tokio::spawn(async move {
+                let tasks = vec![sync.sync_delivered_messages()];
+
+                let (_, _, remaining) = select_all(tasks).await;
+                for task in remaining.into_iter() {
+                    cancel_task!(task);
+                }
+
+                Ok(())
+            })
+            .instrument(span),
+        )
+    }
 }
 
 #[async_trait]
@@ -1,9 +1,10 @@
 use abacus_core::{
-    CheckpointWithMeta, Indexer, InterchainGasPaymasterIndexer, InterchainGasPaymentWithMeta,
-    OutboxIndexer, RawCommittedMessage,
+    CheckpointWithMeta, InboxIndexer, Indexer, InterchainGasPaymasterIndexer,
+    InterchainGasPaymentWithMeta, OutboxIndexer, RawCommittedMessageWithMeta,
 };
 use abacus_test::mocks::indexer::MockAbacusIndexer;
 use async_trait::async_trait;
+use ethers::core::types::H256;
 use eyre::Result;
 
 /// OutboxIndexer type
@@ -32,11 +33,49 @@ impl Indexer for OutboxIndexers {
             OutboxIndexers::Other(indexer) => indexer.get_finalized_block_number().await,
         }
     }
+
+    async fn wait_for_finalized_block_after(&self, block: u32) {
+        match self {
+            OutboxIndexers::Ethereum(indexer) => {
+                indexer.wait_for_finalized_block_after(block).await
+            }
+            OutboxIndexers::Mock(indexer) => indexer.wait_for_finalized_block_after(block).await,
+            OutboxIndexers::Other(indexer) => indexer.wait_for_finalized_block_after(block).await,
+        }
+    }
+
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        match self {
+            OutboxIndexers::Ethereum(indexer) => indexer.get_latest_block_number().await,
+            OutboxIndexers::Mock(indexer) => indexer.get_latest_block_number().await,
+            OutboxIndexers::Other(indexer) => indexer.get_latest_block_number().await,
+        }
+    }
+
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        match self {
+            OutboxIndexers::Ethereum(indexer) => indexer.get_block_hash(block_number).await,
+            OutboxIndexers::Mock(indexer) => indexer.get_block_hash(block_number).await,
+            OutboxIndexers::Other(indexer) => indexer.get_block_hash(block_number).await,
+        }
+    }
+
+    async fn wait_for_block_after(&self, block: u32) {
+        match self {
+            OutboxIndexers::Ethereum(indexer) => indexer.wait_for_block_after(block).await,
+            OutboxIndexers::Mock(indexer) => indexer.wait_for_block_after(block).await,
+            OutboxIndexers::Other(indexer) => indexer.wait_for_block_after(block).await,
+        }
+    }
 }
 
 #[async_trait]
 impl OutboxIndexer for OutboxIndexers {
-    async fn fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessage>> {
+    async fn fetch_sorted_messages(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
         match self {
             OutboxIndexers::Ethereum(indexer) => indexer.fetch_sorted_messages(from, to).await,
             OutboxIndexers::Mock(indexer) => indexer.fetch_sorted_messages(from, to).await,
@@ -44,6 +83,31 @@ impl OutboxIndexer for OutboxIndexers {
         }
     }
 
+    async fn fetch_messages_by_leaf_index(
+        &self,
+        leaf_indices: &[u32],
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
+        match self {
+            OutboxIndexers::Ethereum(indexer) => {
+                indexer
+                    .fetch_messages_by_leaf_index(leaf_indices, from, to)
+                    .await
+            }
+            OutboxIndexers::Mock(indexer) => {
+                indexer
+                    .fetch_messages_by_leaf_index(leaf_indices, from, to)
+                    .await
+            }
+            OutboxIndexers::Other(indexer) => {
+                indexer
+                    .fetch_messages_by_leaf_index(leaf_indices, from, to)
+                    .await
+            }
+        }
+    }
+
     async fn fetch_sorted_cached_checkpoints(
         &self,
         from: u32,
@@ -89,6 +153,62 @@ impl Indexer for InterchainGasPaymasterIndexers {
             }
         }
     }
+
+    async fn wait_for_finalized_block_after(&self, block: u32) {
+        match self {
+            InterchainGasPaymasterIndexers::Ethereum(indexer) => {
+                indexer.wait_for_finalized_block_after(block).await
+            }
+            InterchainGasPaymasterIndexers::Mock(indexer) => {
+                indexer.wait_for_finalized_block_after(block).await
+            }
+            InterchainGasPaymasterIndexers::Other(indexer) => {
+                indexer.wait_for_finalized_block_after(block).await
+            }
+        }
+    }
+
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        match self {
+            InterchainGasPaymasterIndexers::Ethereum(indexer) => {
+                indexer.get_latest_block_number().await
+            }
+            InterchainGasPaymasterIndexers::Mock(indexer) => {
+                indexer.get_latest_block_number().await
+            }
+            InterchainGasPaymasterIndexers::Other(indexer) => {
+                indexer.get_latest_block_number().await
+            }
+        }
+    }
+
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        match self {
+            InterchainGasPaymasterIndexers::Ethereum(indexer) => {
+                indexer.get_block_hash(block_number).await
+            }
+            InterchainGasPaymasterIndexers::Mock(indexer) => {
+                indexer.get_block_hash(block_number).await
+            }
+            InterchainGasPaymasterIndexers::Other(indexer) => {
+                indexer.get_block_hash(block_number).await
+            }
+        }
+    }
+
+    async fn wait_for_block_after(&self, block: u32) {
+        match self {
+            InterchainGasPaymasterIndexers::Ethereum(indexer) => {
+                indexer.wait_for_block_after(block).await
+            }
+            InterchainGasPaymasterIndexers::Mock(indexer) => {
+                indexer.wait_for_block_after(block).await
+            }
+            InterchainGasPaymasterIndexers::Other(indexer) => {
+                indexer.wait_for_block_after(block).await
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -111,3 +231,68 @@ impl InterchainGasPaymasterIndexer for InterchainGasPaymasterIndexers {
         }
     }
 }
+
+/// InboxIndexer type
+#[derive(Debug)]
+pub enum InboxIndexers {
+    /// Ethereum contract indexer
+    Ethereum(Box<dyn InboxIndexer>),
+    /// Mock indexer
+    Mock(Box<dyn InboxIndexer>),
+    /// Other indexer variant
+    Other(Box<dyn InboxIndexer>),
+}
+
+#[async_trait]
+impl Indexer for InboxIndexers {
+    async fn get_finalized_block_number(&self) -> Result<u32> {
+        match self {
+            InboxIndexers::Ethereum(indexer) => indexer.get_finalized_block_number().await,
+            InboxIndexers::Mock(indexer) => indexer.get_finalized_block_number().await,
+            InboxIndexers::Other(indexer) => indexer.get_finalized_block_number().await,
+        }
+    }
+
+    async fn wait_for_finalized_block_after(&self, block: u32) {
+        match self {
+            InboxIndexers::Ethereum(indexer) => indexer.wait_for_finalized_block_after(block).await,
+            InboxIndexers::Mock(indexer) => indexer.wait_for_finalized_block_after(block).await,
+            InboxIndexers::Other(indexer) => indexer.wait_for_finalized_block_after(block).await,
+        }
+    }
+
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        match self {
+            InboxIndexers::Ethereum(indexer) => indexer.get_latest_block_number().await,
+            InboxIndexers::Mock(indexer) => indexer.get_latest_block_number().await,
+            InboxIndexers::Other(indexer) => indexer.get_latest_block_number().await,
+        }
+    }
+
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        match self {
+            InboxIndexers::Ethereum(indexer) => indexer.get_block_hash(block_number).await,
+            InboxIndexers::Mock(indexer) => indexer.get_block_hash(block_number).await,
+            InboxIndexers::Other(indexer) => indexer.get_block_hash(block_number).await,
+        }
+    }
+
+    async fn wait_for_block_after(&self, block: u32) {
+        match self {
+            InboxIndexers::Ethereum(indexer) => indexer.wait_for_block_after(block).await,
+            InboxIndexers::Mock(indexer) => indexer.wait_for_block_after(block).await,
+            InboxIndexers::Other(indexer) => indexer.wait_for_block_after(block).await,
+        }
+    }
+}
+
+#[async_trait]
+impl InboxIndexer for InboxIndexers {
+    async fn fetch_processed_messages(&self, from: u32, to: u32) -> Result<Vec<H256>> {
+        match self {
+            InboxIndexers::Ethereum(indexer) => indexer.fetch_processed_messages(from, to).await,
+            InboxIndexers::Mock(indexer) => indexer.fetch_processed_messages(from, to).await,
+            InboxIndexers::Other(indexer) => indexer.fetch_processed_messages(from, to).await,
+        }
+    }
+}
@@ -29,6 +29,10 @@ pub use outbox::*;
 mod inbox;
 pub use inbox::*;
 
+/// outbox health monitoring
+mod outbox_health;
+pub use outbox_health::*;
+
 mod metrics;
 pub use metrics::*;
 
@@ -50,5 +54,14 @@ pub use types::*;
 mod validator_manager;
 pub use validator_manager::*;
 
+mod snapshot;
+pub use snapshot::*;
+
+mod db_pruner;
+pub use db_pruner::*;
+
+mod remote_signer;
+pub use remote_signer::*;
+
 #[cfg(feature = "oneline-eyre")]
 pub mod oneline_eyre;
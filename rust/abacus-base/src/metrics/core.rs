@@ -13,7 +13,7 @@ use prometheus::{
 };
 use tokio::task::JoinHandle;
 
-use ethers_prometheus::ProviderMetrics;
+use ethers_prometheus::{ProviderMetrics, RPC_DURATION_SECONDS_HELP, RPC_DURATION_SECONDS_LABELS};
 
 use crate::metrics::provider::create_provider_metrics;
 
@@ -79,11 +79,11 @@ impl CoreMetrics {
         let rpc_latencies = register_histogram_vec_with_registry!(
             histogram_opts!(
                 namespaced!("rpc_duration_seconds"),
-                "Duration from dispatch to receipt-of-response for RPC calls",
+                RPC_DURATION_SECONDS_HELP,
                 NETWORK_HISTOGRAM_BUCKETS.into(),
                 const_labels.clone()
             ),
-            &["chain", "method"],
+            RPC_DURATION_SECONDS_LABELS,
             registry
         )?;
 
@@ -293,12 +293,29 @@ impl CoreMetrics {
     }
 
     /// Call with RPC duration after it is complete
-    pub fn rpc_complete(&self, chain: &str, method: &str, duration: Duration) {
+    pub fn rpc_complete(
+        &self,
+        chain: &str,
+        method: &str,
+        provider: &str,
+        success: bool,
+        duration: Duration,
+    ) {
         self.rpc_latencies
-            .with_label_values(&[chain, method, &self.agent_name])
+            .with_label_values(&[
+                chain,
+                method,
+                provider,
+                if success { "success" } else { "failure" },
+            ])
             .observe(duration.as_secs_f64())
     }
 
+    /// Histogram for RPC durations by chain, method and provider
+    pub fn rpc_latencies(&self) -> HistogramVec {
+        self.rpc_latencies.clone()
+    }
+
     /// Gauge for measuring the last known message leaf index
     pub fn last_known_message_leaf_index(&self) -> IntGaugeVec {
         self.last_known_message_leaf_index.clone()
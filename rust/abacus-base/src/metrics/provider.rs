@@ -42,5 +42,6 @@ pub(crate) fn create_provider_metrics(metrics: &CoreMetrics) -> Result<ProviderM
             WALLET_BALANCE_HELP,
             WALLET_BALANCE_LABELS,
         )?)
+        .rpc_duration_seconds(metrics.rpc_latencies())
         .build()?)
 }
@@ -61,6 +61,7 @@ impl CachingOutbox {
     ) -> Instrumented<JoinHandle<Result<()>>> {
         let span = info_span!("OutboxContractSync", self = %self);
 
+        let index_checkpoints = index_settings.checkpoints();
         let sync = ContractSync::new(
             self.outbox.chain_name().into(),
             self.db.clone(),
@@ -70,7 +71,10 @@ impl CachingOutbox {
         );
 
         tokio::spawn(async move {
-            let tasks = vec![sync.sync_outbox_messages()];
+            let mut tasks = vec![sync.sync_outbox_messages(), sync.heal_message_gaps()];
+            if index_checkpoints {
+                tasks.push(sync.sync_cached_checkpoints());
+            }
 
             let (_, _, remaining) = select_all(tasks).await;
             for task in remaining.into_iter() {
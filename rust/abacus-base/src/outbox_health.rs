@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use abacus_core::{AbacusContract, Outbox, OutboxState};
+use eyre::Result;
+use prometheus::IntGauge;
+use tokio::{
+    sync::watch,
+    task::JoinHandle,
+    time::{interval, MissedTickBehavior},
+};
+use tracing::{error, info, info_span, instrument::Instrumented, warn, Instrument};
+
+use crate::{CoreMetrics, Outboxes};
+
+/// How often the outbox's state is polled
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Polls the state of an outbox, exports it as a metric and broadcasts state
+/// transitions to every subscribed `OutboxHealth`.
+#[derive(Debug)]
+pub struct OutboxHealthMonitor {
+    outbox: Outboxes,
+    outbox_name: String,
+    poll_interval: Duration,
+    outbox_state_gauge: IntGauge,
+    tx: watch::Sender<Option<OutboxState>>,
+}
+
+impl OutboxHealthMonitor {
+    /// Create a monitor for `outbox`
+    pub fn new(outbox: Outboxes, metrics: &CoreMetrics) -> Self {
+        let outbox_name = outbox.chain_name().to_owned();
+        let (tx, _) = watch::channel(None);
+        Self {
+            outbox,
+            outbox_state_gauge: metrics.outbox_state().with_label_values(&[&outbox_name]),
+            outbox_name,
+            poll_interval: POLL_INTERVAL,
+            tx,
+        }
+    }
+
+    /// Get a handle that observes the outbox's state
+    pub fn subscribe(&self) -> OutboxHealth {
+        OutboxHealth {
+            outbox_name: self.outbox_name.clone(),
+            rx: self.tx.subscribe(),
+        }
+    }
+
+    /// Spawn the polling task. It never returns, so subscribers never see
+    /// the channel close.
+    pub fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
+        let span = info_span!("OutboxHealthMonitor", outbox = %self.outbox_name);
+        tokio::spawn(self.main_loop()).instrument(span)
+    }
+
+    async fn main_loop(self) -> Result<()> {
+        let mut interval = interval(self.poll_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            match self.outbox.state().await {
+                Ok(state) => self.update(state),
+                Err(e) => warn!(error = %e, "Failed to get outbox state"),
+            }
+        }
+    }
+
+    fn update(&self, state: OutboxState) {
+        self.outbox_state_gauge.set(state as u8 as i64);
+        let previous = *self.tx.borrow();
+        if previous == Some(state) {
+            return;
+        }
+        info!(previous = ?previous, state = ?state, "Outbox state changed");
+        self.tx.send_replace(Some(state));
+    }
+}
+
+/// A handle to the latest state of an outbox, as observed by an
+/// `OutboxHealthMonitor`
+#[derive(Debug, Clone)]
+pub struct OutboxHealth {
+    outbox_name: String,
+    rx: watch::Receiver<Option<OutboxState>>,
+}
+
+impl OutboxHealth {
+    /// The latest observed state, or None if the outbox has not been polled
+    /// yet
+    pub fn state(&self) -> Option<OutboxState> {
+        *self.rx.borrow()
+    }
+
+    /// True if the outbox has been observed in the `Failed` state
+    pub fn is_failed(&self) -> bool {
+        self.state() == Some(OutboxState::Failed)
+    }
+
+    /// Wait until the outbox has been polled at least once and return its
+    /// state
+    pub async fn wait_for_state(&mut self) -> OutboxState {
+        self.wait_for(|_| true).await
+    }
+
+    /// Wait until the outbox has been observed in the `Failed` state. Once
+    /// failed, an outbox never recovers.
+    pub async fn wait_for_failure(&mut self) {
+        self.wait_for(|state| state == OutboxState::Failed).await;
+    }
+
+    async fn wait_for(&mut self, predicate: impl Fn(OutboxState) -> bool) -> OutboxState {
+        loop {
+            if let Some(state) = *self.rx.borrow_and_update() {
+                if predicate(state) {
+                    return state;
+                }
+            }
+            if self.rx.changed().await.is_err() {
+                // The monitor stopped, so the state will never change again
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Log a structured critical event recording that the outbox has failed
+    /// and how the agent is reacting to it
+    pub fn report_failure(&self, reaction: &str) {
+        error!(
+            critical = true,
+            outbox = %self.outbox_name,
+            state = ?self.state(),
+            reaction = reaction,
+            "Outbox has FAILED"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use abacus_test::mocks::MockOutboxContract;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn broadcasts_state_transitions() {
+        let mut mock_outbox = MockOutboxContract::new();
+        let polls = AtomicU32::new(0);
+        mock_outbox.expect__state().returning(move || {
+            Ok(match polls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => OutboxState::Active,
+                _ => OutboxState::Failed,
+            })
+        });
+
+        let metrics = CoreMetrics::new("outbox_health_test", None, prometheus::Registry::new())
+            .expect("could not make metrics");
+        let (tx, _) = watch::channel(None);
+        let monitor = OutboxHealthMonitor {
+            outbox: mock_outbox.into(),
+            outbox_name: "outbox_1".to_owned(),
+            poll_interval: Duration::from_millis(10),
+            outbox_state_gauge: metrics.outbox_state().with_label_values(&["outbox_1"]),
+            tx,
+        };
+        let mut health = monitor.subscribe();
+        assert_eq!(health.state(), None);
+        assert!(!health.is_failed());
+
+        let task = monitor.spawn();
+        assert_eq!(health.wait_for_state().await, OutboxState::Active);
+        health.wait_for_failure().await;
+        assert!(health.is_failed());
+        task.into_inner().abort();
+    }
+}
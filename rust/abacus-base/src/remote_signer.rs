@@ -0,0 +1,111 @@
+use async_trait::async_trait;
+use ethers::core::types::{Address, Signature};
+use ethers::core::utils::hex;
+use serde::Serialize;
+
+use abacus_core::{RemoteSignerError, SigningService};
+
+#[derive(Serialize)]
+struct SignRequest {
+    data: String,
+}
+
+/// A signing service implementing the Web3Signer signing API over HTTP
+#[derive(Debug, Clone)]
+pub struct HttpSigningService {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpSigningService {
+    /// Create a client of the signing service at `url`. TLS and
+    /// authentication are configured on `client`.
+    pub fn new(client: reqwest::Client, url: impl Into<String>) -> Self {
+        Self {
+            client,
+            url: url.into().trim_end_matches('/').to_owned(),
+        }
+    }
+
+    async fn request_signature(&self, address: Address, data: &[u8]) -> reqwest::Result<String> {
+        self.client
+            .post(format!("{}/api/v1/eth1/sign/{:?}", self.url, address))
+            .json(&SignRequest {
+                data: format!("0x{}", hex::encode(data)),
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await
+    }
+}
+
+#[async_trait]
+impl SigningService for HttpSigningService {
+    async fn sign(&self, address: Address, data: &[u8]) -> Result<Signature, RemoteSignerError> {
+        let response = self
+            .request_signature(address, data)
+            .await
+            .map_err(|e| RemoteSignerError::ServiceError(e.into()))?;
+        Ok(response.trim().parse()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use ethers::core::types::H256;
+    use ethers::core::utils::keccak256;
+    use ethers::signers::{LocalWallet, Signer};
+    use warp::Filter;
+
+    use abacus_core::RemoteSigner;
+
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    struct StubRequest {
+        data: String,
+    }
+
+    /// Serve a stub of the signing API backed by `wallet`
+    fn spawn_stub_server(wallet: LocalWallet) -> SocketAddr {
+        let route = warp::post()
+            .and(warp::path!("api" / "v1" / "eth1" / "sign" / String))
+            .and(warp::body::json())
+            .map(move |_identifier: String, request: StubRequest| {
+                let data = hex::decode(request.data.trim_start_matches("0x")).unwrap();
+                let signature = wallet.sign_hash(H256::from(keccak256(data)));
+                format!("0x{}", signature)
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    #[tokio::test]
+    async fn it_signs_through_the_signing_api() {
+        let wallet: LocalWallet =
+            "1111111111111111111111111111111111111111111111111111111111111111"
+                .parse()
+                .unwrap();
+        let addr = spawn_stub_server(wallet.clone());
+        let service = HttpSigningService::new(reqwest::Client::new(), format!("http://{}/", addr));
+        let remote = RemoteSigner::new(Arc::new(service), wallet.address());
+
+        assert_eq!(
+            remote.sign_message("hello").await.unwrap(),
+            wallet.sign_message("hello").await.unwrap()
+        );
+
+        // Errors talking to the service are surfaced
+        let unreachable = HttpSigningService::new(reqwest::Client::new(), "http://127.0.0.1:1");
+        assert!(matches!(
+            unreachable.sign(wallet.address(), b"hello").await,
+            Err(RemoteSignerError::ServiceError(_))
+        ));
+    }
+}
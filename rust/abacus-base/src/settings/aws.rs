@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use eyre::Result;
+use once_cell::sync::Lazy;
+use rusoto_core::{
+    credential::{
+        AutoRefreshingProvider, ChainProvider, ContainerProvider, EnvironmentProvider,
+        InstanceMetadataProvider, ProfileProvider, ProvideAwsCredentials,
+    },
+    HttpClient, Region,
+};
+use rusoto_kms::KmsClient;
+use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient};
+use serde::Deserialize;
+
+/// KMS clients shared by every signer with the same region and credentials.
+/// Clients are leaked as `AwsSigner` requires a `'static` reference to one.
+static KMS_CLIENTS: Lazy<Mutex<HashMap<(String, AwsCredentialsConf), &'static KmsClient>>> =
+    Lazy::new(Default::default);
+
+/// Where to get AWS credentials from
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum AwsCredentialsConf {
+    /// The `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` env vars
+    Environment,
+    /// A named profile in the shared credentials file
+    Profile {
+        /// The profile name
+        name: String,
+    },
+    /// The ECS container credentials endpoint
+    Container,
+    /// The EC2 instance metadata service
+    InstanceMetadata,
+    /// The default chain: env vars, then profile, then container, then
+    /// instance metadata
+    Chain,
+    /// Assume a role, e.g. in another account, using credentials from the
+    /// default chain
+    AssumeRole {
+        /// The ARN of the role to assume
+        rolearn: String,
+        /// The session name to assume the role with
+        sessionname: Option<String>,
+    },
+}
+
+impl Default for AwsCredentialsConf {
+    fn default() -> Self {
+        Self::Environment
+    }
+}
+
+impl AwsCredentialsConf {
+    fn build_client(&self, region: &Region) -> Result<rusoto_core::Client> {
+        fn client_with<P>(provider: P) -> Result<rusoto_core::Client>
+        where
+            P: ProvideAwsCredentials + Send + Sync + 'static,
+        {
+            Ok(rusoto_core::Client::new_with(provider, HttpClient::new()?))
+        }
+
+        match self {
+            AwsCredentialsConf::Environment => client_with(EnvironmentProvider::default()),
+            AwsCredentialsConf::Profile { name } => {
+                let mut provider = ProfileProvider::new()?;
+                provider.set_profile(name.as_str());
+                client_with(provider)
+            }
+            AwsCredentialsConf::Container => client_with(ContainerProvider::new()),
+            AwsCredentialsConf::InstanceMetadata => client_with(InstanceMetadataProvider::new()),
+            AwsCredentialsConf::Chain => {
+                client_with(AutoRefreshingProvider::new(ChainProvider::new())?)
+            }
+            AwsCredentialsConf::AssumeRole {
+                rolearn,
+                sessionname,
+            } => {
+                let sts = StsClient::new_with(
+                    HttpClient::new()?,
+                    AutoRefreshingProvider::new(ChainProvider::new())?,
+                    region.clone(),
+                );
+                let provider = StsAssumeRoleSessionCredentialsProvider::new(
+                    sts,
+                    rolearn.clone(),
+                    sessionname
+                        .clone()
+                        .unwrap_or_else(|| "abacus-agent".to_owned()),
+                    None,
+                    None,
+                    None,
+                    None,
+                );
+                client_with(AutoRefreshingProvider::new(provider)?)
+            }
+        }
+    }
+}
+
+/// Get the KMS client for a region and credentials source, creating it on
+/// first use
+pub fn kms_client(region: &str, credentials: &AwsCredentialsConf) -> Result<&'static KmsClient> {
+    let region: Region = region.parse()?;
+    let key = (region.name().to_owned(), credentials.clone());
+    let mut clients = KMS_CLIENTS.lock().expect("KMS client cache poisoned");
+    if let Some(client) = clients.get(&key) {
+        return Ok(client);
+    }
+    let client: &'static KmsClient = Box::leak(Box::new(KmsClient::new_with_client(
+        credentials.build_client(&region)?,
+        region,
+    )));
+    clients.insert(key, client);
+    Ok(client)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_selects_kms_client_by_region_and_credentials() {
+        let env = AwsCredentialsConf::Environment;
+        let instance = AwsCredentialsConf::InstanceMetadata;
+
+        let us_east = kms_client("us-east-1", &env).unwrap();
+        assert!(std::ptr::eq(
+            us_east,
+            kms_client("us-east-1", &env).unwrap()
+        ));
+        assert!(!std::ptr::eq(
+            us_east,
+            kms_client("eu-west-1", &env).unwrap()
+        ));
+        assert!(!std::ptr::eq(
+            us_east,
+            kms_client("us-east-1", &instance).unwrap()
+        ));
+        assert!(std::ptr::eq(
+            kms_client("eu-west-1", &instance).unwrap(),
+            kms_client("eu-west-1", &instance).unwrap()
+        ));
+
+        assert!(kms_client("not-a-region", &env).is_err());
+    }
+}
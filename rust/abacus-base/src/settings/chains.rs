@@ -8,11 +8,16 @@ use abacus_ethereum::{
     InboxBuilder, InboxValidatorManagerBuilder, InterchainGasPaymasterBuilder,
     MakeableWithProvider, OutboxBuilder,
 };
+use abacus_simulated::{
+    SimulatedConnection, SimulatedInbox, SimulatedInboxValidatorManager,
+    SimulatedInterchainGasPaymaster, SimulatedNetwork, SimulatedOutbox,
+};
 use ethers_prometheus::{ChainInfo, ContractInfo, PrometheusMiddlewareConf, WalletInfo};
 
 use crate::{
     CoreMetrics, InboxValidatorManagerVariants, InboxValidatorManagers, InboxVariants, Inboxes,
-    InterchainGasPaymasterVariants, InterchainGasPaymasters, OutboxVariants, Outboxes,
+    IndexSettings, InterchainGasPaymasterVariants, InterchainGasPaymasters, OutboxVariants,
+    Outboxes,
 };
 
 /// A connection to _some_ blockchain.
@@ -23,6 +28,8 @@ use crate::{
 pub enum ChainConf {
     /// Ethereum configuration
     Ethereum(Connection),
+    /// In-memory simulated chain configuration
+    Simulated(SimulatedConnection),
 }
 
 impl Default for ChainConf {
@@ -85,6 +92,10 @@ pub struct ChainSetup<T> {
     /// Set this key to disable the inbox. Does nothing for outboxes.
     #[serde(default)]
     pub disabled: Option<String>,
+    /// Settings for indexing the messages delivered to the inbox. The inbox
+    /// is not indexed unless this is set. Does nothing for outboxes.
+    #[serde(default)]
+    pub index: Option<IndexSettings>,
     /// Configure chain-specific metrics information. This will automatically add all contract
     /// addresses but will not override any set explicitly.
     /// Use `metrics_conf()` to get the metrics.
@@ -128,6 +139,22 @@ impl ChainSetup<OutboxAddresses> {
                     .await?,
             )
             .into()),
+            ChainConf::Simulated(conn) => {
+                Ok(OutboxVariants::Other(Box::new(SimulatedOutbox::new(
+                    &SimulatedNetwork::get_or_create(&conn.network),
+                    &ContractLocator {
+                        chain_name: self.name.clone(),
+                        domain: self.domain.parse().expect("invalid uint"),
+                        address: self
+                            .addresses
+                            .outbox
+                            .parse::<ethers::types::Address>()?
+                            .into(),
+                    },
+                    signer,
+                )))
+                .into())
+            }
         }
     }
 
@@ -162,6 +189,16 @@ impl ChainSetup<OutboxAddresses> {
                 )
                 .into(),
             )),
+            ChainConf::Simulated(_) => Ok(Some(
+                InterchainGasPaymasterVariants::Other(Box::new(
+                    SimulatedInterchainGasPaymaster::new(&ContractLocator {
+                        chain_name: self.name.clone(),
+                        domain: self.domain.parse().expect("invalid uint"),
+                        address: paymaster_address.parse::<ethers::types::Address>()?.into(),
+                    }),
+                ))
+                .into(),
+            )),
         }
     }
 
@@ -221,6 +258,22 @@ impl ChainSetup<InboxAddresses> {
                     .await?,
             )
             .into()),
+            ChainConf::Simulated(conn) => Ok(InboxVariants::Other(Box::new(SimulatedInbox::new(
+                &SimulatedNetwork::get_or_create(&conn.network),
+                &ContractLocator {
+                    chain_name: self.name.clone(),
+                    domain: self.domain.parse().expect("invalid uint"),
+                    address: self
+                        .addresses
+                        .inbox
+                        .parse::<ethers::types::Address>()?
+                        .into(),
+                },
+                self.addresses
+                    .validator_manager
+                    .parse::<ethers::types::Address>()?,
+            )))
+            .into()),
         }
     }
 
@@ -252,6 +305,21 @@ impl ChainSetup<InboxAddresses> {
                     .await?,
             )
             .into()),
+            ChainConf::Simulated(conn) => Ok(InboxValidatorManagerVariants::Other(Box::new(
+                SimulatedInboxValidatorManager::new(
+                    &SimulatedNetwork::get_or_create(&conn.network),
+                    &ContractLocator {
+                        chain_name: self.name.clone(),
+                        domain: self.domain.parse().expect("invalid uint"),
+                        address: self
+                            .addresses
+                            .validator_manager
+                            .parse::<ethers::types::Address>()?
+                            .into(),
+                    },
+                ),
+            ))
+            .into()),
         }
     }
 
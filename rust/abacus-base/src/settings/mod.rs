@@ -72,31 +72,34 @@
 //!    intended to be used by a specific agent.
 //!    E.g. `export ABC_KATHY_CHAT_TYPE="static message"`
 
-use std::{collections::HashMap, env, sync::Arc};
+use std::{collections::HashMap, env, path::PathBuf, sync::Arc, time::Duration};
 
 use config::{Config, ConfigError, Environment, File};
-use ethers::prelude::AwsSigner;
+use ethers::prelude::{Address, AwsSigner, LocalWallet};
 use eyre::{bail, Report};
-use once_cell::sync::OnceCell;
-use rusoto_core::{credential::EnvironmentProvider, HttpClient};
-use rusoto_kms::KmsClient;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use serde::Deserialize;
 use tracing::instrument;
 
 use abacus_core::{
-    db::{AbacusDB, DB},
+    db::{AbacusDB, MigrationOptions, DB},
     utils::HexString,
-    AbacusContract, ContractLocator, Signers,
+    AbacusContract, ContractLocator, RemoteSigner, Signers,
 };
 use abacus_ethereum::{
-    InterchainGasPaymasterIndexerBuilder, MakeableWithProvider, OutboxIndexerBuilder,
+    InboxIndexerBuilder, InterchainGasPaymasterIndexerBuilder, MakeableWithProvider,
+    OutboxIndexerBuilder,
+};
+use abacus_simulated::{
+    SimulatedInboxIndexer, SimulatedInterchainGasPaymasterIndexer, SimulatedNetwork,
+    SimulatedOutboxIndexer,
 };
 pub use chains::{ChainConf, ChainSetup, InboxAddresses, OutboxAddresses};
 
 use crate::{settings::trace::TracingConfig, CachingInterchainGasPaymaster};
 use crate::{
-    AbacusAgentCore, CachingInbox, CachingOutbox, CoreMetrics, InboxContracts,
-    InboxValidatorManagers, InterchainGasPaymasterIndexers, OutboxIndexers,
+    AbacusAgentCore, CachingInbox, CachingOutbox, CoreMetrics, HttpSigningService, InboxContracts,
+    InboxIndexers, InboxValidatorManagers, InterchainGasPaymasterIndexers, OutboxIndexers,
 };
 
 /// Chain configuration
@@ -105,7 +108,9 @@ pub mod chains;
 /// Tracing subscriber management
 pub mod trace;
 
-static KMS_CLIENT: OnceCell<KmsClient> = OnceCell::new();
+/// AWS credentials and KMS client management
+pub mod aws;
+pub use aws::AwsCredentialsConf;
 
 /// Ethereum signer types
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -116,13 +121,41 @@ pub enum SignerConf {
         /// Hex string of private key, without 0x prefix
         key: HexString<64>,
     },
-    /// An AWS signer. Note that AWS credentials must be inserted into the env
-    /// separately.
+    /// An encrypted JSON keystore file. Exactly one source for the passphrase
+    /// must be given.
+    Keystore {
+        /// Path to the keystore file
+        path: String,
+        /// Path to a file containing the passphrase
+        passwordfile: Option<String>,
+        /// Name of an env var containing the passphrase
+        passwordenv: Option<String>,
+    },
+    /// An AWS signer. Signers with the same region and credentials share a
+    /// KMS client.
     Aws {
         /// The UUID identifying the AWS KMS Key
         id: String, // change to no _ so we can set by env
         /// The AWS region
         region: String,
+        /// Where to get AWS credentials from. Defaults to the env.
+        #[serde(default)]
+        credentials: AwsCredentialsConf,
+    },
+    /// A remote signing service implementing the Web3Signer signing API
+    Remote {
+        /// Base URL of the signing service
+        url: String,
+        /// Address of the key to sign with
+        address: Address,
+        /// Bearer token sent with every request
+        authtoken: Option<String>,
+        /// Path to a PEM encoded CA certificate to trust for the service's
+        /// TLS certificate
+        cacert: Option<String>,
+        /// Path to a PEM file containing the client certificate and private
+        /// key for mutual TLS
+        clientidentity: Option<String>,
     },
     #[serde(other)]
     /// Assume node will sign on RPC calls
@@ -137,24 +170,65 @@ impl Default for SignerConf {
 
 impl SignerConf {
     /// Try to convert the ethereum signer to a local wallet
-    #[instrument(err)]
+    #[instrument(err, skip(self))]
     pub async fn try_into_signer(&self) -> Result<Signers, Report> {
         match self {
             SignerConf::HexKey { key } => Ok(Signers::Local(key.as_ref().parse()?)),
-            SignerConf::Aws { id, region } => {
-                let client = KMS_CLIENT.get_or_init(|| {
-                    KmsClient::new_with_client(
-                        rusoto_core::Client::new_with(
-                            EnvironmentProvider::default(),
-                            HttpClient::new().unwrap(),
-                        ),
-                        region.parse().expect("invalid region"),
-                    )
-                });
-
+            SignerConf::Keystore {
+                path,
+                passwordfile,
+                passwordenv,
+            } => {
+                let password = match (passwordfile, passwordenv) {
+                    (Some(file), None) => std::fs::read_to_string(file)?
+                        .trim_end_matches(&['\r', '\n'][..])
+                        .to_owned(),
+                    (None, Some(var)) => env::var(var)?,
+                    _ => {
+                        bail!("Keystore signer requires exactly one of passwordfile or passwordenv")
+                    }
+                };
+                Ok(Signers::Local(LocalWallet::decrypt_keystore(
+                    path, password,
+                )?))
+            }
+            SignerConf::Aws {
+                id,
+                region,
+                credentials,
+            } => {
+                let client = aws::kms_client(region, credentials)?;
                 let signer = AwsSigner::new(client, id, 0).await?;
                 Ok(Signers::Aws(signer))
             }
+            SignerConf::Remote {
+                url,
+                address,
+                authtoken,
+                cacert,
+                clientidentity,
+            } => {
+                let mut builder = reqwest::Client::builder();
+                if let Some(path) = cacert {
+                    builder = builder.add_root_certificate(reqwest::Certificate::from_pem(
+                        &std::fs::read(path)?,
+                    )?);
+                }
+                if let Some(path) = clientidentity {
+                    builder = builder.identity(reqwest::Identity::from_pem(&std::fs::read(path)?)?);
+                }
+                if let Some(token) = authtoken {
+                    let mut value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+                    value.set_sensitive(true);
+                    builder =
+                        builder.default_headers(HeaderMap::from_iter([(AUTHORIZATION, value)]));
+                }
+                let service = HttpSigningService::new(builder.build()?, url);
+                Ok(Signers::Remote(RemoteSigner::new(
+                    Arc::new(service),
+                    *address,
+                )))
+            }
             SignerConf::Node => bail!("Node signer"),
         }
     }
@@ -166,8 +240,17 @@ impl SignerConf {
 pub struct IndexSettings {
     /// The height at which to start indexing the Outbox contract
     pub from: Option<String>,
-    /// The number of blocks to query at once at which to start indexing the Outbox contract
+    /// The maximum number of blocks to query at once when indexing. Fewer
+    /// are queried while the provider rejects ranges this large.
     pub chunk: Option<String>,
+    /// Whether to index blocks before they reach finality, rolling back
+    /// anything indexed from blocks which are then reorged out. Consumers
+    /// which require finality, like the validator, only use leaves indexed
+    /// from finalized blocks.
+    pub optimistic: Option<String>,
+    /// Whether to also index the checkpoints cached on the outbox, so that
+    /// the roots anchored on-chain can be looked up by block
+    pub checkpoints: Option<String>,
 }
 
 impl IndexSettings {
@@ -186,6 +269,79 @@ impl IndexSettings {
             .and_then(|s| s.parse::<u32>().ok())
             .unwrap_or(1999)
     }
+
+    /// Get the `optimistic` setting
+    pub fn optimistic(&self) -> bool {
+        self.optimistic
+            .as_ref()
+            .and_then(|s| s.parse::<bool>().ok())
+            .unwrap_or_default()
+    }
+
+    /// Get the `checkpoints` setting
+    pub fn checkpoints(&self) -> bool {
+        self.checkpoints
+            .as_ref()
+            .and_then(|s| s.parse::<bool>().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// DB migration settings
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationSettings {
+    /// Whether to only report the migrations an out of date DB needs,
+    /// refusing to open it, rather than apply them
+    pub dryrun: Option<String>,
+    /// A directory to back the DB up to before migrating it
+    pub backup: Option<String>,
+}
+
+impl MigrationSettings {
+    /// Get the `dryrun` setting
+    pub fn dry_run(&self) -> bool {
+        self.dryrun
+            .as_ref()
+            .and_then(|s| s.parse::<bool>().ok())
+            .unwrap_or_default()
+    }
+
+    /// Get the options to migrate the DB with
+    pub fn options(&self) -> MigrationOptions {
+        MigrationOptions {
+            dry_run: self.dry_run(),
+            backup_dir: self.backup.as_ref().map(PathBuf::from),
+        }
+    }
+}
+
+/// DB retention settings
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionSettings {
+    /// The number of latest leaves to keep the message bodies and proofs
+    /// of once they won't be relayed again. Nothing is pruned if unset.
+    pub leaves: Option<String>,
+    /// How often to prune and compact the DB, in seconds
+    pub interval: Option<String>,
+}
+
+impl RetentionSettings {
+    /// Get the `leaves` setting
+    pub fn retained_leaves(&self) -> Option<u32> {
+        self.leaves.as_ref().and_then(|s| s.parse::<u32>().ok())
+    }
+
+    /// Get the `interval` setting, by default an hour
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(
+            self.interval
+                .as_ref()
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or(3600),
+        )
+    }
 }
 
 /// Settings. Usually this should be treated as a base config and used as
@@ -217,8 +373,17 @@ impl IndexSettings {
 pub struct Settings {
     /// The path to use for the DB file
     pub db: String,
+    /// The DB backend, `rocksdb` (the default) to persist the DB at `db`,
+    /// or `memory` to hold it in memory
+    pub dbbackend: Option<String>,
     /// Port to listen for prometheus scrape requests
     pub metrics: Option<String>,
+    /// How to migrate the DB if its schema is out of date
+    #[serde(default)]
+    pub migration: MigrationSettings,
+    /// Which data to prune from the DB once it's no longer needed
+    #[serde(default)]
+    pub retention: RetentionSettings,
     /// Settings for the outbox indexer
     #[serde(default)]
     pub index: IndexSettings,
@@ -237,7 +402,10 @@ impl Settings {
     fn clone(&self) -> Self {
         Self {
             db: self.db.clone(),
+            dbbackend: self.dbbackend.clone(),
             metrics: self.metrics.clone(),
+            migration: self.migration.clone(),
+            retention: self.retention.clone(),
             index: self.index.clone(),
             outbox: self.outbox.clone(),
             inboxes: self.inboxes.clone(),
@@ -290,8 +458,56 @@ impl Settings {
     ) -> Result<CachingInbox, Report> {
         let signer = self.get_signer(&chain_setup.name).await;
         let inbox = chain_setup.try_into_inbox(signer, metrics).await?;
-        let abacus_db = AbacusDB::new(inbox.chain_name(), db);
-        Ok(CachingInbox::new(inbox, abacus_db))
+        let indexer = match &chain_setup.index {
+            Some(index) => Some(Arc::new(
+                self.try_inbox_indexer(chain_setup, index, metrics).await?,
+            )),
+            None => None,
+        };
+        // Scoped apart from the outbox db of the same chain
+        let abacus_db = AbacusDB::new(format!("{}_inbox", inbox.chain_name()), db);
+        Ok(CachingInbox::new(inbox, abacus_db, indexer))
+    }
+
+    /// Try to get an indexer object for an inbox
+    async fn try_inbox_indexer(
+        &self,
+        chain_setup: &ChainSetup<InboxAddresses>,
+        index: &IndexSettings,
+        metrics: &CoreMetrics,
+    ) -> Result<InboxIndexers, Report> {
+        let signer = self.get_signer(&chain_setup.name).await;
+        let metrics = Some((
+            metrics.provider_metrics(),
+            chain_setup.metrics_conf(metrics.agent_name(), &signer),
+        ));
+        let locator = ContractLocator {
+            chain_name: chain_setup.name.clone(),
+            domain: chain_setup.domain.parse().expect("invalid uint"),
+            address: chain_setup
+                .addresses
+                .inbox
+                .parse::<ethers::types::Address>()?
+                .into(),
+        };
+        match &chain_setup.chain {
+            ChainConf::Ethereum(conn) => Ok(InboxIndexers::Ethereum(
+                InboxIndexerBuilder {
+                    from_height: index.from(),
+                    chunk_size: index.chunk_size(),
+                    finality_blocks: chain_setup.finality_blocks(),
+                }
+                .make_with_connection(conn.clone(), &locator, signer, metrics)
+                .await?,
+            )),
+            ChainConf::Simulated(conn) => {
+                Ok(InboxIndexers::Other(Box::new(SimulatedInboxIndexer::new(
+                    &SimulatedNetwork::get_or_create(&conn.network),
+                    &locator,
+                    chain_setup.finality_blocks(),
+                ))))
+            }
+        }
     }
 
     /// Try to get an InboxValidatorManager
@@ -374,6 +590,22 @@ impl Settings {
                 )
                 .await?,
             )),
+            ChainConf::Simulated(conn) => Ok(OutboxIndexers::Other(Box::new(
+                SimulatedOutboxIndexer::new(
+                    &SimulatedNetwork::get_or_create(&conn.network),
+                    &ContractLocator {
+                        chain_name: self.outbox.name.clone(),
+                        domain: self.outbox.domain.parse().expect("invalid uint"),
+                        address: self
+                            .outbox
+                            .addresses
+                            .outbox
+                            .parse::<ethers::types::Address>()?
+                            .into(),
+                    },
+                    self.outbox.finality_blocks(),
+                ),
+            ))),
         }
     }
 
@@ -419,6 +651,37 @@ impl Settings {
                 )
                 .await?,
             )),
+            ChainConf::Simulated(conn) => Ok(InterchainGasPaymasterIndexers::Other(Box::new(
+                SimulatedInterchainGasPaymasterIndexer::new(
+                    &SimulatedNetwork::get_or_create(&conn.network),
+                    &ContractLocator {
+                        chain_name: self.outbox.name.clone(),
+                        domain: self.outbox.domain.parse().expect("invalid uint"),
+                        address: self
+                            .outbox
+                            .addresses
+                            .interchain_gas_paymaster
+                            .as_ref()
+                            .expect("interchain_gas_paymaster not provided")
+                            .parse::<ethers::types::Address>()?
+                            .into(),
+                    },
+                    self.outbox.finality_blocks(),
+                ),
+            ))),
+        }
+    }
+
+    /// Try to open the DB with the configured backend
+    pub fn try_db(&self) -> Result<DB, Report> {
+        match self.dbbackend.as_deref().unwrap_or("rocksdb") {
+            #[cfg(feature = "rocksdb")]
+            "rocksdb" => DB::from_path(&self.db, &self.migration.options()),
+            "memory" => Ok(DB::in_memory()),
+            backend => bail!(
+                "Unsupported DB backend {}, expected memory or rocksdb (if built with the rocksdb feature)",
+                backend
+            ),
         }
     }
 
@@ -436,7 +699,7 @@ impl Settings {
             prometheus::Registry::new(),
         )?);
 
-        let db = DB::from_path(&self.db)?;
+        let db = self.try_db()?;
         let outbox = Arc::new(self.try_caching_outbox(db.clone(), &metrics).await?);
         let interchain_gas_paymaster = self
             .try_caching_interchain_gas_paymaster(db.clone(), &metrics)
@@ -476,3 +739,37 @@ impl Settings {
             .try_deserialize()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ethers::core::rand::thread_rng;
+    use ethers::signers::Signer;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_decrypts_keystore_signer() {
+        let dir = TempDir::new().unwrap();
+        let (wallet, name) =
+            LocalWallet::new_keystore(dir.path(), &mut thread_rng(), "hunter2", None).unwrap();
+        let path = dir.path().join(name).to_str().unwrap().to_owned();
+        let password_file = dir.path().join("password");
+        std::fs::write(&password_file, "hunter2\n").unwrap();
+
+        let conf = SignerConf::Keystore {
+            path: path.clone(),
+            passwordfile: Some(password_file.to_str().unwrap().to_owned()),
+            passwordenv: None,
+        };
+        let signer = conf.try_into_signer().await.unwrap();
+        assert_eq!(signer.address(), wallet.address());
+
+        let conf = SignerConf::Keystore {
+            path,
+            passwordfile: None,
+            passwordenv: None,
+        };
+        assert!(conf.try_into_signer().await.is_err());
+    }
+}
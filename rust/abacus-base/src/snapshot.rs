@@ -0,0 +1,699 @@
+use std::io::{Read, Write};
+
+use ethers::core::types::{H256, U256};
+use eyre::Result;
+use thiserror::Error;
+use tracing::info;
+
+use abacus_core::{
+    accumulator::incremental::IncrementalMerkle,
+    db::{AbacusDB, SCHEMA_VERSION},
+    AbacusError, Checkpoint, Decode, Encode, InterchainGasPaymentMeta, MessageDispatchMeta,
+    RawCommittedMessage, RawCommittedMessageWithMeta,
+};
+
+use crate::contract_sync::{
+    finalized_gas_payments, store_sync_cursor, sync_cursors, LATEST_INDEXED_GAS_PAYMENT_BLOCK,
+    LATEST_VALID_MESSAGE_RANGE_START_BLOCK,
+};
+
+/// Identifies snapshot files
+const SNAPSHOT_MAGIC: &[u8; 8] = b"ABCSNAP\0";
+/// The version of the snapshot format
+const SNAPSHOT_FORMAT_VERSION: u32 = 3;
+
+/// Errors found checking the integrity of a snapshot
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    /// The file isn't a snapshot
+    #[error("Not a snapshot")]
+    NotASnapshot,
+    /// The snapshot was written in a format or from a schema this version
+    /// doesn't support
+    #[error("Unsupported snapshot format {format} of schema version {schema_version}")]
+    Unsupported {
+        /// The snapshot format version
+        format: u32,
+        /// The schema version of the DB the snapshot was exported from
+        schema_version: u32,
+    },
+    /// The leaves don't have contiguous leaf indices from 0
+    #[error("Expected the leaf with index {expected}, found {found}")]
+    NotContiguous {
+        /// The expected leaf index
+        expected: u32,
+        /// The leaf index found
+        found: u32,
+    },
+    /// A message doesn't match its leaf
+    #[error("Message with leaf index {leaf_index} doesn't match its leaf {leaf:?}")]
+    LeafMismatch {
+        /// The leaf index of the message
+        leaf_index: u32,
+        /// The leaf recorded for the message
+        leaf: H256,
+    },
+    /// The root of the messages' leaves doesn't match the recorded root
+    #[error("Snapshot root {recorded:?} doesn't match the root of its messages {computed:?}")]
+    RootMismatch {
+        /// The root recorded in the snapshot
+        recorded: H256,
+        /// The root of the messages in the snapshot
+        computed: H256,
+    },
+    /// The root of the messages' leaves doesn't match the root cached on
+    /// the outbox
+    #[error("Checkpoint {checkpoint:?} doesn't match the root of the messages {computed:?}")]
+    CheckpointMismatch {
+        /// The checkpoint cached on the outbox
+        checkpoint: Checkpoint,
+        /// The root of the messages up to the checkpoint's index
+        computed: H256,
+    },
+    /// The snapshot has no leaf at the index of the checkpoint it's
+    /// verified against
+    #[error("Checkpoint {checkpoint:?} isn't covered by the snapshot's {leaves} leaves")]
+    CheckpointNotCovered {
+        /// The checkpoint the snapshot is verified against
+        checkpoint: Checkpoint,
+        /// The number of leaves in the snapshot
+        leaves: usize,
+    },
+    /// The DB being imported into already has messages
+    #[error("Can't import into a DB which already has messages")]
+    NotEmpty,
+}
+
+/// The messages, gas payments and sync cursors of one entity in an
+/// AbacusDB, so that a new agent can start from them rather than
+/// re-indexing. Only messages indexed from finalized blocks are included.
+/// Messages which were pruned are included as just their leaf.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// The leaves and their messages, ordered by leaf index from 0
+    pub leaves: Vec<SnapshotLeaf>,
+    /// The root of the tree of the messages' leaves
+    pub root: H256,
+    /// The latest indexed checkpoint cached on the outbox which the
+    /// messages cover, to check the root of the messages against
+    pub checkpoint: Option<Checkpoint>,
+    /// The total gas payment of each leaf index paid for
+    pub gas_payments: Vec<(u32, U256)>,
+    /// The gas payments counted in the totals, so that they aren't counted
+    /// again if they're indexed again
+    pub processed_gas_payments: Vec<InterchainGasPaymentMeta>,
+    /// The blocks syncing resumes from, by name
+    pub cursors: Vec<(String, u32)>,
+}
+
+/// A leaf in a snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotLeaf {
+    /// The leaf index
+    pub leaf_index: u32,
+    /// The leaf hash
+    pub leaf: H256,
+    /// The message, unless it was pruned
+    pub message: Option<RawCommittedMessage>,
+    /// Metadata about the message's dispatch, if it was indexed along with
+    /// it and not pruned
+    pub meta: Option<MessageDispatchMeta>,
+}
+
+impl Snapshot {
+    /// Export a snapshot of `db`
+    pub fn export(db: &AbacusDB) -> Result<Self> {
+        // Messages from blocks which may yet be reorged out are left to be
+        // indexed again
+        let count = match db.retrieve_lowest_unfinalized_leaf_index()? {
+            Some(leaf_index) => leaf_index,
+            None => db.retrieve_latest_leaf_index()?.map_or(0, |i| i + 1),
+        };
+
+        let mut tree = IncrementalMerkle::default();
+        let mut leaves = Vec::with_capacity(count as usize);
+        for leaf_index in 0..count {
+            let leaf = db
+                .leaf_by_leaf_index(leaf_index)?
+                .ok_or_else(|| eyre::eyre!("Missing leaf with index {}", leaf_index))?;
+            tree.ingest(leaf);
+            leaves.push(SnapshotLeaf {
+                leaf_index,
+                leaf,
+                message: db.message_by_leaf(leaf)?,
+                meta: db.message_dispatch_meta_by_leaf_index(leaf_index)?,
+            });
+        }
+
+        let mut checkpoint = None;
+        for n in (0..db.retrieve_cached_checkpoint_count()?).rev() {
+            match db.cached_checkpoint(n)? {
+                Some(cached) if cached.checkpoint.index < count => {
+                    checkpoint = Some(cached.checkpoint);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let mut cursors = vec![];
+        for (name, block) in sync_cursors(db)? {
+            if let Some(block) = block {
+                cursors.push((name.to_owned(), block));
+            }
+        }
+        // Resume indexing messages from the block of the latest exported one
+        // if any later ones were left out
+        let messages_left_out = db
+            .retrieve_latest_leaf_index()?
+            .map_or(false, |latest| count <= latest);
+        if messages_left_out {
+            let resume_from = leaves
+                .last()
+                .and_then(|l| l.meta.as_ref())
+                .map(|meta| meta.block_number as u32);
+            set_cursor(
+                &mut cursors,
+                LATEST_VALID_MESSAGE_RANGE_START_BLOCK,
+                resume_from,
+            );
+        }
+        // Likewise, gas payments from blocks which may yet be reorged out are
+        // left to be indexed again
+        let gas_payments = finalized_gas_payments(db)?;
+        if let Some(unfinalized_from) = gas_payments.unfinalized_from {
+            set_cursor(
+                &mut cursors,
+                LATEST_INDEXED_GAS_PAYMENT_BLOCK,
+                unfinalized_from.checked_sub(1),
+            );
+        }
+
+        Ok(Self {
+            leaves,
+            root: tree.root(),
+            checkpoint,
+            gas_payments: gas_payments.totals,
+            processed_gas_payments: gas_payments.processed,
+            cursors,
+        })
+    }
+
+    /// Check that the leaves are contiguous and match their messages, and
+    /// that their root matches the recorded root, the recorded checkpoint
+    /// and `trusted`. As the rest of the snapshot could be forged along with
+    /// the leaves, `trusted` must come from elsewhere, e.g. a checkpoint
+    /// cached on the outbox or signed by its validators.
+    pub fn verify(&self, trusted: &Checkpoint) -> Result<(), SnapshotError> {
+        let mut tree = IncrementalMerkle::default();
+        for (expected, l) in (0..).zip(self.leaves.iter()) {
+            if l.leaf_index != expected {
+                return Err(SnapshotError::NotContiguous {
+                    expected,
+                    found: l.leaf_index,
+                });
+            }
+            if let Some(message) = l.message.as_ref() {
+                if message.leaf_index != l.leaf_index || message.leaf() != l.leaf {
+                    return Err(SnapshotError::LeafMismatch {
+                        leaf_index: message.leaf_index,
+                        leaf: l.leaf,
+                    });
+                }
+            }
+            tree.ingest(l.leaf);
+            let checkpoints = self.checkpoint.iter().chain(Some(trusted));
+            for checkpoint in checkpoints.filter(|c| c.index == expected) {
+                if checkpoint.root != tree.root() {
+                    return Err(SnapshotError::CheckpointMismatch {
+                        checkpoint: *checkpoint,
+                        computed: tree.root(),
+                    });
+                }
+            }
+        }
+        if trusted.index as usize >= self.leaves.len() {
+            return Err(SnapshotError::CheckpointNotCovered {
+                checkpoint: *trusted,
+                leaves: self.leaves.len(),
+            });
+        }
+        if let Some(checkpoint) = self.checkpoint {
+            if checkpoint.index as usize >= self.leaves.len() {
+                return Err(SnapshotError::CheckpointMismatch {
+                    checkpoint,
+                    computed: tree.root(),
+                });
+            }
+        }
+        if tree.root() != self.root {
+            return Err(SnapshotError::RootMismatch {
+                recorded: self.root,
+                computed: tree.root(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Verify the snapshot against `trusted` and import it into `db`, which
+    /// must not have any leaves yet. Only the leaves `trusted` covers are
+    /// imported, as nothing vouches for any after it. Nothing is written if
+    /// importing fails.
+    pub fn import(&self, db: &AbacusDB, trusted: &Checkpoint) -> Result<()> {
+        self.verify(trusted)?;
+        if db.retrieve_latest_leaf_index()?.is_some() {
+            return Err(SnapshotError::NotEmpty.into());
+        }
+
+        let verified = self.verified_by(trusted);
+        db.write_staged(|staged| verified.store(staged))?;
+
+        info!(
+            leaves = verified.leaves.len(),
+            skipped_leaves = self.leaves.len() - verified.leaves.len(),
+            root = ?verified.root,
+            gas_payments = verified.gas_payments.len(),
+            "Imported snapshot"
+        );
+        Ok(())
+    }
+
+    /// The part of the snapshot covered by `trusted`. If any leaves are left
+    /// out, messages are indexed again from the block of the last leaf kept.
+    fn verified_by(&self, trusted: &Checkpoint) -> Self {
+        let mut verified = self.clone();
+        let count = trusted.index as usize + 1;
+        if count < verified.leaves.len() {
+            verified.leaves.truncate(count);
+            verified.root = trusted.root;
+            verified.checkpoint = Some(*trusted);
+            let resume_from = verified
+                .leaves
+                .last()
+                .and_then(|l| l.meta.as_ref())
+                .map(|meta| meta.block_number as u32);
+            set_cursor(
+                &mut verified.cursors,
+                LATEST_VALID_MESSAGE_RANGE_START_BLOCK,
+                resume_from,
+            );
+        }
+        verified
+    }
+
+    /// Store the snapshot's contents in `db`
+    fn store(&self, db: &AbacusDB) -> Result<()> {
+        for l in self.leaves.iter() {
+            match (l.message.as_ref(), l.meta.as_ref()) {
+                (Some(message), Some(meta)) => {
+                    db.store_messages(&[RawCommittedMessageWithMeta {
+                        message: message.clone(),
+                        meta: meta.clone(),
+                    }])?;
+                }
+                (Some(message), None) => db.store_latest_message(message)?,
+                (None, _) => db.store_pruned_leaf(l.leaf_index, l.leaf)?,
+            }
+        }
+        for (leaf_index, total) in self.gas_payments.iter() {
+            db.store_gas_payment_for_leaf(*leaf_index, *total)?;
+        }
+        for meta in self.processed_gas_payments.iter() {
+            db.store_gas_payment_meta_processed(meta)?;
+        }
+        for (name, block) in self.cursors.iter() {
+            store_sync_cursor(db, name, *block)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replace the block the sync named `name` resumes from, or remove it so the
+/// sync starts over if `block` is `None`
+fn set_cursor(cursors: &mut Vec<(String, u32)>, name: &str, block: Option<u32>) {
+    cursors.retain(|(cursor, _)| cursor != name);
+    if let Some(block) = block {
+        cursors.push((name.to_owned(), block));
+    }
+}
+
+impl Encode for Snapshot {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: Write,
+    {
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        let mut written = SNAPSHOT_MAGIC.len();
+        written += SNAPSHOT_FORMAT_VERSION.write_to(writer)?;
+        written += SCHEMA_VERSION.write_to(writer)?;
+
+        written += (self.leaves.len() as u32).write_to(writer)?;
+        for l in self.leaves.iter() {
+            written += l.leaf_index.write_to(writer)?;
+            written += l.leaf.write_to(writer)?;
+            written += l.message.is_some().write_to(writer)?;
+            if let Some(message) = l.message.as_ref() {
+                // Raw messages are variable length, so prefix them with theirs
+                let message = message.to_vec();
+                written += (message.len() as u32).write_to(writer)?;
+                writer.write_all(&message)?;
+                written += message.len();
+            }
+            written += l.meta.is_some().write_to(writer)?;
+            if let Some(meta) = l.meta.as_ref() {
+                written += meta.write_to(writer)?;
+            }
+        }
+        written += self.root.write_to(writer)?;
+        written += self.checkpoint.is_some().write_to(writer)?;
+        if let Some(checkpoint) = self.checkpoint.as_ref() {
+            written += checkpoint.write_to(writer)?;
+        }
+
+        written += (self.gas_payments.len() as u32).write_to(writer)?;
+        for (leaf_index, total) in self.gas_payments.iter() {
+            written += leaf_index.write_to(writer)?;
+            written += total.write_to(writer)?;
+        }
+        written += (self.processed_gas_payments.len() as u32).write_to(writer)?;
+        for meta in self.processed_gas_payments.iter() {
+            written += meta.write_to(writer)?;
+        }
+
+        written += (self.cursors.len() as u32).write_to(writer)?;
+        for (name, block) in self.cursors.iter() {
+            written += (name.len() as u32).write_to(writer)?;
+            writer.write_all(name.as_bytes())?;
+            written += name.len();
+            written += block.write_to(writer)?;
+        }
+        Ok(written)
+    }
+}
+
+/// Read `len` bytes
+fn read_bytes<R: Read>(reader: &mut R, len: u32) -> Result<Vec<u8>, AbacusError> {
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// An error decoding malformed data
+fn invalid_data<E>(err: E) -> AbacusError
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    std::io::Error::new(std::io::ErrorKind::InvalidData, err).into()
+}
+
+impl Decode for Snapshot {
+    fn read_from<R>(reader: &mut R) -> Result<Self, AbacusError>
+    where
+        R: Read,
+        Self: Sized,
+    {
+        let mut magic = [0; SNAPSHOT_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(invalid_data(SnapshotError::NotASnapshot));
+        }
+        let format = u32::read_from(reader)?;
+        let schema_version = u32::read_from(reader)?;
+        if format != SNAPSHOT_FORMAT_VERSION || schema_version > SCHEMA_VERSION {
+            return Err(invalid_data(SnapshotError::Unsupported {
+                format,
+                schema_version,
+            }));
+        }
+
+        let count = u32::read_from(reader)?;
+        let mut leaves = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let leaf_index = u32::read_from(reader)?;
+            let leaf = H256::read_from(reader)?;
+            let message = if bool::read_from(reader)? {
+                let len = u32::read_from(reader)?;
+                Some(RawCommittedMessage::read_from(
+                    &mut read_bytes(reader, len)?.as_slice(),
+                )?)
+            } else {
+                None
+            };
+            let meta = if bool::read_from(reader)? {
+                Some(MessageDispatchMeta::read_from(reader)?)
+            } else {
+                None
+            };
+            leaves.push(SnapshotLeaf {
+                leaf_index,
+                leaf,
+                message,
+                meta,
+            });
+        }
+        let root = H256::read_from(reader)?;
+        let checkpoint = if bool::read_from(reader)? {
+            Some(Checkpoint::read_from(reader)?)
+        } else {
+            None
+        };
+
+        let count = u32::read_from(reader)?;
+        let mut gas_payments = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            gas_payments.push((u32::read_from(reader)?, U256::read_from(reader)?));
+        }
+        let count = u32::read_from(reader)?;
+        let mut processed_gas_payments = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            processed_gas_payments.push(InterchainGasPaymentMeta::read_from(reader)?);
+        }
+
+        let count = u32::read_from(reader)?;
+        let mut cursors = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = u32::read_from(reader)?;
+            let name = String::from_utf8(read_bytes(reader, len)?).map_err(invalid_data)?;
+            cursors.push((name, u32::read_from(reader)?));
+        }
+
+        Ok(Self {
+            leaves,
+            root,
+            checkpoint,
+            gas_payments,
+            processed_gas_payments,
+            cursors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use ethers::core::types::{H256, U256};
+
+    use abacus_core::{
+        db::AbacusDB, AbacusMessage, Checkpoint, Decode, Encode, InterchainGasPayment,
+        InterchainGasPaymentMeta, InterchainGasPaymentWithMeta, MessageDispatchMeta,
+        RawCommittedMessage, RawCommittedMessageWithMeta,
+    };
+    use abacus_test::test_utils;
+
+    use super::{IncrementalMerkle, Snapshot, SnapshotError, SnapshotLeaf};
+    use crate::contract_sync::{
+        store_sync_cursor, sync_cursors, LATEST_VALID_MESSAGE_RANGE_START_BLOCK,
+    };
+
+    #[tokio::test]
+    async fn exports_and_imports_verified_snapshots() {
+        test_utils::run_test_db(|db| async move {
+            let exported_db = AbacusDB::new("outbox_1", db.clone());
+            for leaf_index in 0..3 {
+                exported_db
+                    .store_messages(&[RawCommittedMessageWithMeta {
+                        message: RawCommittedMessage {
+                            leaf_index,
+                            message: AbacusMessage {
+                                body: vec![leaf_index as u8],
+                                ..Default::default()
+                            }
+                            .to_vec(),
+                        },
+                        meta: MessageDispatchMeta {
+                            block_number: 100 + leaf_index as u64,
+                            block_hash: H256::zero(),
+                            timestamp: 0,
+                            transaction_hash: H256::from_low_u64_be(leaf_index.into()),
+                            log_index: U256::zero(),
+                        },
+                    }])
+                    .unwrap();
+            }
+            let gas_payment_meta = InterchainGasPaymentMeta {
+                transaction_hash: H256::from_low_u64_be(10),
+                log_index: U256::zero(),
+            };
+            exported_db
+                .process_gas_payment(
+                    &InterchainGasPaymentWithMeta {
+                        payment: InterchainGasPayment {
+                            leaf_index: 1,
+                            amount: U256::from(10),
+                        },
+                        meta: gas_payment_meta.clone(),
+                    },
+                    150,
+                )
+                .unwrap();
+            store_sync_cursor(&exported_db, "latest_indexed_gas_payment_block", 150).unwrap();
+            // Leaf 0 is processed and pruned, so only its leaf is exported
+            exported_db.mark_leaf_as_processed(0).unwrap();
+            assert_eq!(
+                exported_db
+                    .prune_processed_leaves(1, &HashSet::from([0]))
+                    .unwrap(),
+                1
+            );
+
+            let snapshot = Snapshot::export(&exported_db).unwrap();
+            assert_eq!(snapshot.leaves.len(), 3);
+            assert!(snapshot.leaves[0].message.is_none());
+            let decoded = Snapshot::read_from(&mut snapshot.to_vec().as_slice()).unwrap();
+            assert_eq!(decoded, snapshot);
+
+            // The root of the messages as cached on the outbox
+            let trusted = Checkpoint {
+                outbox_domain: 0,
+                root: snapshot.root,
+                index: 2,
+            };
+            let imported_db = AbacusDB::new("outbox_2", db.clone());
+
+            // Nothing is written if importing fails partway
+            let mut invalid = snapshot.clone();
+            invalid.cursors.push(("unknown_cursor".to_owned(), 1));
+            assert!(invalid.import(&imported_db, &trusted).is_err());
+            assert_eq!(imported_db.retrieve_latest_leaf_index().unwrap(), None);
+            assert_eq!(imported_db.leaf_by_leaf_index(0).unwrap(), None);
+
+            snapshot.import(&imported_db, &trusted).unwrap();
+            assert_eq!(imported_db.retrieve_latest_leaf_index().unwrap(), Some(2));
+            assert_eq!(
+                imported_db.leaf_by_leaf_index(0).unwrap(),
+                exported_db.leaf_by_leaf_index(0).unwrap()
+            );
+            assert_eq!(
+                imported_db.retrieve_leaf_processing_status(0).unwrap(),
+                Some(true)
+            );
+            assert_eq!(
+                imported_db.message_dispatch_meta_by_leaf_index(2).unwrap(),
+                exported_db.message_dispatch_meta_by_leaf_index(2).unwrap()
+            );
+            assert_eq!(
+                imported_db.retrieve_gas_payment_for_leaf(1).unwrap(),
+                U256::from(10)
+            );
+            assert_eq!(
+                imported_db.retrieve_processed_gas_payment_metas().unwrap(),
+                vec![gas_payment_meta]
+            );
+            assert_eq!(
+                sync_cursors(&imported_db).unwrap(),
+                sync_cursors(&exported_db).unwrap()
+            );
+
+            // Refuse to import over existing leaves
+            assert!(snapshot.import(&imported_db, &trusted).is_err());
+
+            // Detect tampered messages and leaves
+            let mut tampered = snapshot.clone();
+            if let Some(message) = tampered.leaves[1].message.as_mut() {
+                message.message = AbacusMessage::default().to_vec();
+            }
+            assert!(matches!(
+                tampered.verify(&trusted),
+                Err(SnapshotError::LeafMismatch { leaf_index: 1, .. })
+            ));
+            let mut tampered = snapshot.clone();
+            tampered.root = H256::zero();
+            assert!(matches!(
+                tampered.verify(&trusted),
+                Err(SnapshotError::RootMismatch { .. })
+            ));
+
+            // Detect forged leaves even if the snapshot's root and checkpoint
+            // are forged to match them
+            let mut forged = snapshot.clone();
+            forged.leaves[0].leaf = H256::zero();
+            let mut tree = IncrementalMerkle::default();
+            forged.leaves.iter().for_each(|l| tree.ingest(l.leaf));
+            forged.root = tree.root();
+            forged.checkpoint = None;
+            assert!(matches!(
+                forged.verify(&trusted),
+                Err(SnapshotError::CheckpointMismatch { checkpoint, .. }) if checkpoint == trusted
+            ));
+
+            // Leaves forged after the trusted checkpoint aren't imported, and
+            // messages are indexed again from the last one imported
+            let mut tree = IncrementalMerkle::default();
+            snapshot.leaves[..2]
+                .iter()
+                .for_each(|l| tree.ingest(l.leaf));
+            let trusted_earlier = Checkpoint {
+                root: tree.root(),
+                index: 1,
+                ..trusted
+            };
+            let mut forged = snapshot.clone();
+            let forged_message = RawCommittedMessage {
+                leaf_index: 3,
+                message: AbacusMessage::default().to_vec(),
+            };
+            forged.leaves.push(SnapshotLeaf {
+                leaf_index: 3,
+                leaf: forged_message.leaf(),
+                message: Some(forged_message),
+                meta: None,
+            });
+            let mut tree = IncrementalMerkle::default();
+            forged.leaves.iter().for_each(|l| tree.ingest(l.leaf));
+            forged.root = tree.root();
+            forged.checkpoint = None;
+            forged.verify(&trusted_earlier).unwrap();
+
+            let forged_db = AbacusDB::new("outbox_3", db);
+            forged.import(&forged_db, &trusted_earlier).unwrap();
+            assert_eq!(forged_db.retrieve_latest_leaf_index().unwrap(), Some(1));
+            assert_eq!(forged_db.leaf_by_leaf_index(2).unwrap(), None);
+            assert_eq!(forged_db.leaf_by_leaf_index(3).unwrap(), None);
+            assert!(sync_cursors(&forged_db)
+                .unwrap()
+                .contains(&(LATEST_VALID_MESSAGE_RANGE_START_BLOCK, Some(101))));
+
+            // The trusted checkpoint must be one the snapshot covers
+            let later = Checkpoint {
+                index: 3,
+                ..trusted
+            };
+            assert!(matches!(
+                snapshot.verify(&later),
+                Err(SnapshotError::CheckpointNotCovered { leaves: 3, .. })
+            ));
+
+            let mut tampered = snapshot;
+            tampered.leaves.remove(1);
+            assert!(matches!(
+                tampered.verify(&trusted),
+                Err(SnapshotError::NotContiguous {
+                    expected: 1,
+                    found: 2
+                })
+            ));
+        })
+        .await
+    }
+}
@@ -1,5 +1,6 @@
-use abacus_core::SignedCheckpoint;
+use abacus_core::{SignedAnnouncement, SignedCheckpoint};
 use async_trait::async_trait;
+use ethers::types::Address;
 use eyre::Result;
 
 /// A generic trait to read/write Checkpoints offchain
@@ -11,4 +12,8 @@ pub trait CheckpointSyncer {
     async fn fetch_checkpoint(&self, index: u32) -> Result<Option<SignedCheckpoint>>;
     /// Write the signed checkpoint to this syncer
     async fn write_checkpoint(&self, signed_checkpoint: SignedCheckpoint) -> Result<()>;
+    /// Attempt to fetch the signed announcement made by `validator`
+    async fn fetch_announcement(&self, validator: Address) -> Result<Option<SignedAnnouncement>>;
+    /// Write the signed announcement to this syncer
+    async fn write_announcement(&self, signed_announcement: &SignedAnnouncement) -> Result<()>;
 }
@@ -3,9 +3,9 @@ use ethers::types::Address;
 use std::collections::HashMap;
 use tracing::instrument;
 
-use abacus_core::SignedCheckpoint;
+use abacus_core::{SignedAnnouncement, SignedCheckpoint};
 use async_trait::async_trait;
-use eyre::{Report, Result};
+use eyre::{bail, eyre, Report, Result};
 
 use crate::S3Storage;
 use crate::{CheckpointSyncer, LocalStorage, MultisigCheckpointSyncer};
@@ -35,9 +35,46 @@ impl CheckpointSyncerConf {
             CheckpointSyncerConf::LocalStorage { path } => {
                 Ok(CheckpointSyncers::Local(LocalStorage::new(path)))
             }
-            CheckpointSyncerConf::S3 { bucket, region } => Ok(CheckpointSyncers::S3(
-                S3Storage::new(bucket, region.parse().expect("invalid s3 region")),
-            )),
+            CheckpointSyncerConf::S3 { bucket, region } => {
+                let region = region
+                    .parse()
+                    .map_err(|e| eyre!("Invalid s3 region {}: {}", region, e))?;
+                Ok(CheckpointSyncers::S3(S3Storage::new(bucket, region)))
+            }
+        }
+    }
+
+    /// The storage location string announced by validators using this
+    /// checkpoint syncer, e.g. `s3://bucket/region` or `file://path`
+    pub fn storage_location(&self) -> String {
+        match self {
+            CheckpointSyncerConf::LocalStorage { path } => format!("file://{}", path),
+            CheckpointSyncerConf::S3 { bucket, region } => format!("s3://{}/{}", bucket, region),
+        }
+    }
+}
+
+impl FromStr for CheckpointSyncerConf {
+    type Err = Report;
+
+    /// Parse a storage location string as produced by `storage_location`
+    fn from_str(location: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = location.strip_prefix("file://") {
+            Ok(CheckpointSyncerConf::LocalStorage {
+                path: path.to_owned(),
+            })
+        } else if let Some(s3) = location.strip_prefix("s3://") {
+            match s3.split_once('/') {
+                Some((bucket, region)) if !bucket.is_empty() && !region.is_empty() => {
+                    Ok(CheckpointSyncerConf::S3 {
+                        bucket: bucket.to_owned(),
+                        region: region.to_owned(),
+                    })
+                }
+                _ => bail!("Invalid s3 storage location: {}", location),
+            }
+        } else {
+            bail!("Unknown storage location: {}", location)
         }
     }
 }
@@ -49,13 +86,41 @@ pub struct MultisigCheckpointSyncerConf {
     /// The quorum threshold
     threshold: usize,
     /// The checkpoint syncer for each valid validator signer address
+    #[serde(default)]
     checkpointsyncers: HashMap<String, CheckpointSyncerConf>,
+    /// Comma separated list of validator signer addresses whose checkpoint
+    /// syncers are discovered through their signed announcements
+    validators: Option<String>,
+    /// Where validator announcements are read from. Required if
+    /// `validators` is set.
+    announcementsyncer: Option<CheckpointSyncerConf>,
 }
 
 impl MultisigCheckpointSyncerConf {
-    /// Get a MultisigCheckpointSyncer from the config
-    pub fn try_into_multisig_checkpoint_syncer(&self) -> Result<MultisigCheckpointSyncer, Report> {
+    /// Get a MultisigCheckpointSyncer from the config for the outbox on
+    /// `outbox_domain`. Explicitly configured checkpoint syncers take
+    /// precedence over announced ones.
+    pub async fn try_into_multisig_checkpoint_syncer(
+        &self,
+        outbox_domain: u32,
+    ) -> Result<MultisigCheckpointSyncer, Report> {
         let mut checkpoint_syncers = HashMap::new();
+        if let Some(validators) = &self.validators {
+            let announcement_syncer = match &self.announcementsyncer {
+                Some(conf) => conf.try_into_checkpoint_syncer()?,
+                None => bail!("validators configured without an announcementsyncer"),
+            };
+            let validators = validators
+                .split(',')
+                .map(|v| Address::from_str(v.trim()))
+                .collect::<Result<Vec<_>, _>>()?;
+            checkpoint_syncers = MultisigCheckpointSyncer::checkpoint_syncers_from_announcements(
+                outbox_domain,
+                &validators,
+                &announcement_syncer,
+            )
+            .await?;
+        }
         for (key, value) in self.checkpointsyncers.iter() {
             checkpoint_syncers.insert(Address::from_str(key)?, value.try_into_checkpoint_syncer()?);
         }
@@ -103,4 +168,49 @@ impl CheckpointSyncer for CheckpointSyncers {
             CheckpointSyncers::S3(syncer) => syncer.write_checkpoint(signed_checkpoint).await,
         }
     }
+
+    #[instrument(err, skip(self))]
+    /// Attempt to fetch the signed announcement made by `validator`
+    async fn fetch_announcement(&self, validator: Address) -> Result<Option<SignedAnnouncement>> {
+        match self {
+            CheckpointSyncers::Local(syncer) => syncer.fetch_announcement(validator).await,
+            CheckpointSyncers::S3(syncer) => syncer.fetch_announcement(validator).await,
+        }
+    }
+
+    #[instrument(err, skip(self))]
+    /// Write the signed announcement to this syncer
+    async fn write_announcement(&self, signed_announcement: &SignedAnnouncement) -> Result<()> {
+        match self {
+            CheckpointSyncers::Local(syncer) => {
+                syncer.write_announcement(signed_announcement).await
+            }
+            CheckpointSyncers::S3(syncer) => syncer.write_announcement(signed_announcement).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn storage_location_roundtrip() {
+        let local = CheckpointSyncerConf::LocalStorage {
+            path: "/tmp/checkpoints".to_owned(),
+        };
+        let s3 = CheckpointSyncerConf::S3 {
+            bucket: "checkpoints".to_owned(),
+            region: "us-east-1".to_owned(),
+        };
+        for conf in [local, s3] {
+            let location = conf.storage_location();
+            let parsed: CheckpointSyncerConf = location.parse().expect("!parse");
+            assert_eq!(parsed.storage_location(), location);
+        }
+        assert!("s3://bucket".parse::<CheckpointSyncerConf>().is_err());
+        assert!("gcs://bucket/region"
+            .parse::<CheckpointSyncerConf>()
+            .is_err());
+    }
 }
@@ -1,6 +1,7 @@
-use abacus_core::SignedCheckpoint;
+use abacus_core::{SignedAnnouncement, SignedCheckpoint};
 
 use async_trait::async_trait;
+use ethers::types::Address;
 use eyre::Result;
 
 use crate::traits::CheckpointSyncer;
@@ -31,6 +32,12 @@ impl LocalStorage {
         path
     }
 
+    fn announcement_file_path(&self, validator: Address) -> String {
+        let mut path = self.path.clone();
+        path.push_str(&format!("/announcement_{:?}.json", validator));
+        path
+    }
+
     async fn write_index(&self, index: u32) -> Result<()> {
         tokio::fs::write(self.latest_index_file_path(), index.to_string()).await?;
         Ok(())
@@ -81,4 +88,22 @@ impl CheckpointSyncer for LocalStorage {
 
         Ok(())
     }
+    async fn fetch_announcement(&self, validator: Address) -> Result<Option<SignedAnnouncement>> {
+        match tokio::fs::read(self.announcement_file_path(validator)).await {
+            Ok(data) => {
+                let announcement = serde_json::from_slice(&data)?;
+                Ok(Some(announcement))
+            }
+            _ => Ok(None),
+        }
+    }
+    async fn write_announcement(&self, signed_announcement: &SignedAnnouncement) -> Result<()> {
+        let serialized_announcement = serde_json::to_string_pretty(signed_announcement)?;
+        tokio::fs::write(
+            self.announcement_file_path(signed_announcement.announcement.validator),
+            &serialized_announcement,
+        )
+        .await?;
+        Ok(())
+    }
 }
@@ -5,9 +5,9 @@ use ethers::prelude::Address;
 use ethers::types::H256;
 
 use eyre::Result;
-use tracing::{debug, instrument};
+use tracing::{debug, info, instrument, warn};
 
-use crate::{CheckpointSyncer, CheckpointSyncers};
+use crate::{CheckpointSyncer, CheckpointSyncerConf, CheckpointSyncers};
 
 /// Fetches signed checkpoints from multiple validators to create MultisigSignedCheckpoints
 #[derive(Clone, Debug)]
@@ -27,6 +27,90 @@ impl MultisigCheckpointSyncer {
         }
     }
 
+    /// The quorum threshold
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The checkpoint syncer of each validator
+    pub fn checkpoint_syncers(&self) -> &HashMap<Address, CheckpointSyncers> {
+        &self.checkpoint_syncers
+    }
+
+    /// Constructs a MultisigCheckpointSyncer for `validators` by looking up
+    /// their signed announcements for `outbox_domain` in `announcement_syncer`.
+    pub async fn from_announcements(
+        threshold: usize,
+        outbox_domain: u32,
+        validators: &[Address],
+        announcement_syncer: &CheckpointSyncers,
+    ) -> Result<Self> {
+        let checkpoint_syncers = Self::checkpoint_syncers_from_announcements(
+            outbox_domain,
+            validators,
+            announcement_syncer,
+        )
+        .await?;
+        Ok(Self::new(threshold, checkpoint_syncers))
+    }
+
+    /// Builds the checkpoint syncer of each validator from its signed
+    /// announcement. Validators without a valid announcement for
+    /// `outbox_domain` are skipped.
+    #[instrument(err, skip(announcement_syncer))]
+    pub async fn checkpoint_syncers_from_announcements(
+        outbox_domain: u32,
+        validators: &[Address],
+        announcement_syncer: &CheckpointSyncers,
+    ) -> Result<HashMap<Address, CheckpointSyncers>> {
+        let mut checkpoint_syncers = HashMap::new();
+        for validator in validators {
+            let signed_announcement = match announcement_syncer.fetch_announcement(*validator).await
+            {
+                Ok(Some(signed_announcement)) => signed_announcement,
+                Ok(None) => {
+                    warn!(validator=?validator, "No announcement found for validator");
+                    continue;
+                }
+                Err(e) => {
+                    warn!(validator=?validator, error=%e, "Failed to fetch announcement for validator");
+                    continue;
+                }
+            };
+            // Ensure the announcement is for this validator and signed by it
+            if signed_announcement.announcement.validator != *validator
+                || signed_announcement.verify().is_err()
+            {
+                warn!(validator=?validator, announcement=?signed_announcement, "Invalid announcement for validator");
+                continue;
+            }
+            // Checkpoints signed for another outbox would never match ours
+            if signed_announcement.announcement.outbox_domain != outbox_domain {
+                warn!(
+                    validator=?validator,
+                    announced_domain=signed_announcement.announcement.outbox_domain,
+                    outbox_domain,
+                    "Ignoring announcement for another outbox domain"
+                );
+                continue;
+            }
+            let location = &signed_announcement.announcement.storage_location;
+            let checkpoint_syncer = match location
+                .parse::<CheckpointSyncerConf>()
+                .and_then(|conf| conf.try_into_checkpoint_syncer())
+            {
+                Ok(checkpoint_syncer) => checkpoint_syncer,
+                Err(e) => {
+                    warn!(validator=?validator, error=%e, "Unsupported announced storage location");
+                    continue;
+                }
+            };
+            info!(validator=?validator, location=%location, "Discovered validator checkpoint syncer");
+            checkpoint_syncers.insert(*validator, checkpoint_syncer);
+        }
+        Ok(checkpoint_syncers)
+    }
+
     /// Fetches a MultisigSignedCheckpoint if there is a quorum.
     /// Returns Ok(None) if there is no quorum.
     #[instrument(err, skip(self))]
@@ -87,13 +171,14 @@ impl MultisigCheckpointSyncer {
     }
 
     /// Attempts to get the latest index with a quorum of signatures among validators.
-    /// First iterates through the `latest_index` of each validator's checkpoint syncer,
+    /// When validators sign every index, the `threshold`-th highest `latest_index` among
+    /// the validators' checkpoint syncers has been signed by at least `threshold` validators,
+    /// so that index is checked for a quorum first.
+    /// Otherwise iterates through the `latest_index` of each validator's checkpoint syncer,
     /// looking for the highest index that >= `threshold` validators have returned.
     /// If there isn't a quorum found this way, each unique index from the highest -> lowest
     /// is checked for a quorum of signed checkpoints using `fetch_checkpoint`.
-    /// Note it's possible for both strategies for finding the latest index to not find a quorum.
-    /// A more robust implementation should be considered in the future that indexes historical
-    /// checkpoint indices.
+    /// Note it's possible for all strategies for finding the latest index to not find a quorum.
     #[instrument(err, skip(self))]
     pub async fn latest_index(&self) -> Result<Option<u32>> {
         // Get the latest_index from each validator's checkpoint syncer.
@@ -113,6 +198,18 @@ impl MultisigCheckpointSyncer {
         // Sort in descending order to iterate through higher indices first.
         latest_indices.sort_by(|a, b| b.cmp(a));
 
+        // If validators sign every index, all validators whose latest index is at least
+        // the `threshold`-th highest have signed it.
+        if let Some(candidate_index) = self
+            .threshold
+            .checked_sub(1)
+            .and_then(|i| latest_indices.get(i))
+        {
+            if let Ok(Some(_)) = self.fetch_checkpoint(*candidate_index).await {
+                return Ok(Some(*candidate_index));
+            }
+        }
+
         let mut last_processed_index = 0;
 
         // Try to find a quorum among the latest indices
@@ -151,3 +248,59 @@ impl MultisigCheckpointSyncer {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use abacus_core::Announcement;
+    use ethers::core::rand::thread_rng;
+    use ethers::signers::{LocalWallet, Signer};
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::LocalStorage;
+
+    #[tokio::test]
+    async fn it_skips_validators_with_bad_announcements() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let announcement_syncer = CheckpointSyncers::Local(LocalStorage::new(path));
+
+        let signers: Vec<_> = (0..3)
+            .map(|_| LocalWallet::new(&mut thread_rng()))
+            .collect();
+        let validators: Vec<_> = signers.iter().map(|s| s.address()).collect();
+        for (signer, location) in signers[..2].iter().zip([
+            format!("file://{}", path),
+            "s3://bucket/not-a-region".to_owned(),
+        ]) {
+            let announcement = Announcement {
+                validator: signer.address(),
+                outbox_domain: 1,
+                storage_location: location,
+            }
+            .sign_with(signer)
+            .await
+            .unwrap();
+            announcement_syncer
+                .write_announcement(&announcement)
+                .await
+                .unwrap();
+        }
+        // The last validator's announcement can't be fetched
+        std::fs::write(
+            format!("{}/announcement_{:?}.json", path, validators[2]),
+            "not json",
+        )
+        .unwrap();
+
+        let checkpoint_syncers = MultisigCheckpointSyncer::checkpoint_syncers_from_announcements(
+            1,
+            &validators,
+            &announcement_syncer,
+        )
+        .await
+        .unwrap();
+        assert_eq!(checkpoint_syncers.len(), 1);
+        assert!(checkpoint_syncers.contains_key(&validators[0]));
+    }
+}
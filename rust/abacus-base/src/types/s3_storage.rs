@@ -1,7 +1,8 @@
 use std::fmt;
 
-use abacus_core::SignedCheckpoint;
+use abacus_core::{SignedAnnouncement, SignedCheckpoint};
 use async_trait::async_trait;
+use ethers::types::Address;
 use eyre::{bail, Result};
 use futures_util::TryStreamExt;
 use rusoto_core::{credential::EnvironmentProvider, HttpClient, Region, RusotoError};
@@ -79,6 +80,9 @@ impl S3Storage {
     fn index_key() -> String {
         "checkpoint_latest_index.json".to_owned()
     }
+    fn announcement_key(validator: Address) -> String {
+        format!("announcement_{:?}.json", validator)
+    }
 }
 
 #[async_trait]
@@ -112,4 +116,20 @@ impl CheckpointSyncer for S3Storage {
         .await?;
         Ok(())
     }
+    async fn fetch_announcement(&self, validator: Address) -> Result<Option<SignedAnnouncement>> {
+        self.read_from_bucket(S3Storage::announcement_key(validator))
+            .await?
+            .map(|data| serde_json::from_slice(&data))
+            .transpose()
+            .map_err(Into::into)
+    }
+    async fn write_announcement(&self, signed_announcement: &SignedAnnouncement) -> Result<()> {
+        let serialized_announcement = serde_json::to_string_pretty(signed_announcement)?;
+        self.write_to_bucket(
+            S3Storage::announcement_key(signed_announcement.announcement.validator),
+            &serialized_announcement,
+        )
+        .await?;
+        Ok(())
+    }
 }
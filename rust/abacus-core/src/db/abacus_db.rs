@@ -1,13 +1,16 @@
 use crate::db::{DbError, TypedDB, DB};
 use crate::{
-    accumulator::merkle::Proof, traits::RawCommittedMessage, AbacusMessage, CommittedMessage,
-    Decode, InterchainGasPayment, InterchainGasPaymentMeta, InterchainGasPaymentWithMeta,
+    accumulator::merkle::Proof,
+    traits::{MessageDispatchMeta, RawCommittedMessage, RawCommittedMessageWithMeta},
+    AbacusMessage, CheckpointWithMeta, CommittedMessage, Decode, InterchainGasPayment,
+    InterchainGasPaymentMeta, InterchainGasPaymentWithMeta,
 };
 use ethers::core::types::{H256, U256};
 use eyre::Result;
 use tokio::time::sleep;
 use tracing::{debug, info, warn};
 
+use std::collections::HashSet;
 use std::future::Future;
 use std::time::Duration;
 
@@ -17,11 +20,24 @@ static LEAF_IDX: &str = "leaf_index_";
 static LEAF: &str = "leaf_";
 static PROOF: &str = "proof_";
 static MESSAGE: &str = "message_";
+static MESSAGE_DISPATCH_META: &str = "message_dispatch_meta_";
 static LATEST_LEAF_INDEX: &str = "latest_known_leaf_index_";
 static LATEST_LEAF_INDEX_FOR_DESTINATION: &str = "latest_known_leaf_index_for_destination_";
 static LEAF_PROCESS_STATUS: &str = "leaf_process_status_";
+static LEAF_SKIPPED: &str = "leaf_skipped_";
 static GAS_PAYMENT_FOR_LEAF: &str = "gas_payment_for_leaf_";
 static GAS_PAYMENT_META_PROCESSED: &str = "gas_payment_meta_processed_";
+static GAS_PAYMENT_META_BLOCK: &str = "gas_payment_meta_block_";
+static LOWEST_UNFINALIZED_LEAF_INDEX: &str = "lowest_unfinalized_leaf_index_";
+static DELIVERED_MESSAGE: &str = "delivered_message_";
+static CACHED_CHECKPOINT: &str = "cached_checkpoint_";
+static CACHED_CHECKPOINT_COUNT: &str = "cached_checkpoint_count_";
+static PRUNED_THROUGH_LEAF_INDEX: &str = "pruned_through_leaf_index_";
+
+/// Delivered message statuses. Deliveries stored before finality was
+/// recorded count as finalized.
+const DELIVERY_FINALIZED: u32 = 1;
+const DELIVERY_UNFINALIZED: u32 = 2;
 
 /// DB handle for storing data tied to a specific Outbox.
 ///
@@ -55,11 +71,25 @@ impl AbacusDB {
         Self(TypedDB::new(entity.as_ref().to_owned(), db))
     }
 
-    /// Store list of messages
-    pub fn store_messages(&self, messages: &[RawCommittedMessage]) -> Result<u32> {
+    /// Stage the writes `f` makes to a view of the DB, then apply them
+    /// atomically once it succeeds, so that nothing is written if it fails
+    pub fn write_staged<T, E>(
+        &self,
+        f: impl FnOnce(&AbacusDB) -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E>
+    where
+        E: From<DbError>,
+    {
+        let db: &DB = self.as_ref();
+        db.write_staged(|staged| f(&AbacusDB::new(self.entity(), staged.clone())))
+    }
+
+    /// Store list of messages, along with metadata about their dispatch
+    pub fn store_messages(&self, messages: &[RawCommittedMessageWithMeta]) -> Result<u32> {
         let mut latest_leaf_index: u32 = 0;
-        for message in messages {
+        for RawCommittedMessageWithMeta { message, meta } in messages {
             self.store_latest_message(message)?;
+            self.store_message_dispatch_meta(message.leaf_index, meta)?;
 
             let committed_message: CommittedMessage = message.try_into()?;
             info!(
@@ -111,6 +141,23 @@ impl AbacusDB {
         Ok(())
     }
 
+    /// Store metadata about the dispatch of the message at `leaf_index`
+    pub fn store_message_dispatch_meta(
+        &self,
+        leaf_index: u32,
+        meta: &MessageDispatchMeta,
+    ) -> Result<(), DbError> {
+        self.store_keyed_encodable(MESSAGE_DISPATCH_META, &leaf_index, meta)
+    }
+
+    /// Retrieve metadata about the dispatch of the message at `leaf_index`
+    pub fn message_dispatch_meta_by_leaf_index(
+        &self,
+        leaf_index: u32,
+    ) -> Result<Option<MessageDispatchMeta>, DbError> {
+        self.retrieve_keyed_decodable(MESSAGE_DISPATCH_META, &leaf_index)
+    }
+
     /// Store the latest known leaf_index
     ///
     /// Key --> value: `LATEST_LEAF_INDEX` --> `leaf_index`
@@ -164,6 +211,14 @@ impl AbacusDB {
         self.update_latest_leaf_index_for_destination(destination, leaf_index)
     }
 
+    /// Store the hash of a processed leaf whose message was pruned, e.g.
+    /// when restoring a DB from a snapshot
+    pub fn store_pruned_leaf(&self, leaf_index: u32, leaf: H256) -> Result<(), DbError> {
+        self.store_keyed_encodable(LEAF, &leaf_index, &leaf)?;
+        self.update_latest_leaf_index(leaf_index)?;
+        self.mark_leaf_as_processed(leaf_index)
+    }
+
     /// Retrieve a raw committed message by its leaf hash
     pub fn message_by_leaf(&self, leaf: H256) -> Result<Option<RawCommittedMessage>, DbError> {
         self.retrieve_keyed_decodable(MESSAGE, &leaf)
@@ -186,6 +241,95 @@ impl AbacusDB {
         }
     }
 
+    /// Record the lowest leaf index indexed from a block which has yet to
+    /// reach finality, or `None` once every stored leaf is final. Leaves from
+    /// this index onwards may still be rolled back.
+    pub fn store_lowest_unfinalized_leaf_index(
+        &self,
+        leaf_index: Option<u32>,
+    ) -> Result<(), DbError> {
+        match leaf_index {
+            Some(leaf_index) => {
+                self.store_encodable("", LOWEST_UNFINALIZED_LEAF_INDEX, &leaf_index)
+            }
+            None => self.delete("", LOWEST_UNFINALIZED_LEAF_INDEX),
+        }
+    }
+
+    /// Retrieve the lowest leaf index indexed from a block which has yet to
+    /// reach finality
+    pub fn retrieve_lowest_unfinalized_leaf_index(&self) -> Result<Option<u32>, DbError> {
+        self.retrieve_decodable("", LOWEST_UNFINALIZED_LEAF_INDEX)
+    }
+
+    /// Retrieve the leaf hash keyed by leaf index, only if it was indexed
+    /// from a block which has reached finality
+    pub fn finalized_leaf_by_leaf_index(&self, leaf_index: u32) -> Result<Option<H256>, DbError> {
+        match self.retrieve_lowest_unfinalized_leaf_index()? {
+            Some(unfinalized) if leaf_index >= unfinalized => Ok(None),
+            _ => self.leaf_by_leaf_index(leaf_index),
+        }
+    }
+
+    /// Remove every message from `leaf_index` onwards, e.g. because the
+    /// blocks they were indexed from were reorged out of the canonical chain
+    pub fn rollback_messages(&self, leaf_index: u32) -> Result<(), DbError> {
+        let latest_leaf_index = match self.retrieve_latest_leaf_index()? {
+            Some(latest_leaf_index) if latest_leaf_index >= leaf_index => latest_leaf_index,
+            _ => return Ok(()),
+        };
+        warn!(
+            from = leaf_index,
+            to = latest_leaf_index,
+            "Rolling back messages"
+        );
+
+        let mut destinations = HashSet::new();
+        for index in leaf_index..=latest_leaf_index {
+            if let Some(leaf) = self.leaf_by_leaf_index(index)? {
+                if let Some(message) = self.message_by_leaf(leaf)? {
+                    let parsed = AbacusMessage::read_from(&mut message.message.as_slice())?;
+                    destinations.insert(parsed.destination);
+                }
+                self.delete_keyed(MESSAGE, &leaf)?;
+                self.delete_keyed(LEAF, &index)?;
+            }
+            self.delete_keyed(PROOF, &index)?;
+            self.delete_keyed(MESSAGE_DISPATCH_META, &index)?;
+            self.delete_keyed(LEAF_SKIPPED, &index)?;
+        }
+
+        match leaf_index.checked_sub(1) {
+            Some(index) => self.store_encodable("", LATEST_LEAF_INDEX, &index)?,
+            None => self.delete("", LATEST_LEAF_INDEX)?,
+        }
+
+        // Find the latest remaining leaf for each destination that lost one.
+        // Reorgs are rare and shallow, so walking back through leaves is fine.
+        for destination in destinations {
+            let mut latest_for_destination = None;
+            for index in (0..leaf_index).rev() {
+                let message = self.message_by_leaf_index(index)?;
+                if let Some(message) = message {
+                    let parsed = AbacusMessage::read_from(&mut message.message.as_slice())?;
+                    if parsed.destination == destination {
+                        latest_for_destination = Some(index);
+                        break;
+                    }
+                }
+            }
+            match latest_for_destination {
+                Some(index) => self.store_keyed_encodable(
+                    LATEST_LEAF_INDEX_FOR_DESTINATION,
+                    &destination,
+                    &index,
+                )?,
+                None => self.delete_keyed(LATEST_LEAF_INDEX_FOR_DESTINATION, &destination)?,
+            }
+        }
+        Ok(())
+    }
+
     /// Iterate over all leaves
     pub fn leaf_iterator(&self) -> PrefixIterator<H256> {
         PrefixIterator::new(self.0.as_ref().prefix_iterator(LEAF_IDX), LEAF_IDX.as_ref())
@@ -205,6 +349,23 @@ impl AbacusDB {
         self.retrieve_keyed_decodable(PROOF, &leaf_index)
     }
 
+    /// Poll the db every 100 milliseconds waiting for a leaf which was
+    /// indexed from a block that has reached finality
+    pub fn wait_for_finalized_leaf(
+        &self,
+        leaf_index: u32,
+    ) -> impl Future<Output = Result<H256, DbError>> {
+        let slf = self.clone();
+        async move {
+            loop {
+                if let Some(leaf) = slf.finalized_leaf_by_leaf_index(leaf_index)? {
+                    return Ok(leaf);
+                }
+                sleep(Duration::from_millis(100)).await
+            }
+        }
+    }
+
     // TODO(james): this is a quick-fix for the prover_sync and I don't like it
     /// poll db ever 100 milliseconds waitinf for a leaf.
     pub fn wait_for_leaf(&self, leaf_index: u32) -> impl Future<Output = Result<H256, DbError>> {
@@ -219,12 +380,113 @@ impl AbacusDB {
         }
     }
 
+    /// Delete the message bodies, proofs, dispatch metadata and gas payment
+    /// totals of leaves which won't be relayed again, other than the latest
+    /// `retained_leaves` and any which may yet be rolled back. Those are the
+    /// leaves processed or skipped, and those for destinations outside
+    /// `relayed_destinations`.
+    /// Leaf hashes are kept, so the tree can still be rebuilt and snapshots
+    /// exported, and pruned leaves are marked processed. Returns the number
+    /// of leaves pruned.
+    pub fn prune_processed_leaves(
+        &self,
+        retained_leaves: u32,
+        relayed_destinations: &HashSet<u32>,
+    ) -> Result<u32, DbError> {
+        let latest_leaf_index = match self.retrieve_latest_leaf_index()? {
+            Some(latest_leaf_index) => latest_leaf_index,
+            None => return Ok(0),
+        };
+        // Always keep the latest leaf, which syncing resumes from
+        let mut end = (latest_leaf_index + 1).saturating_sub(retained_leaves.max(1));
+        if let Some(unfinalized) = self.retrieve_lowest_unfinalized_leaf_index()? {
+            end = end.min(unfinalized);
+        }
+
+        // Leaves up to the first one left to relay have all been pruned
+        let mut start = self
+            .retrieve_pruned_through_leaf_index()?
+            .map_or(0, |index| index + 1);
+        let mut pruned = 0;
+        for leaf_index in start..end {
+            let leaf = self.leaf_by_leaf_index(leaf_index)?;
+            let message = match leaf {
+                Some(leaf) => self.message_by_leaf(leaf)?,
+                None => None,
+            };
+            let relayed = match message.as_ref() {
+                Some(message) => {
+                    let destination =
+                        AbacusMessage::read_from(&mut message.message.as_slice())?.destination;
+                    relayed_destinations.contains(&destination)
+                }
+                None => true,
+            };
+            let done = !relayed
+                || self
+                    .retrieve_leaf_processing_status(leaf_index)?
+                    .unwrap_or_default()
+                || self.leaf_skipped(leaf_index)?;
+            if !done {
+                continue;
+            }
+            if leaf_index == start {
+                start += 1;
+            }
+            let leaf = match (leaf, message) {
+                (Some(leaf), Some(_)) => leaf,
+                _ => continue,
+            };
+            self.delete_keyed(MESSAGE, &leaf)?;
+            self.delete_keyed(PROOF, &leaf_index)?;
+            self.delete_keyed(MESSAGE_DISPATCH_META, &leaf_index)?;
+            self.delete_keyed(GAS_PAYMENT_FOR_LEAF, &leaf_index)?;
+            // Without its message, the leaf can't be relayed any more
+            self.mark_leaf_as_processed(leaf_index)?;
+            pruned += 1;
+        }
+        if let Some(pruned_through) = start.checked_sub(1) {
+            self.store_encodable("", PRUNED_THROUGH_LEAF_INDEX, &pruned_through)?;
+        }
+
+        if pruned > 0 {
+            info!(pruned, before = end, "Pruned leaves");
+        }
+        Ok(pruned)
+    }
+
+    /// Retrieve the leaf index through which every leaf has been pruned
+    pub fn retrieve_pruned_through_leaf_index(&self) -> Result<Option<u32>, DbError> {
+        self.retrieve_decodable("", PRUNED_THROUGH_LEAF_INDEX)
+    }
+
     /// Mark leaf as processed
     pub fn mark_leaf_as_processed(&self, leaf_index: u32) -> Result<(), DbError> {
         debug!(leaf_index = ?leaf_index, "mark leaf as processed");
         self.store_keyed_encodable(LEAF_PROCESS_STATUS, &leaf_index, &(1_u32))
     }
 
+    /// Mark a leaf as skipped by the relayer for its destination, e.g.
+    /// because its message was delivered by another relayer, so that it can
+    /// be pruned
+    pub fn mark_leaf_as_skipped(&self, leaf_index: u32) -> Result<(), DbError> {
+        debug!(leaf_index = ?leaf_index, "mark leaf as skipped");
+        self.store_keyed_encodable(LEAF_SKIPPED, &leaf_index, &true)
+    }
+
+    /// Whether a leaf was skipped by the relayer for its destination
+    pub fn leaf_skipped(&self, leaf_index: u32) -> Result<bool, DbError> {
+        Ok(self
+            .retrieve_keyed_decodable(LEAF_SKIPPED, &leaf_index)?
+            .unwrap_or(false))
+    }
+
+    /// Clear the processed flag of a leaf, so that it's processed again
+    pub fn unmark_leaf_as_processed(&self, leaf_index: u32) -> Result<(), DbError> {
+        debug!(leaf_index = ?leaf_index, "unmark leaf as processed");
+        self.delete_keyed(LEAF_PROCESS_STATUS, &leaf_index)
+    }
+
     /// Retrieve leaf processing status
     pub fn retrieve_leaf_processing_status(
         &self,
@@ -234,11 +496,134 @@ impl AbacusDB {
         Ok(value.map(|x| x == 1))
     }
 
+    /// Record that the message with leaf `leaf` was delivered to the inbox,
+    /// in a block which has reached finality or not
+    pub fn store_delivered_message(&self, leaf: H256, finalized: bool) -> Result<(), DbError> {
+        debug!(leaf = ?leaf, finalized, "store delivered message");
+        let status = if finalized {
+            DELIVERY_FINALIZED
+        } else {
+            DELIVERY_UNFINALIZED
+        };
+        self.store_keyed_encodable(DELIVERED_MESSAGE, &leaf, &status)
+    }
+
+    /// Record that the block delivering the message with leaf `leaf` has
+    /// reached finality
+    pub fn finalize_delivered_message(&self, leaf: H256) -> Result<(), DbError> {
+        if self.message_delivered(leaf)? {
+            self.store_keyed_encodable(DELIVERED_MESSAGE, &leaf, &DELIVERY_FINALIZED)?;
+        }
+        Ok(())
+    }
+
+    /// Forget that the message with leaf `leaf` was delivered, e.g. because
+    /// the block delivering it was reorged out
+    pub fn remove_delivered_message(&self, leaf: H256) -> Result<(), DbError> {
+        debug!(leaf = ?leaf, "remove delivered message");
+        self.delete_keyed(DELIVERED_MESSAGE, &leaf)
+    }
+
+    /// Whether the message with leaf `leaf` is known to have been delivered
+    /// to the inbox
+    pub fn message_delivered(&self, leaf: H256) -> Result<bool, DbError> {
+        let value: Option<u32> = self.retrieve_keyed_decodable(DELIVERED_MESSAGE, &leaf)?;
+        Ok(value.is_some())
+    }
+
+    /// Whether the message with leaf `leaf` is known to have been delivered
+    /// to the inbox in a block which has reached finality, so that the
+    /// delivery can't be reorged out
+    pub fn message_delivery_finalized(&self, leaf: H256) -> Result<bool, DbError> {
+        let value: Option<u32> = self.retrieve_keyed_decodable(DELIVERED_MESSAGE, &leaf)?;
+        Ok(value == Some(DELIVERY_FINALIZED))
+    }
+
+    /// Store checkpoints cached on the outbox, in the order they were cached.
+    /// Checkpoints cached no later than the latest one stored are skipped, so
+    /// that blocks which are indexed again aren't stored twice.
+    pub fn store_cached_checkpoints(
+        &self,
+        checkpoints: &[CheckpointWithMeta],
+    ) -> Result<(), DbError> {
+        let mut count = self.retrieve_cached_checkpoint_count()?;
+        let mut latest = self
+            .latest_cached_checkpoint()?
+            .map(|c| (c.metadata.block_number, c.checkpoint.index));
+        for checkpoint in checkpoints {
+            let position = (
+                checkpoint.metadata.block_number,
+                checkpoint.checkpoint.index,
+            );
+            if latest.map_or(false, |latest| position <= latest) {
+                continue;
+            }
+            debug!(
+                index = checkpoint.checkpoint.index,
+                block_number = checkpoint.metadata.block_number,
+                "store cached checkpoint"
+            );
+            self.store_keyed_encodable(CACHED_CHECKPOINT, &count, checkpoint)?;
+            count += 1;
+            self.store_encodable("", CACHED_CHECKPOINT_COUNT, &count)?;
+            latest = Some(position);
+        }
+        Ok(())
+    }
+
+    /// The number of cached checkpoints stored
+    pub fn retrieve_cached_checkpoint_count(&self) -> Result<u32, DbError> {
+        Ok(self
+            .retrieve_decodable("", CACHED_CHECKPOINT_COUNT)?
+            .unwrap_or_default())
+    }
+
+    /// The `n`th checkpoint cached on the outbox, counting from 0
+    pub fn cached_checkpoint(&self, n: u32) -> Result<Option<CheckpointWithMeta>, DbError> {
+        self.retrieve_keyed_decodable(CACHED_CHECKPOINT, &n)
+    }
+
+    /// The latest checkpoint cached on the outbox
+    pub fn latest_cached_checkpoint(&self) -> Result<Option<CheckpointWithMeta>, DbError> {
+        match self.retrieve_cached_checkpoint_count()?.checked_sub(1) {
+            Some(n) => self.cached_checkpoint(n),
+            None => Ok(None),
+        }
+    }
+
+    /// The latest checkpoint cached on the outbox at or before block
+    /// `block_number`, i.e. the root anchored on-chain as of that block
+    pub fn cached_checkpoint_at_block(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<CheckpointWithMeta>, DbError> {
+        // Checkpoints are stored in the order they were cached, so binary
+        // search for the first one cached after the block
+        let (mut low, mut high) = (0, self.retrieve_cached_checkpoint_count()?);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let cached_before = self
+                .cached_checkpoint(mid)?
+                .map_or(false, |c| c.metadata.block_number <= block_number);
+            if cached_before {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        match low.checked_sub(1) {
+            Some(n) => self.cached_checkpoint(n),
+            None => Ok(None),
+        }
+    }
+
     /// If the provided gas payment, identified by its metadata, has not been processed,
-    /// processes the gas payment and records it as processed.
+    /// processes the gas payment and records it as processed. `indexed_through`
+    /// is the last block of the range the payment was indexed from.
     pub fn process_gas_payment(
         &self,
         gas_payment_with_meta: &InterchainGasPaymentWithMeta,
+        indexed_through: u32,
     ) -> Result<(), DbError> {
         let meta = &gas_payment_with_meta.meta;
         // If the gas payment has already been processed, do nothing
@@ -248,6 +633,7 @@ impl AbacusDB {
         }
         // Set the gas payment as processed
         self.store_gas_payment_meta_processed(meta)?;
+        self.store_keyed_encodable(GAS_PAYMENT_META_BLOCK, meta, &indexed_through)?;
 
         // Update the total gas payment for the leaf to include the payment
         self.update_gas_payment_for_leaf(&gas_payment_with_meta.payment)?;
@@ -255,8 +641,30 @@ impl AbacusDB {
         Ok(())
     }
 
+    /// Undo a processed gas payment, e.g. because the block it was indexed
+    /// from was reorged out of the canonical chain
+    pub fn rollback_gas_payment(
+        &self,
+        gas_payment_with_meta: &InterchainGasPaymentWithMeta,
+    ) -> Result<(), DbError> {
+        let meta = &gas_payment_with_meta.meta;
+        if !self.retrieve_gas_payment_meta_processed(meta)? {
+            return Ok(());
+        }
+        self.store_keyed_encodable(GAS_PAYMENT_META_PROCESSED, meta, &false)?;
+
+        let InterchainGasPayment { leaf_index, amount } = &gas_payment_with_meta.payment;
+        let existing_payment = self.retrieve_gas_payment_for_leaf(*leaf_index)?;
+        let total = existing_payment.saturating_sub(*amount);
+
+        info!(leaf_index=?leaf_index, gas_payment_amount=?amount, new_total_gas_payment=?total, "Rolling back gas payment");
+        self.store_keyed_encodable(GAS_PAYMENT_FOR_LEAF, leaf_index, &total)?;
+
+        Ok(())
+    }
+
     /// Record a gas payment, identified by its metadata, as processed
-    fn store_gas_payment_meta_processed(
+    pub fn store_gas_payment_meta_processed(
         &self,
         gas_payment_meta: &InterchainGasPaymentMeta,
     ) -> Result<(), DbError> {
@@ -273,6 +681,40 @@ impl AbacusDB {
             .unwrap_or(false))
     }
 
+    /// Retrieve the metadata of every gas payment processed
+    pub fn retrieve_processed_gas_payment_metas(
+        &self,
+    ) -> Result<Vec<InterchainGasPaymentMeta>, DbError> {
+        Ok(self
+            .retrieve_all_keyed_decodable(GAS_PAYMENT_META_PROCESSED)?
+            .into_iter()
+            .filter(|(_, processed)| *processed)
+            .map(|(meta, _)| meta)
+            .collect())
+    }
+
+    /// Delete the processed flags of gas payments indexed from ranges ending
+    /// before `block`, which won't be indexed again. Payments recorded as
+    /// processed without the block they were indexed through, e.g. those
+    /// imported from a snapshot, are kept. Returns the number deleted.
+    pub fn prune_processed_gas_payment_metas(&self, block: u32) -> Result<u32, DbError> {
+        let mut pruned = 0;
+        for (meta, indexed_through) in self
+            .retrieve_all_keyed_decodable::<InterchainGasPaymentMeta, u32>(GAS_PAYMENT_META_BLOCK)?
+        {
+            if indexed_through >= block {
+                continue;
+            }
+            self.delete_keyed(GAS_PAYMENT_META_PROCESSED, &meta)?;
+            self.delete_keyed(GAS_PAYMENT_META_BLOCK, &meta)?;
+            pruned += 1;
+        }
+        if pruned > 0 {
+            info!(pruned, before = block, "Pruned processed gas payments");
+        }
+        Ok(pruned)
+    }
+
     /// Update the total gas payment for a leaf index to include gas_payment
     fn update_gas_payment_for_leaf(
         &self,
@@ -289,9 +731,21 @@ impl AbacusDB {
     }
 
     /// Retrieve the total gas payment for a leaf index
-    fn retrieve_gas_payment_for_leaf(&self, leaf_index: u32) -> Result<U256, DbError> {
+    pub fn retrieve_gas_payment_for_leaf(&self, leaf_index: u32) -> Result<U256, DbError> {
         Ok(self
             .retrieve_keyed_decodable(GAS_PAYMENT_FOR_LEAF, &leaf_index)?
             .unwrap_or(U256::zero()))
     }
+
+    /// Store the total gas payment for a leaf index, replacing any total
+    /// already stored
+    pub fn store_gas_payment_for_leaf(&self, leaf_index: u32, total: U256) -> Result<(), DbError> {
+        self.store_keyed_encodable(GAS_PAYMENT_FOR_LEAF, &leaf_index, &total)
+    }
+
+    /// Retrieve the total gas payment for every leaf index paid for, ordered
+    /// by leaf index
+    pub fn retrieve_gas_payments_by_leaf(&self) -> Result<Vec<(u32, U256)>, DbError> {
+        self.retrieve_all_keyed_decodable(GAS_PAYMENT_FOR_LEAF)
+    }
 }
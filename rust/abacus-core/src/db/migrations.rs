@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn};
+
+use crate::db::{DbError, WriteBatch, DB};
+use crate::Encode;
+
+/// The key the schema version is stored under. It isn't scoped to an
+/// entity, as every entity in a DB shares the same layout.
+static SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// The schema version of DBs written by this version of the agents
+pub const SCHEMA_VERSION: u32 = 1;
+
+type Result<T> = std::result::Result<T, DbError>;
+
+/// A change to the layout of the data stored in the DB. As a migration
+/// interrupted before the schema version is updated runs again when the DB
+/// is next opened, migrations must be idempotent.
+pub struct Migration {
+    /// The schema version the migration upgrades to
+    pub version: u32,
+    /// What the migration changes
+    pub description: &'static str,
+    /// Stage the changes to the DB in the batch, which isn't written during
+    /// a dry run
+    pub migrate: fn(&DB, &mut WriteBatch) -> Result<()>,
+}
+
+/// Every migration, ordered by the schema version they upgrade to
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "Record the schema version of DBs predating versioning",
+    migrate: |_, _| Ok(()),
+}];
+
+/// How to migrate a DB whose schema is out of date
+#[derive(Debug, Clone, Default)]
+pub struct MigrationOptions {
+    /// Only log the migrations which would run and how many changes they
+    /// would make. A DB which needs migrating is then left as it is and
+    /// not opened.
+    pub dry_run: bool,
+    /// A directory to snapshot the DB to before migrating it
+    pub backup_dir: Option<PathBuf>,
+}
+
+impl DB {
+    /// The schema version of the DB, if it has been recorded
+    pub fn schema_version(&self) -> Result<Option<u32>> {
+        self.retrieve_decodable("", SCHEMA_VERSION_KEY)
+    }
+
+    /// Whether nothing has been stored in the DB
+    fn is_empty(&self) -> bool {
+        self.prefix_iterator("").next().is_none()
+    }
+
+    /// Run the migrations the DB hasn't yet had applied, in order, refusing
+    /// to use a DB written with a newer schema
+    pub(crate) fn migrate(&self, options: &MigrationOptions) -> Result<()> {
+        let version = match self.schema_version()? {
+            Some(version) => version,
+            // There's nothing to migrate in a new DB
+            None if self.is_empty() => {
+                return self.store_encodable("", SCHEMA_VERSION_KEY, &SCHEMA_VERSION)
+            }
+            None => 0,
+        };
+        if version > SCHEMA_VERSION {
+            return Err(DbError::UnsupportedSchemaVersion {
+                found: version,
+                supported: SCHEMA_VERSION,
+            });
+        }
+
+        let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > version).collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        if options.dry_run {
+            for migration in pending.iter() {
+                let mut batch = WriteBatch::default();
+                (migration.migrate)(self, &mut batch)?;
+                info!(
+                    version = migration.version,
+                    description = migration.description,
+                    changes = batch.len(),
+                    "Dry run: would migrate DB to schema version {}",
+                    migration.version,
+                );
+            }
+            return Err(DbError::MigrationDryRun {
+                version,
+                pending: pending.len(),
+            });
+        }
+
+        if let Some(backup_dir) = options.backup_dir.as_ref() {
+            self.backup(backup_dir, version)?;
+        }
+
+        for migration in pending {
+            let mut batch = WriteBatch::default();
+            (migration.migrate)(self, &mut batch)?;
+            info!(
+                version = migration.version,
+                description = migration.description,
+                changes = batch.len(),
+                "Migrating DB to schema version {}",
+                migration.version,
+            );
+            // Record the new version along with the changes, so they're
+            // applied atomically
+            batch.put(SCHEMA_VERSION_KEY, migration.version.to_vec());
+            self.write(batch)?;
+        }
+        Ok(())
+    }
+
+    /// Snapshot the DB to a new directory within `backup_dir`
+    fn backup(&self, backup_dir: &Path, version: u32) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let path = backup_dir.join(format!("schema_v{}_{}", version, timestamp));
+        warn!(path = ?path, "Backing up DB before migrating it");
+        self.0.backup(&path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn migrations_are_ordered_up_to_the_schema_version() {
+        assert!(MIGRATIONS.windows(2).all(|m| m[0].version < m[1].version));
+        assert_eq!(MIGRATIONS.last().map(|m| m.version), Some(SCHEMA_VERSION));
+    }
+}
@@ -1,11 +1,20 @@
+#[cfg(feature = "rocksdb")]
 use eyre::WrapErr;
-use rocksdb::{DBIterator, Options, DB as Rocks};
-use std::{path::Path, sync::Arc};
+#[cfg(feature = "rocksdb")]
+use rocksdb::{Options, DB as Rocks};
+#[cfg(feature = "rocksdb")]
+use std::path::Path;
+use std::sync::Arc;
+#[cfg(feature = "rocksdb")]
 use tracing::info;
 
-/// Shared functionality surrounding use of rocksdb
+/// Iterating over decoded values in the DB
 pub mod iterator;
 
+/// Key-value stores the DB can be backed by
+mod storage;
+pub use storage::*;
+
 /// Type-specific db operations
 mod typed_db;
 pub use typed_db::*;
@@ -14,12 +23,18 @@ pub use typed_db::*;
 mod abacus_db;
 pub use abacus_db::*;
 
+/// Versioning of the DB's schema
+mod migrations;
+pub use migrations::*;
+
 use crate::{AbacusError, Decode, Encode};
 
 #[derive(Debug, Clone)]
 /// A KV Store
-pub struct DB(Arc<Rocks>);
+pub struct DB(Arc<dyn KvStore>);
 
+/// Wraps the RocksDB as it is, leaving its schema unmigrated
+#[cfg(feature = "rocksdb")]
 impl From<Rocks> for DB {
     fn from(rocks: Rocks) -> Self {
         Self(Arc::new(rocks))
@@ -30,19 +45,57 @@ impl From<Rocks> for DB {
 #[derive(thiserror::Error, Debug)]
 pub enum DbError {
     /// Rocks DB Error
+    #[cfg(feature = "rocksdb")]
     #[error("{0}")]
     RockError(#[from] rocksdb::Error),
     /// Abacus Error
     #[error("{0}")]
     AbacusError(#[from] AbacusError),
+    /// The storage backend doesn't support an operation
+    #[error("{0} isn't supported by the storage backend")]
+    Unsupported(&'static str),
+    /// The DB was written with a newer schema than is supported
+    #[error("DB schema version {found} is newer than the latest supported version {supported}")]
+    UnsupportedSchemaVersion {
+        /// The DB's schema version
+        found: u32,
+        /// The latest supported schema version
+        supported: u32,
+    },
+    /// The DB needs migrating, but migrating was a dry run
+    #[error(
+        "Dry run: DB at schema version {version} left unmigrated, {pending} migrations pending"
+    )]
+    MigrationDryRun {
+        /// The DB's schema version
+        version: u32,
+        /// The number of migrations which would have run
+        pending: usize,
+    },
 }
 
 type Result<T> = std::result::Result<T, DbError>;
 
 impl DB {
-    /// Opens db at `db_path` and creates if missing
+    /// Create a DB backed by `store`, migrating it to the latest schema
+    /// version. New stores are stamped with the latest version.
+    pub fn new(store: impl KvStore + 'static, migration: &MigrationOptions) -> Result<Self> {
+        let db = Self(Arc::new(store));
+        db.migrate(migration)?;
+        Ok(db)
+    }
+
+    /// Create a new, empty DB held in memory
+    pub fn in_memory() -> Self {
+        Self::new(MemoryStore::default(), &MigrationOptions::default())
+            .expect("new DBs have nothing to migrate")
+    }
+
+    /// Opens db at `db_path` and creates if missing, then migrates it to
+    /// the latest schema version
+    #[cfg(feature = "rocksdb")]
     #[tracing::instrument(err)]
-    pub fn from_path(db_path: &str) -> eyre::Result<DB> {
+    pub fn from_path(db_path: &str, migration: &MigrationOptions) -> eyre::Result<DB> {
         // Canonicalize ensures existence, so we have to do that, then extend
         let mut path = Path::new(".").canonicalize()?;
         path.extend(&[db_path]);
@@ -58,22 +111,51 @@ impl DB {
         let mut opts = Options::default();
         opts.create_if_missing(true);
 
-        Rocks::open(&opts, &path)
-            .wrap_err(format!(
-                "Failed to open db path {}, canonicalized as {:?}",
-                db_path, path
-            ))
-            .map(Into::into)
+        let rocks = Rocks::open(&opts, &path).wrap_err(format!(
+            "Failed to open db path {}, canonicalized as {:?}",
+            db_path, path
+        ))?;
+        DB::new(rocks, migration).wrap_err(format!("Failed to migrate db at {:?}", path))
+    }
+
+    /// Opens the db at `db_path` read-only, as a RocksDB secondary instance
+    /// keeping its own logs at `secondary_path`. Unlike opening the db
+    /// itself, this works while an agent has it open.
+    #[cfg(feature = "rocksdb")]
+    #[tracing::instrument(err)]
+    pub fn open_secondary(db_path: &str, secondary_path: &str) -> eyre::Result<DB> {
+        let mut opts = Options::default();
+        // Secondary instances must keep every file open
+        opts.set_max_open_files(-1);
+
+        let db: DB = Rocks::open_as_secondary(&opts, db_path, secondary_path)
+            .wrap_err(format!("Failed to open db path {} as secondary", db_path))?
+            .into();
+        if let Some(version) = db.schema_version()? {
+            if version > SCHEMA_VERSION {
+                return Err(DbError::UnsupportedSchemaVersion {
+                    found: version,
+                    supported: SCHEMA_VERSION,
+                }
+                .into());
+            }
+        }
+        Ok(db)
     }
 
     /// Store a value in the DB
     fn _store(&self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<()> {
-        Ok(self.0.put(key, value)?)
+        self.0.put(key.as_ref(), value.as_ref())
     }
 
     /// Retrieve a value from the DB
     fn _retrieve(&self, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>> {
-        Ok(self.0.get(key)?)
+        self.0.get(key.as_ref())
+    }
+
+    /// Delete a value from the DB
+    fn _delete(&self, key: impl AsRef<[u8]>) -> Result<()> {
+        self.0.delete(key.as_ref())
     }
 
     /// Prefix a key and store in the DB
@@ -101,6 +183,14 @@ impl DB {
         self._retrieve(buf)
     }
 
+    /// Prefix the key and delete
+    fn prefix_delete(&self, prefix: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Result<()> {
+        let mut buf = vec![];
+        buf.extend(prefix.as_ref());
+        buf.extend(key.as_ref());
+        self._delete(buf)
+    }
+
     /// Store any encodeable
     pub fn store_encodable<V: Encode>(
         &self,
@@ -142,8 +232,50 @@ impl DB {
         self.retrieve_decodable(prefix, key.to_vec())
     }
 
-    /// Get prefix db iterator for `prefix`
-    pub fn prefix_iterator(&self, prefix: impl AsRef<[u8]>) -> DBIterator {
-        self.0.prefix_iterator(prefix)
+    /// Delete the value stored under `prefix` and `key`, if any
+    pub fn delete(&self, prefix: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Result<()> {
+        self.prefix_delete(prefix, key)
+    }
+
+    /// Delete the value stored under `prefix` and an encodable key, if any
+    pub fn delete_keyed<K: Encode>(&self, prefix: impl AsRef<[u8]>, key: &K) -> Result<()> {
+        self.delete(prefix, key.to_vec())
+    }
+
+    /// Compact the whole DB, so that the space taken by deleted values is
+    /// reclaimed
+    pub fn compact(&self) {
+        self.0.compact()
+    }
+
+    /// The total size of the DB's files on disk, in bytes
+    pub fn size_on_disk(&self) -> Result<u64> {
+        self.0.size_on_disk()
+    }
+
+    /// Apply every write in `batch` atomically
+    pub fn write(&self, batch: WriteBatch) -> Result<()> {
+        self.0.write(batch)
+    }
+
+    /// Stage the writes `f` makes to a view of the DB, then apply them
+    /// atomically once it succeeds, so that nothing is written if it fails
+    pub fn write_staged<T, E>(
+        &self,
+        f: impl FnOnce(&DB) -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E>
+    where
+        E: From<DbError>,
+    {
+        let staged = Arc::new(StagedStore::new(self.0.clone()));
+        let value = f(&DB(staged.clone()))?;
+        self.write(staged.take_batch())?;
+        Ok(value)
+    }
+
+    /// Get prefix db iterator for `prefix`, over only the keys starting
+    /// with it
+    pub fn prefix_iterator(&self, prefix: impl AsRef<[u8]>) -> KvIterator<'_> {
+        self.0.prefix_iterator(prefix.as_ref())
     }
 }
@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+#[cfg(feature = "rocksdb")]
+use rocksdb::{checkpoint::Checkpoint as RocksCheckpoint, DB as Rocks};
+
+use crate::db::DbError;
+
+type Result<T> = std::result::Result<T, DbError>;
+
+/// An iterator over keys and their values, ordered by key
+pub type KvIterator<'a> = Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>;
+
+/// A write to a key, staged in a `WriteBatch`
+#[derive(Debug, Clone)]
+enum BatchOp {
+    Put(Vec<u8>, Vec<u8>),
+    Delete(Vec<u8>),
+}
+
+/// Writes which are applied to a store atomically, in order
+#[derive(Debug, Clone, Default)]
+pub struct WriteBatch(Vec<BatchOp>);
+
+impl WriteBatch {
+    /// Stage storing `value` under `key`
+    pub fn put(&mut self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) {
+        self.0
+            .push(BatchOp::Put(key.as_ref().to_vec(), value.as_ref().to_vec()));
+    }
+
+    /// Stage deleting the value stored under `key`
+    pub fn delete(&mut self, key: impl AsRef<[u8]>) {
+        self.0.push(BatchOp::Delete(key.as_ref().to_vec()));
+    }
+
+    /// The number of staged writes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether no writes are staged
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A key-value store backing a `DB`
+pub trait KvStore: Debug + Send + Sync {
+    /// Retrieve the value stored under `key`
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    /// Store `value` under `key`
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    /// Delete the value stored under `key`, if any
+    fn delete(&self, key: &[u8]) -> Result<()>;
+
+    /// Apply every write in `batch` atomically
+    fn write(&self, batch: WriteBatch) -> Result<()>;
+
+    /// Iterate over the keys starting with `prefix` and their values
+    fn prefix_iterator<'a>(&'a self, prefix: &[u8]) -> KvIterator<'a>;
+
+    /// Reclaim the space taken by deleted values
+    fn compact(&self) {}
+
+    /// The total size of the store's files on disk, in bytes
+    fn size_on_disk(&self) -> Result<u64> {
+        Ok(0)
+    }
+
+    /// Copy the store to a new directory at `path`
+    fn backup(&self, _path: &Path) -> Result<()> {
+        Err(DbError::Unsupported("backing up"))
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+impl KvStore for Rocks {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(Rocks::get(self, key)?)
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        Ok(Rocks::put(self, key, value)?)
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        Ok(Rocks::delete(self, key)?)
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut rocks_batch = rocksdb::WriteBatch::default();
+        for op in batch.0 {
+            match op {
+                BatchOp::Put(key, value) => rocks_batch.put(key, value),
+                BatchOp::Delete(key) => rocks_batch.delete(key),
+            }
+        }
+        Ok(Rocks::write(self, rocks_batch)?)
+    }
+
+    fn prefix_iterator<'a>(&'a self, prefix: &[u8]) -> KvIterator<'a> {
+        // Without a prefix extractor, RocksDB only seeks to the prefix, so
+        // stop once past it
+        let prefix = prefix.to_vec();
+        Box::new(
+            Rocks::prefix_iterator(self, &prefix)
+                .take_while(move |(key, _)| key.starts_with(&prefix)),
+        )
+    }
+
+    fn compact(&self) {
+        self.compact_range::<&[u8], &[u8]>(None, None)
+    }
+
+    fn size_on_disk(&self) -> Result<u64> {
+        // Count every file rather than just the SST files, as deleted values
+        // also take space in the write-ahead log until compacted
+        let mut size = 0;
+        for entry in std::fs::read_dir(self.path()).map_err(crate::AbacusError::from)? {
+            size += entry
+                .and_then(|entry| entry.metadata())
+                .map_err(crate::AbacusError::from)?
+                .len();
+        }
+        Ok(size)
+    }
+
+    fn backup(&self, path: &Path) -> Result<()> {
+        RocksCheckpoint::new(self)?.create_checkpoint(path)?;
+        Ok(())
+    }
+}
+
+/// A store held in memory, e.g. for tests or agents which don't need to
+/// persist their DB
+#[derive(Debug, Default)]
+pub struct MemoryStore(RwLock<BTreeMap<Vec<u8>, Vec<u8>>>);
+
+impl KvStore for MemoryStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.read().expect("poisoned").get(key).cloned())
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.0
+            .write()
+            .expect("poisoned")
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        self.0.write().expect("poisoned").remove(key);
+        Ok(())
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut map = self.0.write().expect("poisoned");
+        for op in batch.0 {
+            match op {
+                BatchOp::Put(key, value) => map.insert(key, value),
+                BatchOp::Delete(key) => map.remove(&key),
+            };
+        }
+        Ok(())
+    }
+
+    fn prefix_iterator<'a>(&'a self, prefix: &[u8]) -> KvIterator<'a> {
+        // Copy the entries out rather than hold the lock while iterating
+        let entries: Vec<(Box<[u8]>, Box<[u8]>)> = self
+            .0
+            .read()
+            .expect("poisoned")
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone().into(), value.clone().into()))
+            .collect();
+        Box::new(entries.into_iter())
+    }
+}
+
+/// A view of another store which keeps the writes made to it in memory,
+/// so that they can be applied to that store atomically afterwards
+#[derive(Debug)]
+pub(crate) struct StagedStore {
+    base: Arc<dyn KvStore>,
+    /// The staged writes, where `None` stages a delete
+    staged: RwLock<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+}
+
+impl StagedStore {
+    pub(crate) fn new(base: Arc<dyn KvStore>) -> Self {
+        Self {
+            base,
+            staged: Default::default(),
+        }
+    }
+
+    /// Take the staged writes, to apply them to the base store
+    pub(crate) fn take_batch(&self) -> WriteBatch {
+        let staged = std::mem::take(&mut *self.staged.write().expect("poisoned"));
+        WriteBatch(
+            staged
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Some(value) => BatchOp::Put(key, value),
+                    None => BatchOp::Delete(key),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl KvStore for StagedStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.staged.read().expect("poisoned").get(key) {
+            Some(value) => Ok(value.clone()),
+            None => self.base.get(key),
+        }
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.staged
+            .write()
+            .expect("poisoned")
+            .insert(key.to_vec(), Some(value.to_vec()));
+        Ok(())
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<()> {
+        self.staged
+            .write()
+            .expect("poisoned")
+            .insert(key.to_vec(), None);
+        Ok(())
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<()> {
+        let mut staged = self.staged.write().expect("poisoned");
+        for op in batch.0 {
+            match op {
+                BatchOp::Put(key, value) => staged.insert(key, Some(value)),
+                BatchOp::Delete(key) => staged.insert(key, None),
+            };
+        }
+        Ok(())
+    }
+
+    fn prefix_iterator<'a>(&'a self, prefix: &[u8]) -> KvIterator<'a> {
+        // Merge the staged writes over the base store's entries
+        let mut entries: BTreeMap<Vec<u8>, Vec<u8>> = self
+            .base
+            .prefix_iterator(prefix)
+            .map(|(key, value)| (key.into(), value.into()))
+            .collect();
+        let staged = self.staged.read().expect("poisoned");
+        for (key, value) in staged
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+        {
+            match value {
+                Some(value) => entries.insert(key.clone(), value.clone()),
+                None => entries.remove(key),
+            };
+        }
+        Box::new(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Exercise the operations `DB` relies on
+    fn check_store(store: &dyn KvStore) {
+        store.put(b"a_1", b"1").unwrap();
+        store.put(b"a_2", b"2").unwrap();
+        store.put(b"b_1", b"3").unwrap();
+        assert_eq!(store.get(b"a_1").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(store.get(b"c_1").unwrap(), None);
+
+        store.delete(b"a_1").unwrap();
+        assert_eq!(store.get(b"a_1").unwrap(), None);
+
+        let mut batch = WriteBatch::default();
+        batch.put(b"a_3", b"4");
+        batch.put(b"a_0", b"5");
+        batch.delete(b"a_2");
+        assert_eq!(batch.len(), 3);
+        store.write(batch).unwrap();
+
+        let entries: Vec<_> = store
+            .prefix_iterator(b"a_")
+            .map(|(key, value)| (key.to_vec(), value.to_vec()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (b"a_0".to_vec(), b"5".to_vec()),
+                (b"a_3".to_vec(), b"4".to_vec()),
+            ]
+        );
+        assert_eq!(store.prefix_iterator(b"").count(), 3);
+    }
+
+    #[test]
+    fn memory_store_behaves_like_a_kv_store() {
+        check_store(&MemoryStore::default());
+        assert!(MemoryStore::default().backup(Path::new("unused")).is_err());
+    }
+
+    #[test]
+    fn staged_store_behaves_like_a_kv_store() {
+        check_store(&StagedStore::new(Arc::new(MemoryStore::default())));
+    }
+
+    #[test]
+    fn staged_store_writes_nothing_until_applied() {
+        let base: Arc<dyn KvStore> = Arc::new(MemoryStore::default());
+        base.put(b"a_1", b"1").unwrap();
+        base.put(b"a_2", b"2").unwrap();
+
+        let staged = StagedStore::new(base.clone());
+        staged.put(b"a_3", b"3").unwrap();
+        staged.delete(b"a_1").unwrap();
+        assert_eq!(staged.get(b"a_1").unwrap(), None);
+        assert_eq!(staged.get(b"a_2").unwrap(), Some(b"2".to_vec()));
+        let keys: Vec<_> = staged
+            .prefix_iterator(b"a_")
+            .map(|(key, _)| key.to_vec())
+            .collect();
+        assert_eq!(keys, vec![b"a_2".to_vec(), b"a_3".to_vec()]);
+        assert_eq!(base.get(b"a_1").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(base.get(b"a_3").unwrap(), None);
+
+        base.write(staged.take_batch()).unwrap();
+        assert_eq!(base.get(b"a_1").unwrap(), None);
+        assert_eq!(base.get(b"a_3").unwrap(), Some(b"3".to_vec()));
+        assert!(staged.take_batch().is_empty());
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn rocks_store_behaves_like_a_kv_store() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        let rocks = Rocks::open(&opts, dir.path()).unwrap();
+        check_store(&rocks);
+        assert!(rocks.size_on_disk().unwrap() > 0);
+    }
+}
@@ -23,6 +23,11 @@ impl TypedDB {
         Self { entity, db }
     }
 
+    /// The entity the data is tied to
+    pub fn entity(&self) -> &str {
+        &self.entity
+    }
+
     fn full_prefix(&self, prefix: impl AsRef<[u8]>) -> Vec<u8> {
         let mut full_prefix = vec![];
         full_prefix.extend(self.entity.as_ref() as &[u8]);
@@ -71,4 +76,36 @@ impl TypedDB {
         self.db
             .retrieve_keyed_decodable(self.full_prefix(prefix), key)
     }
+
+    /// Retrieve every decodable key and value stored under `prefix`,
+    /// ordered by the encoded key
+    pub fn retrieve_all_keyed_decodable<K: Decode, V: Decode>(
+        &self,
+        prefix: impl AsRef<[u8]>,
+    ) -> Result<Vec<(K, V)>, DbError> {
+        let full_prefix = self.full_prefix(prefix);
+        self.db
+            .prefix_iterator(&full_prefix)
+            .map(|(key, value)| {
+                Ok((
+                    K::read_from(&mut &key[full_prefix.len()..])?,
+                    V::read_from(&mut value.as_ref())?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Delete value
+    pub fn delete(&self, prefix: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Result<(), DbError> {
+        self.db.delete(self.full_prefix(prefix), key)
+    }
+
+    /// Delete value given encodable key
+    pub fn delete_keyed<K: Encode>(
+        &self,
+        prefix: impl AsRef<[u8]>,
+        key: &K,
+    ) -> Result<(), DbError> {
+        self.db.delete_keyed(self.full_prefix(prefix), key)
+    }
 }
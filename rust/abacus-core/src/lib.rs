@@ -34,6 +34,10 @@ pub mod test_output;
 mod chain;
 pub use chain::*;
 
+/// Signer backed by a remote signing service
+mod remote_signer;
+pub use remote_signer::*;
+
 use std::convert::Infallible;
 
 pub use identifiers::AbacusIdentifier;
@@ -81,6 +85,9 @@ pub enum SignersError {
     /// Wallet Signer Error
     #[error("{0}")]
     WalletError(#[from] WalletError),
+    /// Remote Signer Error
+    #[error("{0}")]
+    RemoteSignerError(#[from] RemoteSignerError),
 }
 
 impl From<Infallible> for SignersError {
@@ -96,6 +103,8 @@ pub enum Signers {
     Local(LocalWallet),
     /// A signer using a key stored in aws kms
     Aws(AwsSigner<'static>),
+    /// A signer using a key held by a remote signing service
+    Remote(RemoteSigner),
 }
 
 impl From<LocalWallet> for Signers {
@@ -110,6 +119,12 @@ impl From<AwsSigner<'static>> for Signers {
     }
 }
 
+impl From<RemoteSigner> for Signers {
+    fn from(s: RemoteSigner) -> Self {
+        Signers::Remote(s)
+    }
+}
+
 #[async_trait]
 impl Signer for Signers {
     type Error = SignersError;
@@ -118,6 +133,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => signer.with_chain_id(chain_id).into(),
             Signers::Aws(signer) => signer.with_chain_id(chain_id).into(),
+            Signers::Remote(signer) => signer.with_chain_id(chain_id).into(),
         }
     }
 
@@ -128,6 +144,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => Ok(signer.sign_message(message).await?),
             Signers::Aws(signer) => Ok(signer.sign_message(message).await?),
+            Signers::Remote(signer) => Ok(signer.sign_message(message).await?),
         }
     }
 
@@ -136,6 +153,7 @@ impl Signer for Signers {
             Signers::Local(signer) => Ok(signer.sign_transaction(message).await?),
 
             Signers::Aws(signer) => Ok(signer.sign_transaction(message).await?),
+            Signers::Remote(signer) => Ok(signer.sign_transaction(message).await?),
         }
     }
 
@@ -143,6 +161,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => signer.address(),
             Signers::Aws(signer) => signer.address(),
+            Signers::Remote(signer) => signer.address(),
         }
     }
 
@@ -150,6 +169,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => signer.chain_id(),
             Signers::Aws(signer) => signer.chain_id(),
+            Signers::Remote(signer) => signer.chain_id(),
         }
     }
 
@@ -160,6 +180,7 @@ impl Signer for Signers {
         match self {
             Signers::Local(signer) => Ok(signer.sign_typed_data(payload).await?),
             Signers::Aws(signer) => Ok(signer.sign_typed_data(payload).await?),
+            Signers::Remote(signer) => Ok(signer.sign_typed_data(payload).await?),
         }
     }
 }
@@ -207,4 +228,28 @@ mod test {
             .unwrap()
             .block_on(t)
     }
+
+    #[test]
+    fn it_signs_announcement() {
+        let t = async {
+            let signer: ethers::signers::LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+            let announcement = Announcement {
+                validator: signer.address(),
+                outbox_domain: 5,
+                storage_location: "file:///tmp/checkpoints".to_owned(),
+            };
+
+            let signed = announcement.sign_with(&signer).await.expect("!sign_with");
+            signed.verify().expect("!verify");
+            assert_eq!(signed.recover().expect("!recover"), signer.address());
+        };
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(t)
+    }
 }
@@ -0,0 +1,195 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::core::types::{
+    transaction::{eip2718::TypedTransaction, eip712::Eip712},
+    Address, Signature, SignatureError, H256,
+};
+use ethers::core::utils::keccak256;
+use ethers::signers::Signer;
+
+/// Error types for the RemoteSigner
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteSignerError {
+    /// Error talking to the signing service
+    #[error("{0}")]
+    ServiceError(Box<dyn std::error::Error + Send + Sync>),
+    /// The signing service returned a malformed signature
+    #[error("{0}")]
+    SignatureError(#[from] SignatureError),
+    /// The signing service returned a signature by another key
+    #[error("Remote signature recovers to {recovered:?} instead of {expected:?}")]
+    WrongSigner {
+        /// The configured address of the signer
+        expected: Address,
+        /// The address the returned signature recovers to
+        recovered: Address,
+    },
+    /// The typed data could not be encoded
+    #[error("Failed to encode typed data: {0}")]
+    Eip712Error(String),
+}
+
+/// A service holding signing keys, e.g. a Web3Signer-style HTTP API
+#[async_trait]
+pub trait SigningService: Debug + Send + Sync {
+    /// Sign the keccak256 hash of `data` with the key of `address`
+    async fn sign(&self, address: Address, data: &[u8]) -> Result<Signature, RemoteSignerError>;
+}
+
+/// A signer that delegates signing to a signing service holding the key
+/// identified by `address`.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    service: Arc<dyn SigningService>,
+    address: Address,
+    chain_id: u64,
+}
+
+impl RemoteSigner {
+    /// Create a signer for the key with `address` held by `service`
+    pub fn new(service: Arc<dyn SigningService>, address: Address) -> Self {
+        Self {
+            service,
+            address,
+            chain_id: 1,
+        }
+    }
+
+    /// Have the signing service sign the keccak256 hash of `data`, and check
+    /// that the signature was made by the configured key. The returned
+    /// signature's `v` is 27 or 28.
+    async fn sign_data(&self, data: &[u8]) -> Result<Signature, RemoteSignerError> {
+        let mut signature = self.service.sign(self.address, data).await?;
+        // Some services return the raw recovery id
+        if signature.v < 27 {
+            signature.v += 27;
+        }
+
+        let recovered = signature.recover(H256::from(keccak256(data)))?;
+        if recovered != self.address {
+            return Err(RemoteSignerError::WrongSigner {
+                expected: self.address,
+                recovered,
+            });
+        }
+        Ok(signature)
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    type Error = RemoteSignerError;
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        let message = message.as_ref();
+        let mut data = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+        data.extend_from_slice(message);
+        self.sign_data(&data).await
+    }
+
+    async fn sign_transaction(&self, message: &TypedTransaction) -> Result<Signature, Self::Error> {
+        let mut tx = message.clone();
+        if tx.chain_id().is_none() {
+            tx.set_chain_id(self.chain_id);
+        }
+        let chain_id = tx.chain_id().map(|id| id.as_u64()).unwrap_or(self.chain_id);
+
+        let mut signature = self.sign_data(&tx.rlp()).await?;
+        // Apply EIP-155 like the local wallet does
+        signature.v = (signature.v - 27) + 35 + chain_id * 2;
+        Ok(signature)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        let domain_separator = payload
+            .domain_separator()
+            .map_err(|e| RemoteSignerError::Eip712Error(e.to_string()))?;
+        let struct_hash = payload
+            .struct_hash()
+            .map_err(|e| RemoteSignerError::Eip712Error(e.to_string()))?;
+
+        let mut data = vec![0x19, 0x01];
+        data.extend_from_slice(&domain_separator);
+        data.extend_from_slice(&struct_hash);
+        self.sign_data(&data).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::core::types::TransactionRequest;
+    use ethers::signers::LocalWallet;
+
+    use super::*;
+
+    /// A signing service holding a local wallet's key
+    #[derive(Debug)]
+    struct WalletService(LocalWallet);
+
+    #[async_trait]
+    impl SigningService for WalletService {
+        async fn sign(
+            &self,
+            _address: Address,
+            data: &[u8],
+        ) -> Result<Signature, RemoteSignerError> {
+            Ok(self.0.sign_hash(H256::from(keccak256(data))))
+        }
+    }
+
+    #[tokio::test]
+    async fn it_signs_like_a_local_wallet() {
+        let wallet: LocalWallet =
+            "1111111111111111111111111111111111111111111111111111111111111111"
+                .parse()
+                .unwrap();
+        let wallet = wallet.with_chain_id(5u64);
+        let service = Arc::new(WalletService(wallet.clone()));
+        let remote = RemoteSigner::new(service.clone(), wallet.address()).with_chain_id(5u64);
+
+        assert_eq!(
+            remote.sign_message("hello").await.unwrap(),
+            wallet.sign_message("hello").await.unwrap()
+        );
+
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .value(100)
+            .nonce(3)
+            .gas(21000)
+            .gas_price(1)
+            .into();
+        assert_eq!(
+            remote.sign_transaction(&tx).await.unwrap(),
+            wallet.sign_transaction(&tx).await.unwrap()
+        );
+
+        // A service holding another key is rejected
+        let other = RemoteSigner::new(service, Address::repeat_byte(2));
+        assert!(matches!(
+            other.sign_message("hello").await,
+            Err(RemoteSignerError::WrongSigner { .. })
+        ));
+    }
+}
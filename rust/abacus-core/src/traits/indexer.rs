@@ -8,26 +8,71 @@
 //! other entities can retrieve this chain-specific info.
 
 use std::fmt::Debug;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use ethers::core::types::H256;
 use eyre::Result;
+use tokio::time::sleep;
 
-use crate::{CheckpointWithMeta, InterchainGasPaymentWithMeta, RawCommittedMessage};
+use crate::{CheckpointWithMeta, InterchainGasPaymentWithMeta, RawCommittedMessageWithMeta};
 
 /// Interface for an indexer.
 #[async_trait]
 pub trait Indexer: Send + Sync + Debug {
     /// Get the chain's latest block number that has reached finality
     async fn get_finalized_block_number(&self) -> Result<u32>;
+
+    /// Get the chain's latest block number, which may yet be reorged out.
+    /// Defaults to the latest finalized block number.
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        self.get_finalized_block_number().await
+    }
+
+    /// Get the hash of the block at `block_number` on the canonical chain,
+    /// or `None` if there is no such block yet. Indexing blocks before they
+    /// reach finality relies on block hashes to detect reorgs, so is not
+    /// possible with indexers which, by default, return `None`.
+    async fn get_block_hash(&self, _block_number: u32) -> Result<Option<H256>> {
+        Ok(None)
+    }
+
+    /// Wait until a block after `block` may have been produced. By default
+    /// this sleeps for a second so that the latest block number is polled.
+    async fn wait_for_block_after(&self, _block: u32) {
+        sleep(Duration::from_secs(1)).await
+    }
+
+    /// Wait until a block after `block` may have reached finality. By default
+    /// this sleeps for a second so that the finalized block number is polled.
+    /// Indexers which are notified of new blocks should return once one
+    /// arrives instead.
+    async fn wait_for_finalized_block_after(&self, _block: u32) {
+        sleep(Duration::from_secs(1)).await
+    }
 }
 
 /// Interface for Outbox contract indexer. Interface for allowing other
 /// entities to retrieve chain-specific data from an outbox.
 #[async_trait]
 pub trait OutboxIndexer: Indexer + Send + Sync + Debug {
-    /// Fetch list of messages between blocks `from` and `to`.
-    async fn fetch_sorted_messages(&self, _from: u32, _to: u32)
-        -> Result<Vec<RawCommittedMessage>>;
+    /// Fetch list of messages between blocks `from` and `to`, sorted by leaf
+    /// index, along with metadata about their dispatch.
+    async fn fetch_sorted_messages(
+        &self,
+        _from: u32,
+        _to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>>;
+
+    /// Fetch the messages with the given leaf indices dispatched between
+    /// blocks `from` and `to`, sorted by leaf index, along with metadata
+    /// about their dispatch. Used to refetch messages a range query missed.
+    async fn fetch_messages_by_leaf_index(
+        &self,
+        leaf_indices: &[u32],
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>>;
 
     /// Fetch sequentially sorted list of cached checkpoints between blocks `from` and `to`
     async fn fetch_sorted_cached_checkpoints(
@@ -47,3 +92,11 @@ pub trait InterchainGasPaymasterIndexer: Indexer + Send + Sync + Debug {
         to_block: u32,
     ) -> Result<Vec<InterchainGasPaymentWithMeta>>;
 }
+
+/// Interface for Inbox contract indexer.
+#[async_trait]
+pub trait InboxIndexer: Indexer + Send + Sync + Debug {
+    /// Fetch the leaves of the messages processed between `from` and `to`,
+    /// inclusive
+    async fn fetch_processed_messages(&self, from: u32, to: u32) -> Result<Vec<H256>>;
+}
@@ -1,7 +1,7 @@
 use std::convert::TryFrom;
 
 use crate::{AbacusError, AbacusMessage, Decode, Encode};
-use ethers::core::types::H256;
+use ethers::core::types::{H256, U256};
 use eyre::Result;
 use sha3::{Digest, Keccak256};
 
@@ -62,6 +62,62 @@ impl Decode for RawCommittedMessage {
     }
 }
 
+/// Metadata about the dispatch of a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageDispatchMeta {
+    /// The number of the block the message was dispatched in
+    pub block_number: u64,
+    /// The hash of the block the message was dispatched in
+    pub block_hash: H256,
+    /// The timestamp of the block the message was dispatched in, in seconds
+    /// since the unix epoch
+    pub timestamp: u64,
+    /// The hash of the transaction which dispatched the message
+    pub transaction_hash: H256,
+    /// The index of the Dispatch log within the block's logs
+    pub log_index: U256,
+}
+
+impl Encode for MessageDispatchMeta {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = 0;
+        written += self.block_number.write_to(writer)?;
+        written += self.block_hash.write_to(writer)?;
+        written += self.timestamp.write_to(writer)?;
+        written += self.transaction_hash.write_to(writer)?;
+        written += self.log_index.write_to(writer)?;
+        Ok(written)
+    }
+}
+
+impl Decode for MessageDispatchMeta {
+    fn read_from<R>(reader: &mut R) -> Result<Self, AbacusError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            block_number: u64::read_from(reader)?,
+            block_hash: H256::read_from(reader)?,
+            timestamp: u64::read_from(reader)?,
+            transaction_hash: H256::read_from(reader)?,
+            log_index: U256::read_from(reader)?,
+        })
+    }
+}
+
+/// A RawCommittedMessage with metadata about its dispatch
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawCommittedMessageWithMeta {
+    /// The RawCommittedMessage
+    pub message: RawCommittedMessage,
+    /// Metadata about the message's dispatch
+    pub meta: MessageDispatchMeta,
+}
+
 // ember: tracingify these across usage points
 /// A Stamped message that has been committed at some leaf index
 #[derive(Debug, Default, Clone)]
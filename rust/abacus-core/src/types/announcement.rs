@@ -0,0 +1,85 @@
+use crate::{utils::domain_hash, AbacusError, SignerExt};
+use ethers::{
+    prelude::{Address, Signature},
+    types::H256,
+    utils::hash_message,
+};
+use ethers_signers::Signer;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+/// An announcement by a validator of where its signed checkpoints are stored
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Announcement {
+    /// The validator's signing address
+    pub validator: Address,
+    /// The outbox chain the validator signs checkpoints for
+    pub outbox_domain: u32,
+    /// The location of the validator's checkpoints, e.g. `s3://bucket/region`
+    /// or `file://path`
+    pub storage_location: String,
+}
+
+impl std::fmt::Display for Announcement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Announcement(validator {:?} for domain {} at {})",
+            self.validator, self.outbox_domain, self.storage_location
+        )
+    }
+}
+
+impl Announcement {
+    fn signing_hash(&self) -> H256 {
+        // sign:
+        // domain_hash(outbox_domain) || validator || storage_location
+        H256::from_slice(
+            Keccak256::new()
+                .chain(domain_hash(self.outbox_domain))
+                .chain(self.validator)
+                .chain(self.storage_location.as_bytes())
+                .finalize()
+                .as_slice(),
+        )
+    }
+
+    fn prepended_hash(&self) -> H256 {
+        hash_message(self.signing_hash())
+    }
+
+    /// Sign an announcement using the specified signer
+    pub async fn sign_with<S: Signer>(self, signer: &S) -> Result<SignedAnnouncement, S::Error> {
+        let signature = signer
+            .sign_message_without_eip_155(self.signing_hash())
+            .await?;
+        Ok(SignedAnnouncement {
+            announcement: self,
+            signature,
+        })
+    }
+}
+
+/// A signed validator announcement
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SignedAnnouncement {
+    /// The announcement
+    pub announcement: Announcement,
+    /// The signature
+    pub signature: Signature,
+}
+
+impl SignedAnnouncement {
+    /// Recover the Ethereum address of the signer
+    pub fn recover(&self) -> Result<Address, AbacusError> {
+        Ok(self.signature.recover(self.announcement.prepended_hash())?)
+    }
+
+    /// Check that the announcement was signed by the validator it announces
+    pub fn verify(&self) -> Result<(), AbacusError> {
+        Ok(self.signature.verify(
+            self.announcement.prepended_hash(),
+            self.announcement.validator,
+        )?)
+    }
+}
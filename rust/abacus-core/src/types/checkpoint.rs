@@ -138,6 +138,31 @@ pub struct CheckpointWithMeta {
     pub metadata: CheckpointMeta,
 }
 
+impl Encode for CheckpointWithMeta {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = 0;
+        written += self.checkpoint.write_to(writer)?;
+        written += self.metadata.write_to(writer)?;
+        Ok(written)
+    }
+}
+
+impl Decode for CheckpointWithMeta {
+    fn read_from<R>(reader: &mut R) -> Result<Self, AbacusError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            checkpoint: Checkpoint::read_from(reader)?,
+            metadata: CheckpointMeta::read_from(reader)?,
+        })
+    }
+}
+
 /// A Signed Abacus checkpoint
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct SignedCheckpoint {
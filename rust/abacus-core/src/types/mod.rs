@@ -1,5 +1,6 @@
 use ethers::types::{H256, U256};
 
+mod announcement;
 mod checkpoint;
 mod messages;
 
@@ -7,13 +8,14 @@ mod messages;
 /// 20-byte ids (e.g ethereum addresses)
 pub mod identifiers;
 
+pub use announcement::*;
 pub use checkpoint::*;
 pub use messages::*;
 
 use crate::{AbacusError, Decode, Encode};
 
 /// A payment of Outbox native tokens for a message
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InterchainGasPayment {
     /// The index of the message's leaf in the merkle tree
     pub leaf_index: u32,
@@ -21,8 +23,33 @@ pub struct InterchainGasPayment {
     pub amount: U256,
 }
 
+impl Encode for InterchainGasPayment {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = 0;
+        written += self.leaf_index.write_to(writer)?;
+        written += self.amount.write_to(writer)?;
+        Ok(written)
+    }
+}
+
+impl Decode for InterchainGasPayment {
+    fn read_from<R>(reader: &mut R) -> Result<Self, AbacusError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            leaf_index: u32::read_from(reader)?,
+            amount: U256::read_from(reader)?,
+        })
+    }
+}
+
 /// Uniquely identifying metadata for an InterchainGasPayment
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InterchainGasPaymentMeta {
     /// The transaction hash in which the GasPayment log was emitted
     pub transaction_hash: H256,
@@ -56,10 +83,35 @@ impl Decode for InterchainGasPaymentMeta {
 }
 
 /// An InterchainGasPayment with metadata to uniquely identify the payment
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InterchainGasPaymentWithMeta {
     /// The InterchainGasPayment
     pub payment: InterchainGasPayment,
     /// Metadata for the payment
     pub meta: InterchainGasPaymentMeta,
 }
+
+impl Encode for InterchainGasPaymentWithMeta {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let mut written = 0;
+        written += self.payment.write_to(writer)?;
+        written += self.meta.write_to(writer)?;
+        Ok(written)
+    }
+}
+
+impl Decode for InterchainGasPaymentWithMeta {
+    fn read_from<R>(reader: &mut R) -> Result<Self, AbacusError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        Ok(Self {
+            payment: InterchainGasPayment::read_from(reader)?,
+            meta: InterchainGasPaymentMeta::read_from(reader)?,
+        })
+    }
+}
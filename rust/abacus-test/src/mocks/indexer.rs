@@ -10,7 +10,7 @@ mock! {
     pub Indexer {
         pub fn _get_finalized_block_number(&self) -> Result<u32> {}
 
-        pub fn _fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessage>> {}
+        pub fn _fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessageWithMeta>> {}
     }
 }
 
@@ -26,7 +26,9 @@ mock! {
 
         pub fn _fetch_sorted_cached_checkpoints(&self, from: u32, to: u32) -> Result<Vec<CheckpointWithMeta>> {}
 
-        pub fn _fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessage>> {}
+        pub fn _fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessageWithMeta>> {}
+
+        pub fn _fetch_messages_by_leaf_index(&self, leaf_indices: &[u32], from: u32, to: u32) -> Result<Vec<RawCommittedMessageWithMeta>> {}
     }
 }
 
@@ -45,10 +47,23 @@ impl Indexer for MockAbacusIndexer {
 
 #[async_trait]
 impl OutboxIndexer for MockAbacusIndexer {
-    async fn fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessage>> {
+    async fn fetch_sorted_messages(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
         self._fetch_sorted_messages(from, to)
     }
 
+    async fn fetch_messages_by_leaf_index(
+        &self,
+        leaf_indices: &[u32],
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
+        self._fetch_messages_by_leaf_index(leaf_indices, from, to)
+    }
+
     async fn fetch_sorted_cached_checkpoints(
         &self,
         from: u32,
@@ -1,9 +1,10 @@
 use futures_util::Future;
+#[cfg(feature = "rocksdb")]
 use rocksdb::Options;
-use tempfile::TempDir;
 
 use abacus_core::db::DB;
 
+#[cfg(feature = "rocksdb")]
 pub fn setup_db(db_path: String) -> DB {
     let mut opts = Options::default();
     opts.create_if_missing(true);
@@ -12,26 +13,29 @@ pub fn setup_db(db_path: String) -> DB {
         .into()
 }
 
+/// Run `test` with a new DB held in memory
 pub async fn run_test_db<T, Fut>(test: T)
 where
     T: FnOnce(DB) -> Fut,
     Fut: Future<Output = ()>,
 {
-    // Use `/tmp`-equivalent so that any resource leak of the db files will
-    // eventually be cleaned up, even if e.g. TempDir's drop handler never runs
-    // due to a segfault etc encountered during the test.
-    let db_tmp_dir = TempDir::new().unwrap();
-    let db = setup_db(db_tmp_dir.path().to_str().unwrap().into());
-    let _test_result = test(db).await;
-    let _ = rocksdb::DB::destroy(&Options::default(), db_tmp_dir);
+    test(DB::in_memory()).await
 }
 
 #[cfg(test)]
 mod test {
-    use ethers::types::H256;
+    use std::collections::HashSet;
+
+    use ethers::types::{H256, U256};
+    #[cfg(feature = "rocksdb")]
+    use tempfile::TempDir;
 
     use abacus_core::{
-        accumulator::merkle::Proof, db::AbacusDB, AbacusMessage, Encode, RawCommittedMessage,
+        accumulator::merkle::Proof,
+        db::{AbacusDB, KvStore, MemoryStore, MigrationOptions, SCHEMA_VERSION},
+        AbacusMessage, Checkpoint, CheckpointMeta, CheckpointWithMeta, Encode,
+        InterchainGasPayment, InterchainGasPaymentMeta, InterchainGasPaymentWithMeta,
+        MessageDispatchMeta, RawCommittedMessage, RawCommittedMessageWithMeta,
     };
 
     use super::*;
@@ -72,6 +76,35 @@ mod test {
         .await;
     }
 
+    #[tokio::test]
+    async fn db_stores_and_retrieves_message_dispatch_meta() {
+        run_test_db(|db| async move {
+            let outbox_name = "outbox_1".to_owned();
+            let db = AbacusDB::new(outbox_name, db);
+
+            let message = RawCommittedMessageWithMeta {
+                message: RawCommittedMessage {
+                    leaf_index: 0,
+                    message: AbacusMessage::default().to_vec(),
+                },
+                meta: MessageDispatchMeta {
+                    block_number: 42,
+                    block_hash: H256::from_low_u64_be(42),
+                    timestamp: 1_650_000_000,
+                    transaction_hash: H256::from_low_u64_be(7),
+                    log_index: U256::from(3),
+                },
+            };
+
+            db.store_messages(&[message.clone()]).unwrap();
+
+            let meta = db.message_dispatch_meta_by_leaf_index(0).unwrap().unwrap();
+            assert_eq!(meta, message.meta);
+            assert!(db.message_dispatch_meta_by_leaf_index(1).unwrap().is_none());
+        })
+        .await;
+    }
+
     #[tokio::test]
     async fn db_stores_and_retrieves_proofs() {
         run_test_db(|db| async move {
@@ -90,4 +123,345 @@ mod test {
         })
         .await;
     }
+
+    #[tokio::test]
+    async fn db_prunes_processed_leaves_beyond_retention() {
+        run_test_db(|db| async move {
+            let outbox_name = "outbox_1".to_owned();
+            let db = AbacusDB::new(outbox_name, db);
+
+            for leaf_index in 0..6 {
+                let message = RawCommittedMessage {
+                    leaf_index,
+                    message: AbacusMessage {
+                        destination: if leaf_index == 2 { 2 } else { 1 },
+                        body: vec![leaf_index as u8],
+                        ..Default::default()
+                    }
+                    .to_vec(),
+                };
+                db.store_latest_message(&message).unwrap();
+                db.store_proof(
+                    leaf_index,
+                    &Proof {
+                        leaf: message.leaf(),
+                        index: leaf_index as usize,
+                        path: Default::default(),
+                    },
+                )
+                .unwrap();
+            }
+            // Leaf 1 is unprocessed, leaf 2 is for a destination which isn't
+            // relayed, and leaf 5 may yet be rolled back
+            for leaf_index in [0, 3, 4, 5] {
+                db.mark_leaf_as_processed(leaf_index).unwrap();
+            }
+            db.store_lowest_unfinalized_leaf_index(Some(5)).unwrap();
+            let relayed_destinations = HashSet::from([1]);
+            let payment = |leaf_index: u32| InterchainGasPaymentWithMeta {
+                payment: InterchainGasPayment {
+                    leaf_index,
+                    amount: 10.into(),
+                },
+                meta: InterchainGasPaymentMeta {
+                    transaction_hash: H256::from_low_u64_be(leaf_index.into()),
+                    log_index: U256::zero(),
+                },
+            };
+            for leaf_index in 0..6 {
+                db.process_gas_payment(&payment(leaf_index), 100 + leaf_index)
+                    .unwrap();
+            }
+
+            assert_eq!(
+                db.prune_processed_leaves(2, &relayed_destinations).unwrap(),
+                3
+            );
+            for leaf_index in 0..6 {
+                let pruned = [0, 2, 3].contains(&leaf_index);
+                assert!(db.leaf_by_leaf_index(leaf_index).unwrap().is_some());
+                assert_eq!(
+                    db.message_by_leaf_index(leaf_index).unwrap().is_none(),
+                    pruned
+                );
+                assert_eq!(
+                    db.proof_by_leaf_index(leaf_index).unwrap().is_none(),
+                    pruned
+                );
+            }
+            assert_eq!(db.retrieve_pruned_through_leaf_index().unwrap(), Some(0));
+            // Pruned leaves can't be relayed any more
+            assert_eq!(db.retrieve_leaf_processing_status(2).unwrap(), Some(true));
+            // Nor are they paid for any more
+            for leaf_index in 0..6 {
+                let total = if [0, 2, 3].contains(&leaf_index) {
+                    U256::zero()
+                } else {
+                    10.into()
+                };
+                assert_eq!(db.retrieve_gas_payment_for_leaf(leaf_index).unwrap(), total);
+            }
+
+            // Processed gas payments are pruned once indexed from blocks which
+            // won't be indexed again, while those which may be are kept, so
+            // they aren't counted twice
+            assert_eq!(db.prune_processed_gas_payment_metas(103).unwrap(), 3);
+            assert_eq!(
+                db.retrieve_processed_gas_payment_metas().unwrap(),
+                (3..6)
+                    .map(|leaf_index| payment(leaf_index).meta)
+                    .collect::<Vec<_>>()
+            );
+            db.process_gas_payment(&payment(4), 104).unwrap();
+            assert_eq!(db.retrieve_gas_payment_for_leaf(4).unwrap(), 10.into());
+
+            // Leaves are pruned once skipped, e.g. if delivered by another
+            // relayer
+            db.mark_leaf_as_skipped(1).unwrap();
+            assert_eq!(
+                db.prune_processed_leaves(2, &relayed_destinations).unwrap(),
+                1
+            );
+            assert!(db.message_by_leaf_index(1).unwrap().is_none());
+            assert_eq!(db.retrieve_pruned_through_leaf_index().unwrap(), Some(3));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_rolls_back_messages() {
+        run_test_db(|db| async move {
+            let outbox_name = "outbox_1".to_owned();
+            let db = AbacusDB::new(outbox_name, db);
+
+            let messages: Vec<_> = (0..5)
+                .map(|leaf_index| RawCommittedMessageWithMeta {
+                    message: RawCommittedMessage {
+                        leaf_index,
+                        message: AbacusMessage {
+                            destination: leaf_index % 2 + 1,
+                            body: vec![leaf_index as u8],
+                            ..Default::default()
+                        }
+                        .to_vec(),
+                    },
+                    meta: MessageDispatchMeta {
+                        block_number: leaf_index.into(),
+                        block_hash: H256::from_low_u64_be(leaf_index.into()),
+                        timestamp: 0,
+                        transaction_hash: H256::zero(),
+                        log_index: U256::zero(),
+                    },
+                })
+                .collect();
+            db.store_messages(&messages).unwrap();
+            for leaf_index in 0..5 {
+                db.store_proof(
+                    leaf_index,
+                    &Proof {
+                        leaf: messages[leaf_index as usize].message.leaf(),
+                        index: leaf_index as usize,
+                        path: Default::default(),
+                    },
+                )
+                .unwrap();
+            }
+
+            // Rolling back messages that were never stored does nothing
+            db.rollback_messages(5).unwrap();
+            assert_eq!(db.retrieve_latest_leaf_index().unwrap(), Some(4));
+
+            db.rollback_messages(3).unwrap();
+            for leaf_index in 0..5 {
+                let rolled_back = leaf_index >= 3;
+                let leaf = messages[leaf_index as usize].message.leaf();
+                assert_eq!(
+                    db.leaf_by_leaf_index(leaf_index).unwrap().is_none(),
+                    rolled_back
+                );
+                assert_eq!(db.message_by_leaf(leaf).unwrap().is_none(), rolled_back);
+                assert_eq!(
+                    db.message_dispatch_meta_by_leaf_index(leaf_index)
+                        .unwrap()
+                        .is_none(),
+                    rolled_back
+                );
+                assert_eq!(
+                    db.proof_by_leaf_index(leaf_index).unwrap().is_none(),
+                    rolled_back
+                );
+            }
+            assert_eq!(db.retrieve_latest_leaf_index().unwrap(), Some(2));
+            assert_eq!(
+                db.retrieve_latest_leaf_index_for_destination(1).unwrap(),
+                Some(2)
+            );
+            assert_eq!(
+                db.retrieve_latest_leaf_index_for_destination(2).unwrap(),
+                Some(1)
+            );
+
+            db.rollback_messages(0).unwrap();
+            assert_eq!(db.retrieve_latest_leaf_index().unwrap(), None);
+            assert_eq!(
+                db.retrieve_latest_leaf_index_for_destination(1).unwrap(),
+                None
+            );
+            assert_eq!(
+                db.retrieve_latest_leaf_index_for_destination(2).unwrap(),
+                None
+            );
+
+            // Messages are stored again from the rolled back leaf index
+            db.store_messages(&messages[..1]).unwrap();
+            assert_eq!(db.retrieve_latest_leaf_index().unwrap(), Some(0));
+            assert_eq!(
+                db.retrieve_latest_leaf_index_for_destination(1).unwrap(),
+                Some(0)
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_rolls_back_gas_payments() {
+        run_test_db(|db| async move {
+            let outbox_name = "outbox_1".to_owned();
+            let db = AbacusDB::new(outbox_name, db);
+
+            let payment = |amount: u64, log_index: u64| InterchainGasPaymentWithMeta {
+                payment: InterchainGasPayment {
+                    leaf_index: 1,
+                    amount: amount.into(),
+                },
+                meta: InterchainGasPaymentMeta {
+                    transaction_hash: H256::from_low_u64_be(1),
+                    log_index: log_index.into(),
+                },
+            };
+            db.process_gas_payment(&payment(10, 0), 100).unwrap();
+            db.process_gas_payment(&payment(5, 1), 100).unwrap();
+            assert_eq!(db.retrieve_gas_payment_for_leaf(1).unwrap(), 15.into());
+
+            db.rollback_gas_payment(&payment(10, 0)).unwrap();
+            assert_eq!(db.retrieve_gas_payment_for_leaf(1).unwrap(), 5.into());
+
+            // Payments which are not processed are not rolled back
+            db.rollback_gas_payment(&payment(10, 0)).unwrap();
+            db.rollback_gas_payment(&payment(7, 2)).unwrap();
+            assert_eq!(db.retrieve_gas_payment_for_leaf(1).unwrap(), 5.into());
+
+            // A rolled back payment is processed again if it's indexed again
+            db.process_gas_payment(&payment(10, 0), 100).unwrap();
+            assert_eq!(db.retrieve_gas_payment_for_leaf(1).unwrap(), 15.into());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn db_stores_and_queries_cached_checkpoints() {
+        run_test_db(|db| async move {
+            let outbox_name = "outbox_1".to_owned();
+            let db = AbacusDB::new(outbox_name, db);
+
+            let cached = |index: u32, block_number: u64| CheckpointWithMeta {
+                checkpoint: Checkpoint {
+                    outbox_domain: 1,
+                    root: H256::from_low_u64_be(index.into()),
+                    index,
+                },
+                metadata: CheckpointMeta { block_number },
+            };
+            let checkpoints = vec![cached(3, 10), cached(7, 20), cached(9, 20), cached(12, 35)];
+
+            assert_eq!(db.latest_cached_checkpoint().unwrap(), None);
+            db.store_cached_checkpoints(&checkpoints[..2]).unwrap();
+            // Storing overlapping checkpoints again doesn't duplicate them
+            db.store_cached_checkpoints(&checkpoints).unwrap();
+            assert_eq!(db.retrieve_cached_checkpoint_count().unwrap(), 4);
+            assert_eq!(
+                db.latest_cached_checkpoint().unwrap(),
+                Some(checkpoints[3].clone())
+            );
+
+            assert_eq!(db.cached_checkpoint_at_block(9).unwrap(), None);
+            assert_eq!(
+                db.cached_checkpoint_at_block(10).unwrap(),
+                Some(checkpoints[0].clone())
+            );
+            assert_eq!(
+                db.cached_checkpoint_at_block(34).unwrap(),
+                Some(checkpoints[2].clone())
+            );
+            assert_eq!(
+                db.cached_checkpoint_at_block(1000).unwrap(),
+                Some(checkpoints[3].clone())
+            );
+        })
+        .await;
+    }
+
+    #[test]
+    fn new_dbs_record_the_schema_version() {
+        assert_eq!(
+            DB::in_memory().schema_version().unwrap(),
+            Some(SCHEMA_VERSION)
+        );
+
+        // Stores written to before being versioned are migrated instead
+        let store = MemoryStore::default();
+        store
+            .put(b"outbox_1_latest_known_leaf_index_", &[3])
+            .unwrap();
+        let dry_run = MigrationOptions {
+            dry_run: true,
+            backup_dir: None,
+        };
+        assert!(DB::new(store, &dry_run).is_err());
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn db_records_schema_version_and_refuses_newer_schemas() {
+        let db_tmp_dir = TempDir::new().unwrap();
+        let db_path = db_tmp_dir.path().to_str().unwrap();
+
+        let db = DB::from_path(db_path, &MigrationOptions::default()).unwrap();
+        assert_eq!(db.schema_version().unwrap(), Some(SCHEMA_VERSION));
+        db.store_encodable("", "schema_version", &(SCHEMA_VERSION + 1))
+            .unwrap();
+        drop(db);
+
+        assert!(DB::from_path(db_path, &MigrationOptions::default()).is_err());
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn db_migrates_unversioned_schemas() {
+        let db_tmp_dir = TempDir::new().unwrap();
+        let db_path = db_tmp_dir.path().to_str().unwrap();
+        let backup_tmp_dir = TempDir::new().unwrap();
+
+        // A DB written before the schema was versioned
+        let db = setup_db(db_path.into());
+        db.store_encodable("outbox_1_", "latest_known_leaf_index_", &3u32)
+            .unwrap();
+        drop(db);
+
+        // Dry runs leave the DB unmigrated
+        let dry_run = MigrationOptions {
+            dry_run: true,
+            backup_dir: None,
+        };
+        assert!(DB::from_path(db_path, &dry_run).is_err());
+        assert_eq!(setup_db(db_path.into()).schema_version().unwrap(), None);
+
+        let migration = MigrationOptions {
+            dry_run: false,
+            backup_dir: Some(backup_tmp_dir.path().to_owned()),
+        };
+        let db = DB::from_path(db_path, &migration).unwrap();
+        assert_eq!(db.schema_version().unwrap(), Some(SCHEMA_VERSION));
+        assert_eq!(std::fs::read_dir(backup_tmp_dir.path()).unwrap().count(), 1);
+    }
 }
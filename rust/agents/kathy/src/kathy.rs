@@ -8,16 +8,22 @@ use tokio::{sync::Mutex, task::JoinHandle, time::sleep};
 use tracing::instrument::Instrumented;
 use tracing::{info, Instrument};
 
-use abacus_base::{decl_agent, AbacusAgentCore, Agent, CachingInbox};
+use abacus_base::{
+    decl_agent, AbacusAgentCore, Agent, CachingInbox, OutboxHealth, OutboxHealthMonitor,
+};
 use abacus_core::{AbacusCommon, Message, Outbox};
 
-decl_agent!(Kathy {
-    duration: u64,
-    generator: ChatGenerator,
-    outbox_lock: Arc<Mutex<()>>,
-});
+decl_agent!(
+    /// An agent that dispatches generated messages to every inbox
+    Kathy {
+        duration: u64,
+        generator: ChatGenerator,
+        outbox_lock: Arc<Mutex<()>>,
+    }
+);
 
 impl Kathy {
+    /// Instantiate a new Kathy
     pub fn new(duration: u64, generator: ChatGenerator, core: AbacusAgentCore) -> Self {
         Self {
             duration,
@@ -48,7 +54,11 @@ impl Agent for Kathy {
 
 impl Kathy {
     #[tracing::instrument]
-    fn run_inbox(&self, inbox: Arc<CachingInbox>) -> Instrumented<JoinHandle<Result<()>>> {
+    fn run_inbox(
+        &self,
+        inbox: Arc<CachingInbox>,
+        outbox_health: OutboxHealth,
+    ) -> Instrumented<JoinHandle<Result<()>>> {
         let outbox = self.outbox();
         let outbox_lock = self.outbox_lock.clone();
 
@@ -62,6 +72,13 @@ impl Kathy {
                 let msg = generator.gen_chat();
                 let recipient = generator.gen_recipient();
 
+                if outbox_health.is_failed() {
+                    outbox_health.report_failure("halting message dispatch");
+                    // Dispatching would revert on a failed outbox, so keep
+                    // running without dispatching rather than stopping the agent
+                    std::future::pending::<()>().await;
+                }
+
                 match msg {
                     Some(body) => {
                         let message = Message {
@@ -97,23 +114,32 @@ impl Kathy {
         &self,
         inbox_name: &str,
         inbox: Arc<CachingInbox>,
+        outbox_health: OutboxHealth,
     ) -> Instrumented<JoinHandle<Result<()>>> {
         let m = format!("Task for inbox named {} failed", inbox_name);
-        let handle = self.run_inbox(inbox).in_current_span();
+        let handle = self.run_inbox(inbox, outbox_health).in_current_span();
         let fut = async move { handle.await?.wrap_err(m) };
 
         tokio::spawn(fut).in_current_span()
     }
 
+    /// Dispatch messages to every inbox until the generator runs out
     pub fn run(&self) -> Instrumented<JoinHandle<Result<()>>> {
-        let inbox_tasks: Vec<Instrumented<JoinHandle<Result<()>>>> = self
+        let outbox_health_monitor =
+            OutboxHealthMonitor::new(self.outbox().outbox(), &self.core.metrics);
+        let mut tasks: Vec<Instrumented<JoinHandle<Result<()>>>> = self
             .inboxes()
             .iter()
             .map(|(inbox_name, inbox_contracts)| {
-                self.wrap_inbox_run(inbox_name, inbox_contracts.inbox.clone())
+                self.wrap_inbox_run(
+                    inbox_name,
+                    inbox_contracts.inbox.clone(),
+                    outbox_health_monitor.subscribe(),
+                )
             })
             .collect();
-        self.run_all(inbox_tasks)
+        tasks.push(outbox_health_monitor.spawn());
+        self.run_all(tasks)
     }
 }
 
@@ -0,0 +1,11 @@
+//! Kathy is chatty. She sends random messages to random recipients
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+#![warn(unused_extern_crates)]
+
+mod kathy;
+mod settings;
+
+pub use crate::kathy::Kathy;
+pub use crate::settings::KathySettings;
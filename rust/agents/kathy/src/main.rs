@@ -8,10 +8,7 @@ use eyre::Result;
 
 use abacus_base::Agent;
 
-use crate::kathy::Kathy;
-
-mod kathy;
-mod settings;
+use kathy::{Kathy, KathySettings};
 
 async fn _main() -> Result<()> {
     #[cfg(feature = "oneline-errors")]
@@ -19,7 +16,7 @@ async fn _main() -> Result<()> {
     #[cfg(not(feature = "oneline-errors"))]
     color_eyre::install()?;
 
-    let settings = settings::KathySettings::new()?;
+    let settings = KathySettings::new()?;
     let agent = Kathy::from_settings(settings).await?;
 
     agent
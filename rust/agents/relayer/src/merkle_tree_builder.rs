@@ -112,12 +112,9 @@ impl MerkleTreeBuilder {
         &mut self,
         checkpoint: &Checkpoint,
     ) -> Result<(), MerkleTreeBuilderError> {
-        if checkpoint.index == 0 {
-            return Ok(());
-        }
         let starting_index = self.prover.count() as u32;
         for i in starting_index..=checkpoint.index {
-            self.db.wait_for_leaf(i).await?;
+            self.db.wait_for_finalized_leaf(i).await?;
             self.ingest_leaf_index(i)?;
         }
 
@@ -1,4 +1,4 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, time::Duration};
 
 use abacus_core::{accumulator::merkle::Proof, CommittedMessage, MultisigSignedCheckpoint};
 
@@ -8,6 +8,10 @@ pub mod gelato_submitter;
 pub mod processor;
 pub mod serial_submitter;
 
+/// How long to wait before checking again whether a message delivered in a
+/// block yet to reach finality is still delivered
+const PARKED_DELIVERY_RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
 /// Processor scans DB for new messages and sends relevant messages
 /// over a channel to a submitter, for delivery.
 ///
@@ -1,26 +1,26 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::VecDeque, sync::Arc};
 
 use eyre::Result;
 use prometheus::IntGauge;
 use tokio::{
     sync::{mpsc, watch},
     task::JoinHandle,
-    time::{Instant, MissedTickBehavior},
+    time::Instant,
 };
-use tracing::{debug, info_span, instrument, instrument::Instrumented, warn, Instrument};
+use tracing::{debug, info, info_span, instrument, instrument::Instrumented, warn, Instrument};
 
-use abacus_base::{CoreMetrics, InboxContracts, Outboxes};
+use abacus_base::{CoreMetrics, InboxContracts, OutboxHealth};
 use abacus_core::{
-    db::AbacusDB, AbacusCommon, AbacusContract, CommittedMessage, MultisigSignedCheckpoint, Outbox,
+    db::AbacusDB, AbacusCommon, AbacusContract, CommittedMessage, Inbox, MessageStatus,
+    MultisigSignedCheckpoint,
 };
 
 use crate::{merkle_tree_builder::MerkleTreeBuilder, settings::matching_list::MatchingList};
 
-use super::SubmitMessageArgs;
+use super::{SubmitMessageArgs, PARKED_DELIVERY_RECHECK_INTERVAL};
 
 #[derive(Debug)]
 pub(crate) struct MessageProcessor {
-    outbox: Outboxes,
     db: AbacusDB,
     inbox_contracts: InboxContracts,
     whitelist: Arc<MatchingList>,
@@ -30,12 +30,15 @@ pub(crate) struct MessageProcessor {
     ckpt_rx: watch::Receiver<Option<MultisigSignedCheckpoint>>,
     prover_sync: MerkleTreeBuilder,
     message_leaf_index: u32,
+    /// Leaf indices of messages delivered in blocks yet to reach finality,
+    /// with when to check them again
+    parked_deliveries: VecDeque<(u32, Instant)>,
+    outbox_health: OutboxHealth,
 }
 
 impl MessageProcessor {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
-        outbox: Outboxes,
         db: AbacusDB,
         inbox_contracts: InboxContracts,
         whitelist: Arc<MatchingList>,
@@ -43,9 +46,9 @@ impl MessageProcessor {
         metrics: MessageProcessorMetrics,
         tx_msg: mpsc::UnboundedSender<SubmitMessageArgs>,
         ckpt_rx: watch::Receiver<Option<MultisigSignedCheckpoint>>,
+        outbox_health: OutboxHealth,
     ) -> Self {
         Self {
-            outbox,
             db: db.clone(),
             inbox_contracts,
             whitelist,
@@ -55,25 +58,31 @@ impl MessageProcessor {
             ckpt_rx,
             prover_sync: MerkleTreeBuilder::new(db),
             message_leaf_index: 0,
+            parked_deliveries: VecDeque::new(),
+            outbox_health,
         }
     }
 
     pub(crate) fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
         let span = info_span!("MessageProcessor");
-        let metrics_loop = tokio::spawn(Self::metrics_loop(
-            self.metrics.outbox_state_gauge.clone(),
-            self.outbox.clone(),
-        ));
-        tokio::spawn(async move {
-            let res = self.main_loop().await;
-            metrics_loop.abort();
-            res
-        })
-        .instrument(span)
+        tokio::spawn(self.main_loop()).instrument(span)
     }
 
     #[instrument(ret, err, skip(self), fields(inbox_name=self.inbox_contracts.inbox.chain_name(), local_domain=?self.inbox_contracts.inbox.local_domain()), level = "info")]
-    async fn main_loop(mut self) -> Result<()> {
+    async fn main_loop(self) -> Result<()> {
+        let mut outbox_health = self.outbox_health.clone();
+        tokio::select! {
+            biased;
+            _ = outbox_health.wait_for_failure() => {
+                outbox_health.report_failure("freezing message processing");
+                // Messages from a failed outbox are never forwarded again
+                std::future::pending().await
+            }
+            res = self.process_messages() => res,
+        }
+    }
+
+    async fn process_messages(mut self) -> Result<()> {
         // Ensure that there is at least one valid, known checkpoint before starting work loop.
         loop {
             self.ckpt_rx.changed().await?;
@@ -96,6 +105,11 @@ impl MessageProcessor {
             .processor_loop_gauge
             .set(self.message_leaf_index as i64);
 
+        // Check again on a message whose delivery was yet to reach finality.
+        if let Some(leaf_index) = self.next_parked_delivery() {
+            return self.recheck_delivery(leaf_index).await;
+        }
+
         // Scan until we find next index without delivery confirmation.
         if self
             .db
@@ -154,6 +168,7 @@ impl MessageProcessor {
                 whitelist=?self.whitelist,
                 msg=?message,
                 "Message not whitelisted, skipping idx {}", self.message_leaf_index);
+            self.db.mark_leaf_as_skipped(self.message_leaf_index)?;
             self.message_leaf_index += 1;
             return Ok(());
         }
@@ -167,17 +182,114 @@ impl MessageProcessor {
                 blacklist=?self.blacklist,
                 msg=?message,
                 "Message blacklisted, skipping idx {}", self.message_leaf_index);
+            self.db.mark_leaf_as_skipped(self.message_leaf_index)?;
             self.message_leaf_index += 1;
             return Ok(());
         }
 
-        // If validator hasn't published checkpoint covering self.message_leaf_index yet, wait
+        // Skip if already delivered, e.g. by another relayer. Deliveries in
+        // blocks yet to reach finality may be reorged out, so check on those
+        // again later.
+        let leaf = message.to_leaf();
+        if self.inbox_contracts.inbox.message_delivered(leaf)? {
+            if self
+                .inbox_contracts
+                .inbox
+                .message_delivery_finalized(leaf)?
+            {
+                debug!(
+                    inbox_name=?self.inbox_contracts.inbox.chain_name(),
+                    local_domain=?self.inbox_contracts.inbox.local_domain(),
+                    msg=?message,
+                    "Message already delivered, skipping idx {}", self.message_leaf_index);
+                self.db.mark_leaf_as_skipped(self.message_leaf_index)?;
+            } else {
+                debug!(
+                    inbox_name=?self.inbox_contracts.inbox.chain_name(),
+                    local_domain=?self.inbox_contracts.inbox.local_domain(),
+                    msg=?message,
+                    "Message delivered in a block yet to reach finality, parking idx {}", self.message_leaf_index);
+                self.park_delivery(self.message_leaf_index);
+            }
+            self.message_leaf_index += 1;
+            return Ok(());
+        }
+
+        if self.forward(self.message_leaf_index, message).await? {
+            self.message_leaf_index += 1;
+        }
+        Ok(())
+    }
+
+    /// Pop the next parked delivery if it's due to be checked again
+    fn next_parked_delivery(&mut self) -> Option<u32> {
+        match self.parked_deliveries.front() {
+            Some(&(leaf_index, recheck_at)) if recheck_at <= Instant::now() => {
+                self.parked_deliveries.pop_front();
+                Some(leaf_index)
+            }
+            _ => None,
+        }
+    }
+
+    fn park_delivery(&mut self, leaf_index: u32) {
+        self.parked_deliveries.push_back((
+            leaf_index,
+            Instant::now() + PARKED_DELIVERY_RECHECK_INTERVAL,
+        ));
+    }
+
+    /// Check whether a parked message's delivery has reached finality. The
+    /// message is parked again while it's yet to, and forwarded to the
+    /// submitter once the delivery has been reorged out.
+    async fn recheck_delivery(&mut self, leaf_index: u32) -> Result<()> {
+        let message = match self
+            .db
+            .message_by_leaf_index(leaf_index)?
+            .map(CommittedMessage::try_from)
+            .transpose()?
+        {
+            Some(message) => message,
+            // The message itself was reorged out of the outbox
+            None => return Ok(()),
+        };
+        let leaf = message.to_leaf();
+        let inbox = &self.inbox_contracts.inbox;
+        if inbox.message_delivery_finalized(leaf)? {
+            debug!(idx = leaf_index, "Message delivery reached finality");
+            self.db.mark_leaf_as_skipped(leaf_index)?;
+            return Ok(());
+        }
+        // Wait for the delivery to be rolled back from the DB too, as the
+        // submitter parks messages it still finds delivered there.
+        if inbox.message_delivered(leaf)?
+            || inbox.message_status(leaf).await? == MessageStatus::Processed
+        {
+            self.park_delivery(leaf_index);
+            return Ok(());
+        }
+
+        info!(
+            idx = leaf_index,
+            "Message delivery was reorged out, forwarding the message again"
+        );
+        if !self.forward(leaf_index, message).await? {
+            self.park_delivery(leaf_index);
+        }
+        Ok(())
+    }
+
+    /// Forward a message to the submitter with a proof against the latest
+    /// checkpoint, once a checkpoint covers it. Returns whether the message
+    /// was forwarded.
+    async fn forward(&mut self, leaf_index: u32, message: CommittedMessage) -> Result<bool> {
+        // If validator hasn't published checkpoint covering the message yet, wait
         // until it has, before forwarding the message to the submitter channel.
         let mut ckpt;
         loop {
             ckpt = self.ckpt_rx.borrow().clone();
             match &ckpt {
-                Some(ckpt) if ckpt.checkpoint.index >= self.message_leaf_index => {
+                Some(ckpt) if ckpt.checkpoint.index >= leaf_index => {
                     break;
                 }
                 _ => {
@@ -186,7 +298,7 @@ impl MessageProcessor {
             }
         }
         let checkpoint = ckpt.unwrap();
-        assert!(checkpoint.checkpoint.index >= self.message_leaf_index);
+        assert!(checkpoint.checkpoint.index >= leaf_index);
 
         // Include proof against checkpoint for message in the args provided to the submitter.
         if checkpoint.checkpoint.index >= self.prover_sync.count() {
@@ -195,48 +307,21 @@ impl MessageProcessor {
                 .await?;
         }
         assert_eq!(checkpoint.checkpoint.index + 1, self.prover_sync.count());
-        let proof = self.prover_sync.get_proof(self.message_leaf_index)?;
+        let proof = self.prover_sync.get_proof(leaf_index)?;
 
-        if self
-            .db
-            .leaf_by_leaf_index(self.message_leaf_index)?
-            .is_some()
-        {
-            debug!(
-                "Sending message at idx {} to submitter",
-                self.message_leaf_index
-            );
+        if self.db.leaf_by_leaf_index(leaf_index)?.is_some() {
+            debug!("Sending message at idx {} to submitter", leaf_index);
             // Finally, build the submit arg and dispatch it to the submitter.
-            let submit_args = SubmitMessageArgs::new(
-                self.message_leaf_index,
-                message,
-                checkpoint,
-                proof,
-                Instant::now(),
-            );
+            let submit_args =
+                SubmitMessageArgs::new(leaf_index, message, checkpoint, proof, Instant::now());
             self.tx_msg.send(submit_args)?;
-            self.message_leaf_index += 1;
+            Ok(true)
         } else {
             warn!(
-                idx=self.message_leaf_index,
+                idx=leaf_index,
                 inbox_name=?self.inbox_contracts.inbox.chain_name(),
                 "Unexpected missing leaf_by_leaf_index");
-        }
-        Ok(())
-    }
-
-    /// Spawn a task to update the outbox state gauge.
-    async fn metrics_loop(outbox_state_gauge: IntGauge, outbox: Outboxes) {
-        let mut interval = tokio::time::interval(Duration::from_secs(60));
-        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
-        loop {
-            let state = outbox.state().await;
-            match &state {
-                Ok(state) => outbox_state_gauge.set(*state as u8 as i64),
-                Err(e) => warn!(error = %e, "Failed to get outbox state"),
-            };
-
-            interval.tick().await;
+            Ok(false)
         }
     }
 }
@@ -244,7 +329,6 @@ impl MessageProcessor {
 #[derive(Debug)]
 pub(crate) struct MessageProcessorMetrics {
     processor_loop_gauge: IntGauge,
-    outbox_state_gauge: IntGauge,
 }
 
 impl MessageProcessorMetrics {
@@ -255,7 +339,154 @@ impl MessageProcessorMetrics {
                 outbox_chain,
                 inbox_chain,
             ]),
-            outbox_state_gauge: metrics.outbox_state().with_label_values(&[outbox_chain]),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use ethers::types::{H256, U256};
+
+    use abacus_base::{CachingInbox, InboxValidatorManagerVariants, OutboxHealthMonitor, Outboxes};
+    use abacus_core::{
+        accumulator::{incremental::IncrementalMerkle, merkle::Proof},
+        AbacusMessage, ChainCommunicationError, Checkpoint, Encode, InboxValidatorManager,
+        MessageDispatchMeta, RawCommittedMessage, RawCommittedMessageWithMeta, TxOutcome,
+    };
+    use abacus_test::{
+        mocks::{inbox::MockInboxContract, MockOutboxContract},
+        test_utils::run_test_db,
+    };
+
+    use super::*;
+
+    const LOCAL_DOMAIN: u32 = 2000;
+
+    #[derive(Debug)]
+    struct UnusedValidatorManager;
+
+    #[async_trait]
+    impl InboxValidatorManager for UnusedValidatorManager {
+        async fn process(
+            &self,
+            _: &MultisigSignedCheckpoint,
+            _: &AbacusMessage,
+            _: &Proof,
+        ) -> Result<TxOutcome, ChainCommunicationError> {
+            unimplemented!("messages are only forwarded to the submitter")
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_rechecks_deliveries_yet_to_reach_finality() {
+        run_test_db(|db| async move {
+            let outbox_db = AbacusDB::new("outbox", db.clone());
+            let inbox_db = AbacusDB::new("inbox", db);
+
+            let messages: Vec<_> = (0..2)
+                .map(|leaf_index| RawCommittedMessageWithMeta {
+                    message: RawCommittedMessage {
+                        leaf_index,
+                        message: AbacusMessage {
+                            destination: LOCAL_DOMAIN,
+                            body: vec![leaf_index as u8],
+                            ..Default::default()
+                        }
+                        .to_vec(),
+                    },
+                    meta: MessageDispatchMeta {
+                        block_number: leaf_index.into(),
+                        block_hash: H256::from_low_u64_be(leaf_index.into()),
+                        timestamp: 0,
+                        transaction_hash: H256::zero(),
+                        log_index: U256::zero(),
+                    },
+                })
+                .collect();
+            outbox_db.store_messages(&messages).unwrap();
+            let leaves: Vec<_> = messages.iter().map(|m| m.message.leaf()).collect();
+            let mut tree = IncrementalMerkle::default();
+            leaves.iter().for_each(|leaf| tree.ingest(*leaf));
+
+            // Both messages were delivered in blocks yet to reach finality
+            inbox_db.store_delivered_message(leaves[0], false).unwrap();
+            inbox_db.store_delivered_message(leaves[1], false).unwrap();
+
+            let status = Arc::new(Mutex::new(MessageStatus::Processed));
+            let mut mock_inbox = MockInboxContract::new();
+            mock_inbox.expect__local_domain().return_const(LOCAL_DOMAIN);
+            mock_inbox
+                .expect__chain_name()
+                .return_const("inbox".to_owned());
+            {
+                let status = status.clone();
+                mock_inbox
+                    .expect__message_status()
+                    .returning(move |_| Ok(*status.lock().unwrap()));
+            }
+            let mut mock_outbox = MockOutboxContract::new();
+            mock_outbox
+                .expect__chain_name()
+                .return_const("outbox".to_owned());
+
+            let metrics =
+                CoreMetrics::new("relayer_test", None, prometheus::Registry::new()).unwrap();
+            let outbox_health =
+                OutboxHealthMonitor::new(Outboxes::from(mock_outbox), &metrics).subscribe();
+            let inbox_contracts = InboxContracts {
+                inbox: Arc::new(CachingInbox::new(mock_inbox.into(), inbox_db.clone(), None)),
+                validator_manager: Arc::new(
+                    InboxValidatorManagerVariants::Mock(Box::new(UnusedValidatorManager)).into(),
+                ),
+            };
+            let (tx_msg, mut rx_msg) = mpsc::unbounded_channel();
+            let (_ckpt_tx, ckpt_rx) = watch::channel(Some(MultisigSignedCheckpoint {
+                checkpoint: Checkpoint {
+                    outbox_domain: 0,
+                    root: tree.root(),
+                    index: 1,
+                },
+                signatures: vec![],
+            }));
+            let mut processor = MessageProcessor::new(
+                outbox_db,
+                inbox_contracts,
+                Default::default(),
+                Default::default(),
+                MessageProcessorMetrics::new(&metrics, "outbox", "inbox"),
+                tx_msg,
+                ckpt_rx,
+                outbox_health,
+            );
+
+            // Both deliveries are parked rather than skipped for good
+            processor.tick().await.unwrap();
+            processor.tick().await.unwrap();
+            assert_eq!(processor.message_leaf_index, 2);
+            assert_eq!(processor.parked_deliveries.len(), 2);
+
+            // Check on both once the recheck interval elapses
+            tokio::time::advance(PARKED_DELIVERY_RECHECK_INTERVAL).await;
+
+            // One delivery reaches finality, the other is reorged out
+            inbox_db.finalize_delivered_message(leaves[0]).unwrap();
+            inbox_db.remove_delivered_message(leaves[1]).unwrap();
+            *status.lock().unwrap() = MessageStatus::None;
+
+            processor.tick().await.unwrap();
+            processor.tick().await.unwrap();
+            assert!(processor.parked_deliveries.is_empty());
+            assert!(processor.db.leaf_skipped(0).unwrap());
+            assert!(!processor.db.leaf_skipped(1).unwrap());
+
+            let forwarded = rx_msg.try_recv().unwrap();
+            assert_eq!(forwarded.leaf_index, 1);
+            assert_eq!(forwarded.committed_message.to_leaf(), leaves[1]);
+            assert!(rx_msg.try_recv().is_err());
+        })
+        .await
+    }
+}
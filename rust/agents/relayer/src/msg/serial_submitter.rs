@@ -1,7 +1,8 @@
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, VecDeque};
 
 use abacus_base::CoreMetrics;
 use abacus_base::InboxContracts;
+use abacus_base::OutboxHealth;
 use abacus_core::db::AbacusDB;
 use abacus_core::AbacusContract;
 use abacus_core::Inbox;
@@ -17,7 +18,7 @@ use tracing::debug;
 use tracing::instrument;
 use tracing::{info, info_span, instrument::Instrumented, Instrument};
 
-use super::SubmitMessageArgs;
+use super::{SubmitMessageArgs, PARKED_DELIVERY_RECHECK_INTERVAL};
 
 /// SerialSubmitter accepts undelivered messages over a channel from a MessageProcessor.  It is
 /// responsible for executing the right strategy to deliver those messages to the destination
@@ -77,7 +78,9 @@ use super::SubmitMessageArgs;
 ///
 ///  *  Insufficient interchain gas payment on source chain
 ///  *  Already delivered to destination chain, e.g. maybe by a different relayer, or the result of
-///     a submission attempt just prior to an old incarnation of this task crashing.
+///     a submission attempt just prior to an old incarnation of this task crashing. Messages
+///     delivered in blocks yet to reach finality are parked, and checked again until the
+///     delivery either reaches finality or is reorged out.
 ///  *  Not whitelisted (currently checked by processor)
 ///  *  Wrong destination chain (currently checked by processor)
 ///  *  Checkpoint index < leaf index (currently checked by processor)
@@ -118,12 +121,16 @@ pub(crate) struct SerialSubmitter {
     /// to be dispatched. The SerialSubmitter can only dispatch one message at a time, so this
     /// queue could grow.
     run_queue: BinaryHeap<SubmitMessageArgs>,
+    /// Messages delivered in blocks yet to reach finality, with when to check them again.
+    parked_deliveries: VecDeque<(SubmitMessageArgs, Instant)>,
     /// Inbox / InboxValidatorManager on the destination chain.
     inbox_contracts: InboxContracts,
     /// Interface to agent rocks DB for e.g. writing delivery status upon completion.
     db: AbacusDB,
     /// Metrics for serial submitter.
     metrics: SerialSubmitterMetrics,
+    /// State of the origin outbox. Submissions are frozen once it has failed.
+    outbox_health: OutboxHealth,
 }
 
 impl SerialSubmitter {
@@ -132,14 +139,17 @@ impl SerialSubmitter {
         inbox_contracts: InboxContracts,
         db: AbacusDB,
         metrics: SerialSubmitterMetrics,
+        outbox_health: OutboxHealth,
     ) -> Self {
         Self {
             rx,
             wait_queue: Vec::new(),
             run_queue: BinaryHeap::new(),
+            parked_deliveries: VecDeque::new(),
             inbox_contracts,
             db,
             metrics,
+            outbox_health,
         }
     }
 
@@ -181,6 +191,15 @@ impl SerialSubmitter {
         // have been in the verification queue for > threshold_time, move them back to the wait
         // queue for further processing.
 
+        // Check again on parked deliveries which are due.
+        while let Some((_, recheck_at)) = self.parked_deliveries.front() {
+            if *recheck_at > Instant::now() {
+                break;
+            }
+            let (msg, _) = self.parked_deliveries.pop_front().unwrap();
+            self.wait_queue.push(msg);
+        }
+
         // Promote any newly-ready messages from the wait queue to the run queue.
         let wait_messages: Vec<_> = self.wait_queue.drain(..).collect();
         for msg in wait_messages {
@@ -197,22 +216,33 @@ impl SerialSubmitter {
             .run_queue_length_gauge
             .set(self.run_queue.len() as i64);
 
+        // Messages from a failed outbox stay queued but are never submitted.
+        if self.outbox_health.is_failed() {
+            debug!("Origin outbox has failed, not submitting messages");
+            return Ok(());
+        }
+
         // Pick the next message to try processing.
         let mut msg = match self.run_queue.pop() {
             Some(m) => m,
             None => return Ok(()),
         };
 
-        // If the message has already been processed according to message_status call on
-        // inbox, e.g. due to another relayer having already processed, then mark it as
-        // already-processed, and move on to the next tick.
-        // TODO(webbhorn): Make this robust to re-orgs on inbox.
-        if let MessageStatus::Processed = self
-            .inbox_contracts
-            .inbox
-            .message_status(msg.committed_message.to_leaf())
-            .await?
-        {
+        // If the message has already been processed according to the indexed inbox events or
+        // the message_status call on inbox, e.g. due to another relayer having already
+        // processed, then mark it as already-processed, and move on to the next tick. A
+        // delivery indexed from a block yet to reach finality may still be reorged out, so
+        // the message is parked instead until it's known which.
+        let leaf = msg.committed_message.to_leaf();
+        let inbox = &self.inbox_contracts.inbox;
+        let delivery_finalized = inbox.message_delivery_finalized(leaf)?;
+        if !delivery_finalized && inbox.message_delivered(leaf)? {
+            debug!(msg=?msg, "Message delivery is yet to reach finality, parking message");
+            self.parked_deliveries
+                .push_back((msg, Instant::now() + PARKED_DELIVERY_RECHECK_INTERVAL));
+            return Ok(());
+        }
+        if delivery_finalized || inbox.message_status(leaf).await? == MessageStatus::Processed {
             info!(
                 "Unexpected status for message with leaf index '{}' (already processed): '{:?}'",
                 msg.leaf_index, msg
@@ -315,3 +345,141 @@ impl SerialSubmitterMetrics {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use async_trait::async_trait;
+    use ethers::types::H256;
+
+    use abacus_base::{CachingInbox, InboxValidatorManagerVariants, OutboxHealthMonitor, Outboxes};
+    use abacus_core::{
+        accumulator::{merkle::Proof, TREE_DEPTH},
+        AbacusMessage, ChainCommunicationError, Checkpoint, CommittedMessage,
+        MultisigSignedCheckpoint, TxOutcome,
+    };
+    use abacus_test::{
+        mocks::{inbox::MockInboxContract, MockOutboxContract},
+        test_utils::run_test_db,
+    };
+
+    use super::*;
+
+    /// Counts the messages it's asked to process
+    #[derive(Debug, Default)]
+    struct CountingValidatorManager(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl InboxValidatorManager for CountingValidatorManager {
+        async fn process(
+            &self,
+            _: &MultisigSignedCheckpoint,
+            _: &AbacusMessage,
+            _: &Proof,
+        ) -> Result<TxOutcome, ChainCommunicationError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(TxOutcome {
+                txid: H256::zero(),
+                executed: true,
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_parks_messages_delivered_yet_to_reach_finality() {
+        run_test_db(|db| async move {
+            let outbox_db = AbacusDB::new("outbox", db.clone());
+            let inbox_db = AbacusDB::new("inbox", db);
+
+            let message = CommittedMessage {
+                leaf_index: 0,
+                message: AbacusMessage::default(),
+            };
+            let leaf = message.to_leaf();
+
+            let mut mock_inbox = MockInboxContract::new();
+            mock_inbox
+                .expect__chain_name()
+                .return_const("inbox".to_owned());
+            mock_inbox
+                .expect__message_status()
+                .returning(|_| Ok(MessageStatus::None));
+            let mut mock_outbox = MockOutboxContract::new();
+            mock_outbox
+                .expect__chain_name()
+                .return_const("outbox".to_owned());
+
+            let metrics =
+                CoreMetrics::new("relayer_test", None, prometheus::Registry::new()).unwrap();
+            let outbox_health =
+                OutboxHealthMonitor::new(Outboxes::from(mock_outbox), &metrics).subscribe();
+            let processed = Arc::new(AtomicUsize::new(0));
+            let inbox_contracts = InboxContracts {
+                inbox: Arc::new(CachingInbox::new(mock_inbox.into(), inbox_db.clone(), None)),
+                validator_manager: Arc::new(
+                    InboxValidatorManagerVariants::Mock(Box::new(CountingValidatorManager(
+                        processed.clone(),
+                    )))
+                    .into(),
+                ),
+            };
+            let (tx, rx) = mpsc::unbounded_channel();
+            let mut submitter = SerialSubmitter::new(
+                rx,
+                inbox_contracts,
+                outbox_db,
+                SerialSubmitterMetrics::new(&metrics, "outbox", "inbox"),
+                outbox_health,
+            );
+
+            // The message's delivery is indexed from a block yet to reach finality
+            inbox_db.store_delivered_message(leaf, false).unwrap();
+            tx.send(SubmitMessageArgs::new(
+                0,
+                message,
+                MultisigSignedCheckpoint {
+                    checkpoint: Checkpoint {
+                        outbox_domain: 0,
+                        root: H256::zero(),
+                        index: 0,
+                    },
+                    signatures: vec![],
+                },
+                Proof {
+                    leaf,
+                    index: 0,
+                    path: [H256::zero(); TREE_DEPTH],
+                },
+                Instant::now(),
+            ))
+            .unwrap();
+
+            // The message is parked rather than marked processed
+            submitter.tick().await.unwrap();
+            assert_eq!(submitter.parked_deliveries.len(), 1);
+            assert_eq!(
+                submitter.db.retrieve_leaf_processing_status(0).unwrap(),
+                None
+            );
+
+            // The delivery is rolled back, so the message is processed once rechecked
+            inbox_db.remove_delivered_message(leaf).unwrap();
+            submitter.tick().await.unwrap();
+            assert_eq!(processed.load(Ordering::SeqCst), 0);
+
+            tokio::time::advance(PARKED_DELIVERY_RECHECK_INTERVAL).await;
+            submitter.tick().await.unwrap();
+            assert!(submitter.parked_deliveries.is_empty());
+            assert_eq!(processed.load(Ordering::SeqCst), 1);
+            assert_eq!(
+                submitter.db.retrieve_leaf_processing_status(0).unwrap(),
+                Some(true)
+            );
+        })
+        .await
+    }
+}
@@ -11,9 +11,9 @@ use tracing::{info, info_span, instrument::Instrumented, Instrument};
 
 use abacus_base::{
     chains::GelatoConf, AbacusAgentCore, Agent, CachingInterchainGasPaymaster, ContractSyncMetrics,
-    InboxContracts, MultisigCheckpointSyncer,
+    DbPruner, InboxContracts, MultisigCheckpointSyncer, OutboxHealth, OutboxHealthMonitor,
 };
-use abacus_core::{AbacusContract, MultisigSignedCheckpoint};
+use abacus_core::{AbacusCommon, AbacusContract, MultisigSignedCheckpoint};
 
 use crate::msg::gelato_submitter::GelatoSubmitter;
 use crate::msg::processor::{MessageProcessor, MessageProcessorMetrics};
@@ -49,9 +49,14 @@ impl Agent for Relayer {
     where
         Self: Sized,
     {
+        let core = settings
+            .as_ref()
+            .try_into_abacus_core(Self::AGENT_NAME, true)
+            .await?;
         let multisig_checkpoint_syncer: MultisigCheckpointSyncer = settings
             .multisigcheckpointsyncer
-            .try_into_multisig_checkpoint_syncer()?;
+            .try_into_multisig_checkpoint_syncer(core.outbox.local_domain())
+            .await?;
 
         let whitelist = parse_matching_list(&settings.whitelist);
         let blacklist = parse_matching_list(&settings.blacklist);
@@ -63,10 +68,7 @@ impl Agent for Relayer {
                 .parse()
                 .unwrap_or(5),
             multisig_checkpoint_syncer,
-            core: settings
-                .as_ref()
-                .try_into_abacus_core(Self::AGENT_NAME, true)
-                .await?,
+            core,
             whitelist,
             blacklist,
         })
@@ -111,6 +113,7 @@ impl Relayer {
         inbox_contracts: InboxContracts,
         signed_checkpoint_receiver: Receiver<Option<MultisigSignedCheckpoint>>,
         gelato_conf: Option<GelatoConf>,
+        outbox_health: OutboxHealth,
     ) -> Instrumented<JoinHandle<Result<()>>> {
         let outbox = self.outbox().outbox();
         let metrics = MessageProcessorMetrics::new(
@@ -139,12 +142,12 @@ impl Relayer {
                         outbox.chain_name(),
                         inbox_contracts.inbox.chain_name(),
                     ),
+                    outbox_health.clone(),
                 );
                 serial_submitter.spawn()
             }
         };
         let message_processor = MessageProcessor::new(
-            outbox,
             self.outbox().db(),
             inbox_contracts,
             self.whitelist.clone(),
@@ -152,6 +155,7 @@ impl Relayer {
             metrics,
             new_messages_send_channel,
             signed_checkpoint_receiver,
+            outbox_health,
         );
         info!(
             message_processor=?message_processor,
@@ -169,6 +173,8 @@ impl Relayer {
     pub fn run(&self) -> Instrumented<JoinHandle<Result<()>>> {
         let (signed_checkpoint_sender, signed_checkpoint_receiver) =
             tokio::sync::watch::channel::<Option<MultisigSignedCheckpoint>>(None);
+        let outbox_health_monitor =
+            OutboxHealthMonitor::new(self.outbox().outbox(), &self.core.metrics);
 
         let mut tasks: Vec<Instrumented<JoinHandle<Result<()>>>> = self
             .inboxes()
@@ -178,21 +184,50 @@ impl Relayer {
                     inbox_contracts.clone(),
                     signed_checkpoint_receiver.clone(),
                     self.core.settings.inboxes[inbox_name].gelato_conf.clone(),
+                    outbox_health_monitor.subscribe(),
                 )
             })
             .collect();
 
         tasks.push(self.run_checkpoint_fetcher(signed_checkpoint_sender));
+        tasks.push(outbox_health_monitor.spawn());
 
         let sync_metrics = ContractSyncMetrics::new(self.metrics());
         tasks.push(self.run_outbox_sync(sync_metrics.clone()));
 
+        for (inbox_name, inbox_contracts) in self.inboxes().iter() {
+            if let Some(index) = &self.core.settings.inboxes[inbox_name].index {
+                tasks.extend(
+                    inbox_contracts
+                        .inbox
+                        .sync(index.clone(), sync_metrics.clone()),
+                );
+            }
+        }
+
         if let Some(paymaster) = self.interchain_gas_paymaster() {
             tasks.push(self.run_interchain_gas_paymaster_sync(paymaster, sync_metrics));
         } else {
             info!("Interchain Gas Paymaster not provided, not running sync");
         }
 
+        let relayed_destinations = self
+            .inboxes()
+            .values()
+            .map(|inbox_contracts| inbox_contracts.inbox.local_domain())
+            .collect();
+        if let Some(pruner) = DbPruner::new(
+            self.outbox().db(),
+            self.outbox().outbox().chain_name(),
+            relayed_destinations,
+            &self.core.settings.retention,
+            &self.core.metrics,
+        ) {
+            tasks.push(pruner.spawn());
+        } else {
+            info!("DB retention not configured, not pruning");
+        }
+
         self.run_all(tasks)
     }
 }
@@ -208,4 +243,177 @@ fn parse_matching_list(list: &Option<String>) -> Arc<MatchingList> {
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use ethers::signers::{LocalWallet, Signer};
+    use ethers::types::Address;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    use abacus_core::{
+        CommittedMessage, ContractLocator, Inbox, Indexer, MessageStatus, OutboxIndexer,
+    };
+    use abacus_simulated::{SimulatedInbox, SimulatedNetwork, SimulatedOutboxIndexer};
+    use kathy::{Kathy, KathySettings};
+    use validator::{Validator, ValidatorSettings};
+
+    use super::*;
+    use crate::settings::RelayerSettings;
+
+    const NETWORK: &str = "agents-e2e";
+    const VALIDATOR_KEY: &str = "1111111111111111111111111111111111111111111111111111111111111111";
+    const KATHY_KEY: &str = "2222222222222222222222222222222222222222222222222222222222222222";
+    const RELAYER_KEY: &str = "3333333333333333333333333333333333333333333333333333333333333333";
+
+    /// Base settings of an agent connected to the simulated chains, merged
+    /// with the agent's own `settings`
+    fn agent_settings(
+        db: &Path,
+        inbox: Address,
+        validator_manager: Address,
+        signers: Value,
+        settings: Value,
+    ) -> Value {
+        let chain = |name: &str, domain: &str, addresses: Value| {
+            json!({
+                "name": name,
+                "domain": domain,
+                "finalityBlocks": "1",
+                "rpcStyle": "simulated",
+                "connection": { "network": NETWORK },
+                "addresses": addresses,
+            })
+        };
+        let mut base = json!({
+            "db": db.to_str().unwrap(),
+            "outbox": chain("test1", "1000", json!({ "outbox": format!("{:?}", Address::zero()) })),
+            "inboxes": {
+                "test2": chain("test2", "2000", json!({
+                    "inbox": format!("{:?}", inbox),
+                    "validatorManager": format!("{:?}", validator_manager),
+                })),
+            },
+            "tracing": {},
+            "signers": signers,
+        });
+        for (key, value) in settings.as_object().unwrap() {
+            base[key] = value.clone();
+        }
+        base
+    }
+
+    fn hex_key(key: &str) -> Value {
+        json!({ "type": "hexKey", "key": key })
+    }
+
+    #[tokio::test]
+    async fn it_relays_messages_dispatched_by_kathy() {
+        let network = SimulatedNetwork::create(NETWORK, Duration::from_millis(50));
+        let validator_wallet: LocalWallet = VALIDATOR_KEY.parse().unwrap();
+        let validator_address = format!("{:?}", validator_wallet.address());
+        let destination = network.chain(2000);
+        let inbox_address = destination.deploy_inbox(1000);
+        let validator_manager_address = destination.deploy_inbox_validator_manager(
+            inbox_address,
+            [validator_wallet.address()],
+            1,
+        );
+
+        let dbs = [(); 3].map(|_| TempDir::new().unwrap());
+        let checkpoints_dir = TempDir::new().unwrap();
+        let checkpoints_path = checkpoints_dir.path().to_str().unwrap();
+        let settings = |db: &TempDir, signers: Value, settings: Value| {
+            agent_settings(
+                db.path(),
+                inbox_address,
+                validator_manager_address,
+                signers,
+                settings,
+            )
+        };
+
+        let validator_settings: ValidatorSettings = serde_json::from_value(settings(
+            &dbs[0],
+            json!({}),
+            json!({
+                "validator": hex_key(VALIDATOR_KEY),
+                "checkpointsyncer": { "type": "localStorage", "path": checkpoints_path },
+                "reorgperiod": "0",
+                "interval": "1",
+                "signeveryindex": "true",
+            }),
+        ))
+        .unwrap();
+        let kathy_settings: KathySettings = serde_json::from_value(settings(
+            &dbs[1],
+            json!({ "test1": hex_key(KATHY_KEY) }),
+            json!({
+                "interval": "1",
+                "chat": { "type": "orderedList", "messages": ["hello"] },
+            }),
+        ))
+        .unwrap();
+        let relayer_settings: RelayerSettings = serde_json::from_value(settings(
+            &dbs[2],
+            json!({ "test2": hex_key(RELAYER_KEY) }),
+            json!({
+                "signedcheckpointpollinginterval": "1",
+                "maxprocessingretries": "3",
+                "multisigcheckpointsyncer": {
+                    "threshold": 1,
+                    "checkpointsyncers": {
+                        validator_address: {
+                            "type": "localStorage",
+                            "path": checkpoints_path,
+                        },
+                    },
+                },
+            }),
+        ))
+        .unwrap();
+
+        let validator = Validator::from_settings(validator_settings).await.unwrap();
+        let kathy = Kathy::from_settings(kathy_settings).await.unwrap();
+        let relayer = Relayer::from_settings(relayer_settings).await.unwrap();
+        let tasks = [validator.run(), kathy.run(), relayer.run()];
+
+        // Wait for kathy's message to be dispatched and then processed
+        let locator = |domain: u32, address: Address| ContractLocator {
+            chain_name: format!("test-{}", domain),
+            domain,
+            address: address.into(),
+        };
+        let outbox_indexer =
+            SimulatedOutboxIndexer::new(&network, &locator(1000, Address::zero()), 0);
+        let inbox = SimulatedInbox::new(
+            &network,
+            &locator(2000, inbox_address),
+            validator_manager_address,
+        );
+        let processed = tokio::time::timeout(Duration::from_secs(60), async {
+            loop {
+                let block = outbox_indexer.get_finalized_block_number().await.unwrap();
+                let messages = outbox_indexer
+                    .fetch_sorted_messages(0, block)
+                    .await
+                    .unwrap();
+                if let Some(message) = messages.first() {
+                    let leaf = CommittedMessage::try_from(message.message.clone())
+                        .unwrap()
+                        .to_leaf();
+                    if inbox.message_status(leaf).await.unwrap() == MessageStatus::Processed {
+                        break;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+        for task in tasks {
+            task.into_inner().abort();
+        }
+        assert!(processed.is_ok(), "message was not processed");
+    }
+}
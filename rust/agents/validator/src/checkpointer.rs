@@ -0,0 +1,147 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use eyre::Result;
+use tokio::{
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
+use tracing::{debug, info, info_span, instrument::Instrumented, warn, Instrument};
+
+use abacus_base::{CachingOutbox, OutboxHealth};
+use abacus_core::Outbox;
+
+/// When to cache the latest checkpoint on the outbox. Nothing is cached
+/// while every dispatched message is covered by the cached checkpoint.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CachePolicy {
+    /// Cache once this long has passed since a checkpoint was last cached
+    pub(crate) period: Option<Duration>,
+    /// Cache once this many messages are not covered by the cached checkpoint
+    pub(crate) messages: Option<u32>,
+}
+
+impl CachePolicy {
+    /// Whether the checkpoint should be cached with `uncached_messages`
+    /// messages dispatched after the cached checkpoint, which was cached
+    /// `since_cached` ago
+    fn is_due(&self, uncached_messages: u32, since_cached: Duration) -> bool {
+        uncached_messages > 0
+            && (self.messages.map_or(false, |m| uncached_messages >= m)
+                || self.period.map_or(false, |p| since_cached >= p))
+    }
+}
+
+/// Caches the latest checkpoint on the outbox according to a `CachePolicy`,
+/// anchoring roots on-chain. Caching is a transaction, so a signer must be
+/// configured for the outbox chain.
+pub(crate) struct Checkpointer {
+    interval: u64,
+    outbox: Arc<CachingOutbox>,
+    policy: CachePolicy,
+    outbox_health: OutboxHealth,
+}
+
+impl Checkpointer {
+    pub(crate) fn new(
+        interval: u64,
+        outbox: Arc<CachingOutbox>,
+        policy: CachePolicy,
+        outbox_health: OutboxHealth,
+    ) -> Self {
+        Self {
+            interval,
+            outbox,
+            policy,
+            outbox_health,
+        }
+    }
+
+    pub(crate) fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
+        let span = info_span!("Checkpointer");
+        tokio::spawn(self.main_task()).instrument(span)
+    }
+
+    async fn main_task(self) -> Result<()> {
+        let mut outbox_health = self.outbox_health.clone();
+        tokio::select! {
+            biased;
+            _ = outbox_health.wait_for_failure() => {
+                outbox_health.report_failure("halting checkpoint caching");
+                // Caching would revert on a failed outbox
+                std::future::pending().await
+            }
+            res = self.cache_checkpoints() => res,
+        }
+    }
+
+    async fn cache_checkpoints(&self) -> Result<()> {
+        info!(policy = ?self.policy, "Starting Checkpointer");
+        let mut cached_count = None;
+        let mut last_cached = Instant::now();
+        loop {
+            sleep(Duration::from_secs(self.interval)).await;
+
+            let count = self.outbox.count().await?;
+            let cached = self.outbox.latest_cached_checkpoint().await?;
+            // Nothing has been cached while the cached root is zero
+            let latest_cached_count = if cached.root.is_zero() {
+                0
+            } else {
+                cached.index + 1
+            };
+            // Checkpoints cached by others also reset the period
+            if cached_count != Some(latest_cached_count) {
+                cached_count = Some(latest_cached_count);
+                last_cached = Instant::now();
+            }
+
+            let uncached_messages = count.saturating_sub(latest_cached_count);
+            if !self.policy.is_due(uncached_messages, last_cached.elapsed()) {
+                debug!(
+                    uncached_messages = uncached_messages,
+                    "Not yet caching checkpoint"
+                );
+                continue;
+            }
+
+            match self.outbox.cache_checkpoint().await {
+                Ok(outcome) => {
+                    info!(
+                        txid = ?outcome.txid,
+                        uncached_messages = uncached_messages,
+                        "Cached checkpoint"
+                    );
+                    last_cached = Instant::now();
+                }
+                // Retried at the next interval
+                Err(error) => warn!(error = ?error, "Failed to cache checkpoint"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::CachePolicy;
+
+    #[test]
+    fn it_caches_when_either_threshold_is_reached() {
+        let policy = CachePolicy {
+            period: Some(Duration::from_secs(60)),
+            messages: Some(10),
+        };
+        assert!(!policy.is_due(0, Duration::from_secs(600)));
+        assert!(!policy.is_due(9, Duration::from_secs(59)));
+        assert!(policy.is_due(10, Duration::from_secs(0)));
+        assert!(policy.is_due(1, Duration::from_secs(60)));
+
+        let messages_only = CachePolicy {
+            period: None,
+            messages: Some(10),
+        };
+        assert!(!messages_only.is_due(9, Duration::from_secs(600)));
+    }
+}
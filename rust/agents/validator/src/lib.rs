@@ -0,0 +1,14 @@
+//! The validator signs Outbox checkpoints that have reached finality.
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+#![warn(unused_extern_crates)]
+
+mod checkpointer;
+mod settings;
+mod signing_guard;
+mod submit;
+mod validator;
+
+pub use crate::settings::ValidatorSettings;
+pub use crate::validator::Validator;
@@ -8,11 +8,7 @@ use eyre::Result;
 
 use abacus_base::Agent;
 
-use crate::validator::Validator;
-
-mod settings;
-mod submit;
-mod validator;
+use validator::{Validator, ValidatorSettings};
 
 async fn _main() -> Result<()> {
     #[cfg(feature = "oneline-errors")]
@@ -20,10 +16,15 @@ async fn _main() -> Result<()> {
     #[cfg(not(feature = "oneline-errors"))]
     color_eyre::install()?;
 
-    let settings = settings::ValidatorSettings::new()?;
+    let settings = ValidatorSettings::new()?;
+    let signing_history_export = settings.signinghistoryexport.clone();
 
     let agent = Validator::from_settings(settings).await?;
 
+    if let Some(path) = signing_history_export {
+        return agent.export_signing_history(&path);
+    }
+
     agent
         .as_ref()
         .settings
@@ -7,8 +7,27 @@ decl_settings!(Validator {
     validator: abacus_base::SignerConf,
     /// The checkpoint syncer configuration
     checkpointsyncer: abacus_base::CheckpointSyncerConf,
+    /// An optional shared location to additionally publish the signed
+    /// announcement of the checkpoint syncer's storage location to
+    announcementsyncer: Option<abacus_base::CheckpointSyncerConf>,
     /// The reorg_period in blocks
     reorgperiod: String,
     /// How frequently to check for new checkpoints
     interval: String,
+    /// Optional. If "true", sign a checkpoint for every index rather than only
+    /// the latest checkpoint
+    signeveryindex: Option<String>,
+    /// Optional. A signing history file to merge into the local signing
+    /// history on startup
+    signinghistoryimport: Option<String>,
+    /// Optional. If set, export the local signing history to this file and
+    /// exit without signing
+    signinghistoryexport: Option<String>,
+    /// Optional. If set, cache the latest checkpoint on the outbox once this
+    /// many seconds have passed since a checkpoint was last cached, if any
+    /// messages have been dispatched since
+    cacheperiod: Option<String>,
+    /// Optional. If set, cache the latest checkpoint on the outbox once this
+    /// many messages have been dispatched since a checkpoint was last cached
+    cachemessages: Option<String>,
 });
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use ethers::signers::Signer;
+use ethers::types::H256;
+use tracing::{error, info, instrument};
+
+use abacus_core::db::{DbError, TypedDB, DB};
+use abacus_core::{AbacusError, Checkpoint, SignedCheckpoint, Signers, SignersError};
+
+static SIGNED_CHECKPOINT: &str = "signed_checkpoint_";
+static LATEST_SIGNED_INDEX: &str = "latest_signed_index";
+
+/// Errors returned by the CheckpointSigningGuard
+#[derive(Debug, thiserror::Error)]
+pub enum SigningGuardError {
+    /// A different root has already been signed at this index
+    #[error("Refusing to sign conflicting checkpoint at index {index}. Previously signed root: {signed_root:?}, attempted root: {attempted_root:?}")]
+    ConflictingCheckpoint {
+        /// The index of the checkpoint
+        index: u32,
+        /// The root that was previously signed at this index
+        signed_root: H256,
+        /// The root that was attempted to be signed
+        attempted_root: H256,
+    },
+    /// An imported signed checkpoint was not signed by this validator
+    #[error("Imported checkpoint at index {index} was not signed by this validator")]
+    ForeignSignature {
+        /// The index of the checkpoint
+        index: u32,
+    },
+    /// An imported signed checkpoint is for another outbox
+    #[error("Imported checkpoint at index {index} is for outbox domain {outbox_domain}, not {expected_domain}")]
+    ForeignDomain {
+        /// The index of the checkpoint
+        index: u32,
+        /// The outbox domain of the checkpoint
+        outbox_domain: u32,
+        /// The domain of this validator's outbox
+        expected_domain: u32,
+    },
+    /// Signer Error
+    #[error("{0}")]
+    SignersError(#[from] SignersError),
+    /// Abacus Error
+    #[error("{0}")]
+    AbacusError(#[from] AbacusError),
+    /// DB Error
+    #[error("{0}")]
+    DbError(#[from] DbError),
+    /// IO Error when importing or exporting the signing history
+    #[error("{0}")]
+    IoError(#[from] std::io::Error),
+    /// Serialization Error when importing or exporting the signing history
+    #[error("{0}")]
+    SerdeError(#[from] serde_json::Error),
+}
+
+/// Guards checkpoint signing against double signing by keeping a history of
+/// every checkpoint the validator has signed.
+///
+/// Key structure: ```<outbox_name>_signing_history_<prefix>_<key>```
+#[derive(Debug, Clone)]
+pub(crate) struct CheckpointSigningGuard {
+    outbox_domain: u32,
+    signer: Arc<Signers>,
+    db: TypedDB,
+}
+
+impl CheckpointSigningGuard {
+    pub(crate) fn new(outbox_name: &str, outbox_domain: u32, signer: Arc<Signers>, db: DB) -> Self {
+        Self {
+            outbox_domain,
+            signer,
+            db: TypedDB::new(format!("{}_signing_history", outbox_name), db),
+        }
+    }
+
+    /// The signer used to sign checkpoints
+    pub(crate) fn signer(&self) -> &Signers {
+        self.signer.as_ref()
+    }
+
+    /// Sign the checkpoint unless a different root has already been signed at
+    /// the same index. Re-signing an identical checkpoint returns the
+    /// previously stored signature.
+    #[instrument(err, skip(self))]
+    pub(crate) async fn sign(
+        &self,
+        checkpoint: Checkpoint,
+    ) -> Result<SignedCheckpoint, SigningGuardError> {
+        if let Some(signed_checkpoint) = self.retrieve_signed_checkpoint(checkpoint.index)? {
+            if signed_checkpoint.checkpoint == checkpoint {
+                return Ok(signed_checkpoint);
+            }
+            error!(
+                index = checkpoint.index,
+                signed_root = ?signed_checkpoint.checkpoint.root,
+                attempted_root = ?checkpoint.root,
+                "DOUBLE SIGNING PREVENTED: attempted to sign a conflicting checkpoint. Refusing to sign and shutting down!"
+            );
+            return Err(SigningGuardError::ConflictingCheckpoint {
+                index: checkpoint.index,
+                signed_root: signed_checkpoint.checkpoint.root,
+                attempted_root: checkpoint.root,
+            });
+        }
+
+        let signed_checkpoint = checkpoint.sign_with(self.signer.as_ref()).await?;
+        self.store_signed_checkpoint(&signed_checkpoint)?;
+        Ok(signed_checkpoint)
+    }
+
+    /// Retrieve the signed checkpoint at an index from the signing history
+    pub(crate) fn retrieve_signed_checkpoint(
+        &self,
+        index: u32,
+    ) -> Result<Option<SignedCheckpoint>, DbError> {
+        self.db.retrieve_keyed_decodable(SIGNED_CHECKPOINT, &index)
+    }
+
+    /// Retrieve the highest index in the signing history
+    pub(crate) fn retrieve_latest_signed_index(&self) -> Result<Option<u32>, DbError> {
+        self.db.retrieve_decodable("", LATEST_SIGNED_INDEX)
+    }
+
+    fn store_signed_checkpoint(&self, signed_checkpoint: &SignedCheckpoint) -> Result<(), DbError> {
+        let index = signed_checkpoint.checkpoint.index;
+        self.db
+            .store_keyed_encodable(SIGNED_CHECKPOINT, &index, signed_checkpoint)?;
+        match self.retrieve_latest_signed_index()? {
+            Some(latest) if latest >= index => Ok(()),
+            _ => self.db.store_encodable("", LATEST_SIGNED_INDEX, &index),
+        }
+    }
+
+    /// Write the full signing history to a JSON file at `path`
+    pub(crate) fn export_history(&self, path: impl AsRef<Path>) -> Result<(), SigningGuardError> {
+        let mut history = vec![];
+        if let Some(latest) = self.retrieve_latest_signed_index()? {
+            for index in 0..=latest {
+                if let Some(signed_checkpoint) = self.retrieve_signed_checkpoint(index)? {
+                    history.push(signed_checkpoint);
+                }
+            }
+        }
+        std::fs::write(path.as_ref(), serde_json::to_string_pretty(&history)?)?;
+        info!(path = ?path.as_ref(), count = history.len(), "Exported signing history");
+        Ok(())
+    }
+
+    /// Merge the signing history in the JSON file at `path` into the local
+    /// history. Fails without importing anything if the file contains
+    /// checkpoints signed by another key, for another outbox, or conflicting
+    /// with each other or the local history.
+    pub(crate) fn import_history(&self, path: impl AsRef<Path>) -> Result<(), SigningGuardError> {
+        let history: Vec<SignedCheckpoint> =
+            serde_json::from_slice(&std::fs::read(path.as_ref())?)?;
+        let mut imported: HashMap<u32, Checkpoint> = HashMap::new();
+        for signed_checkpoint in &history {
+            let checkpoint = signed_checkpoint.checkpoint;
+            if signed_checkpoint.recover()? != self.signer.address() {
+                return Err(SigningGuardError::ForeignSignature {
+                    index: checkpoint.index,
+                });
+            }
+            if checkpoint.outbox_domain != self.outbox_domain {
+                return Err(SigningGuardError::ForeignDomain {
+                    index: checkpoint.index,
+                    outbox_domain: checkpoint.outbox_domain,
+                    expected_domain: self.outbox_domain,
+                });
+            }
+            let existing = match imported.get(&checkpoint.index) {
+                Some(existing) => Some(*existing),
+                None => self
+                    .retrieve_signed_checkpoint(checkpoint.index)?
+                    .map(|existing| existing.checkpoint),
+            };
+            if let Some(existing) = existing {
+                if existing != checkpoint {
+                    return Err(SigningGuardError::ConflictingCheckpoint {
+                        index: checkpoint.index,
+                        signed_root: existing.root,
+                        attempted_root: checkpoint.root,
+                    });
+                }
+            }
+            imported.insert(checkpoint.index, checkpoint);
+        }
+        for signed_checkpoint in &history {
+            self.store_signed_checkpoint(signed_checkpoint)?;
+        }
+        info!(path = ?path.as_ref(), count = history.len(), "Imported signing history");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::signers::LocalWallet;
+
+    use abacus_test::test_utils;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_conflicting_checkpoints() {
+        test_utils::run_test_db(|db| async move {
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+            let guard = CheckpointSigningGuard::new("outbox_1", 1000, Arc::new(signer.into()), db);
+            let checkpoint = Checkpoint {
+                outbox_domain: 1000,
+                root: H256::repeat_byte(1),
+                index: 5,
+            };
+
+            let signed = guard.sign(checkpoint).await.expect("!sign");
+            // Re-signing the same checkpoint returns the stored signature
+            assert_eq!(guard.sign(checkpoint).await.expect("!sign"), signed);
+            assert_eq!(guard.retrieve_latest_signed_index().unwrap(), Some(5));
+
+            let conflicting = Checkpoint {
+                root: H256::repeat_byte(2),
+                ..checkpoint
+            };
+            assert!(matches!(
+                guard.sign(conflicting).await,
+                Err(SigningGuardError::ConflictingCheckpoint { index: 5, .. })
+            ));
+            assert_eq!(guard.retrieve_signed_checkpoint(5).unwrap(), Some(signed));
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn refuses_to_import_foreign_or_conflicting_history() {
+        test_utils::run_test_db(|db| async move {
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+            let guard =
+                CheckpointSigningGuard::new("outbox_1", 1000, Arc::new(signer.clone().into()), db);
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("history.json");
+            let sign = |outbox_domain: u32, root: u8, index: u32| {
+                let signer = signer.clone();
+                async move {
+                    Checkpoint {
+                        outbox_domain,
+                        root: H256::repeat_byte(root),
+                        index,
+                    }
+                    .sign_with(&signer)
+                    .await
+                    .unwrap()
+                }
+            };
+            let write_history = |history: &[SignedCheckpoint]| {
+                std::fs::write(&path, serde_json::to_string(history).unwrap()).unwrap()
+            };
+
+            // Checkpoints for another outbox are rejected
+            write_history(&[sign(1000, 1, 1).await, sign(2000, 1, 2).await]);
+            assert!(matches!(
+                guard.import_history(&path),
+                Err(SigningGuardError::ForeignDomain {
+                    index: 2,
+                    outbox_domain: 2000,
+                    expected_domain: 1000,
+                })
+            ));
+            assert_eq!(guard.retrieve_latest_signed_index().unwrap(), None);
+
+            // As are different checkpoints at the same index
+            write_history(&[
+                sign(1000, 1, 1).await,
+                sign(1000, 1, 2).await,
+                sign(1000, 2, 2).await,
+            ]);
+            assert!(matches!(
+                guard.import_history(&path),
+                Err(SigningGuardError::ConflictingCheckpoint { index: 2, .. })
+            ));
+            assert_eq!(guard.retrieve_latest_signed_index().unwrap(), None);
+
+            // Checkpoints repeated in the history are imported once
+            write_history(&[sign(1000, 1, 1).await, sign(1000, 1, 1).await]);
+            guard.import_history(&path).unwrap();
+            assert_eq!(guard.retrieve_latest_signed_index().unwrap(), Some(1));
+        })
+        .await
+    }
+}
@@ -1,79 +1,114 @@
 use std::sync::Arc;
 use std::time::Duration;
 
-use eyre::Result;
+use eyre::{bail, Result};
 use prometheus::IntGauge;
-use tokio::time::MissedTickBehavior;
 use tokio::{task::JoinHandle, time::sleep};
-use tracing::warn;
+use tracing::error;
 use tracing::{info, info_span, instrument::Instrumented, Instrument};
 
-use abacus_base::{CachingOutbox, CheckpointSyncer, CheckpointSyncers, CoreMetrics};
-use abacus_core::{Outbox, Signers};
+use abacus_base::{CachingOutbox, CheckpointSyncer, CheckpointSyncers, CoreMetrics, OutboxHealth};
+use abacus_core::{
+    accumulator::incremental::IncrementalMerkle, AbacusCommon, Announcement, Checkpoint, Outbox,
+};
+use ethers::signers::Signer;
+
+use crate::signing_guard::CheckpointSigningGuard;
 
 pub(crate) struct ValidatorSubmitter {
     interval: u64,
     reorg_period: u64,
-    signer: Arc<Signers>,
+    signing_guard: Arc<CheckpointSigningGuard>,
     outbox: Arc<CachingOutbox>,
     checkpoint_syncer: Arc<CheckpointSyncers>,
+    storage_location: String,
+    announcement_syncer: Option<Arc<CheckpointSyncers>>,
+    sign_every_index: bool,
+    outbox_health: OutboxHealth,
     metrics: ValidatorSubmitterMetrics,
 }
 
 impl ValidatorSubmitter {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         interval: u64,
         reorg_period: u64,
         outbox: Arc<CachingOutbox>,
-        signer: Arc<Signers>,
+        signing_guard: Arc<CheckpointSigningGuard>,
         checkpoint_syncer: Arc<CheckpointSyncers>,
+        storage_location: String,
+        announcement_syncer: Option<Arc<CheckpointSyncers>>,
+        sign_every_index: bool,
+        outbox_health: OutboxHealth,
         metrics: ValidatorSubmitterMetrics,
     ) -> Self {
         Self {
             reorg_period,
             interval,
             outbox,
-            signer,
+            signing_guard,
             checkpoint_syncer,
+            storage_location,
+            announcement_syncer,
+            sign_every_index,
+            outbox_health,
             metrics,
         }
     }
 
     pub(crate) fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
         let span = info_span!("ValidatorSubmitter");
-        let metrics_loop = tokio::spawn(Self::metrics_loop(
-            self.metrics.outbox_state.clone(),
-            self.outbox.clone(),
-        ));
-        tokio::spawn(async move {
-            let res = self.main_task().await;
-            metrics_loop.abort();
-            res
-        })
-        .instrument(span)
+        tokio::spawn(self.main_task()).instrument(span)
     }
 
-    /// Spawn a task to update the outbox state gauge.
-    async fn metrics_loop(outbox_state_gauge: IntGauge, outbox: Arc<CachingOutbox>) {
-        let mut interval = tokio::time::interval(Duration::from_secs(60));
-        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
-        loop {
-            let state = outbox.state().await;
-            match &state {
-                Ok(state) => outbox_state_gauge.set(*state as u8 as i64),
-                Err(e) => warn!(error = %e, "Failed to get outbox state"),
-            };
-
-            interval.tick().await;
+    /// Sign an announcement of where this validator's checkpoints are stored
+    /// and publish it to the checkpoint syncer and, if configured, the
+    /// announcement syncer.
+    async fn announce(&self) -> Result<()> {
+        let announcement = Announcement {
+            validator: self.signing_guard.signer().address(),
+            outbox_domain: self.outbox.local_domain(),
+            storage_location: self.storage_location.clone(),
+        };
+        let signed_announcement = announcement.sign_with(self.signing_guard.signer()).await?;
+        self.checkpoint_syncer
+            .write_announcement(&signed_announcement)
+            .await?;
+        if let Some(announcement_syncer) = &self.announcement_syncer {
+            announcement_syncer
+                .write_announcement(&signed_announcement)
+                .await?;
         }
+        info!(announcement = %signed_announcement.announcement, "Announced checkpoint storage location");
+        Ok(())
     }
 
     async fn main_task(self) -> Result<()> {
+        // Never sign before knowing whether the outbox has failed
+        let mut outbox_health = self.outbox_health.clone();
+        outbox_health.wait_for_state().await;
+
+        tokio::select! {
+            biased;
+            _ = outbox_health.wait_for_failure() => {
+                outbox_health.report_failure("halting checkpoint signing");
+                // Keep running without signing, rather than exiting and
+                // signing again after a restart until the state is polled
+                std::future::pending().await
+            }
+            res = self.sign_checkpoints() => res,
+        }
+    }
+
+    async fn sign_checkpoints(&self) -> Result<()> {
         let reorg_period = if self.reorg_period == 0 {
             None
         } else {
             Some(self.reorg_period)
         };
+
+        self.announce().await?;
+
         // Ensure that the outbox has > 0 messages before we enter the main
         // validator submit loop. This is to avoid an underflow / reverted
         // call when we invoke the `outbox.latest_checkpoint()` method,
@@ -86,6 +121,16 @@ impl ValidatorSubmitter {
             sleep(Duration::from_secs(self.interval)).await;
         }
 
+        if self.sign_every_index {
+            self.sign_every_checkpoint(reorg_period).await
+        } else {
+            self.sign_latest_checkpoint(reorg_period).await
+        }
+    }
+
+    /// Signs the latest checkpoint of the outbox at each interval.
+    async fn sign_latest_checkpoint(&self, reorg_period: Option<u64>) -> Result<()> {
+        let mut tree = IncrementalMerkle::default();
         let mut current_index = self
             .checkpoint_syncer
             .latest_index()
@@ -106,40 +151,235 @@ impl ValidatorSubmitter {
                 .set(latest_checkpoint.index as i64);
 
             if current_index < latest_checkpoint.index {
-                let signed_checkpoint = latest_checkpoint.sign_with(self.signer.as_ref()).await?;
-
-                info!(signature = ?signed_checkpoint, signer=?self.signer, "Sign latest checkpoint");
+                self.ingest_to_checkpoint(&mut tree, &latest_checkpoint)
+                    .await?;
+                self.sign_and_write_checkpoint(latest_checkpoint).await?;
                 current_index = latest_checkpoint.index;
+            }
+
+            sleep(Duration::from_secs(self.interval)).await;
+        }
+    }
+
+    /// Signs a checkpoint for every index between the last signed index and
+    /// the latest checkpoint of the outbox. Roots are computed from a merkle
+    /// tree built out of the messages indexed into the outbox's db.
+    async fn sign_every_checkpoint(&self, reorg_period: Option<u64>) -> Result<()> {
+        let mut tree = IncrementalMerkle::default();
+        let mut next_index = 0;
 
-                self.checkpoint_syncer
-                    .write_checkpoint(signed_checkpoint.clone())
+        // Skip past the last signed index. The tree is still built from the
+        // first leaf, so the next latest checkpoint is verified in full.
+        if let Some(latest_signed_index) = self.checkpoint_syncer.latest_index().await? {
+            next_index = latest_signed_index + 1;
+            self.metrics
+                .latest_checkpoint_processed
+                .set(latest_signed_index as i64);
+        }
+
+        info!(next_index = next_index, "Starting Validator");
+        loop {
+            // Check the latest checkpoint
+            let latest_checkpoint = self.outbox.latest_checkpoint(reorg_period).await?;
+
+            self.metrics
+                .latest_checkpoint_observed
+                .set(latest_checkpoint.index as i64);
+
+            // Sign every index up to and including the latest checkpoint
+            if next_index <= latest_checkpoint.index {
+                let checkpoints = self
+                    .ingest_to_checkpoint(&mut tree, &latest_checkpoint)
                     .await?;
-                self.metrics
-                    .latest_checkpoint_processed
-                    .set(signed_checkpoint.checkpoint.index as i64);
+                for checkpoint in checkpoints.into_iter().filter(|c| c.index >= next_index) {
+                    self.sign_and_write_checkpoint(checkpoint).await?;
+                }
+                next_index = latest_checkpoint.index + 1;
             }
 
             sleep(Duration::from_secs(self.interval)).await;
         }
     }
+
+    /// Ingests the leaves indexed from finalized outbox blocks into `tree` up
+    /// to and including `checkpoint.index`, and returns the checkpoint at each
+    /// newly ingested index.
+    ///
+    /// Fails if the resulting root does not match `checkpoint.root`, which
+    /// means the outbox reported a root that is not backed by the dispatched
+    /// messages, e.g. because the RPC provider is compromised.
+    async fn ingest_to_checkpoint(
+        &self,
+        tree: &mut IncrementalMerkle,
+        checkpoint: &Checkpoint,
+    ) -> Result<Vec<Checkpoint>> {
+        let db = self.outbox.db();
+        let mut checkpoints = vec![];
+        while tree.count() as u32 <= checkpoint.index {
+            let index = tree.count() as u32;
+            tree.ingest(db.wait_for_finalized_leaf(index).await?);
+            checkpoints.push(Checkpoint {
+                outbox_domain: checkpoint.outbox_domain,
+                root: tree.root(),
+                index,
+            });
+        }
+
+        let local_root = tree.root();
+        if local_root != checkpoint.root {
+            self.metrics.checkpoint_root_mismatch.set(1);
+            error!(
+                index = checkpoint.index,
+                outbox_root = ?checkpoint.root,
+                local_root = ?local_root,
+                "Outbox checkpoint root does not match the locally built merkle tree. The RPC provider may be compromised. Refusing to sign and shutting down!"
+            );
+            bail!(
+                "Outbox checkpoint root {:?} does not match local root {:?} at index {}",
+                checkpoint.root,
+                local_root,
+                checkpoint.index
+            );
+        }
+        Ok(checkpoints)
+    }
+
+    async fn sign_and_write_checkpoint(&self, checkpoint: Checkpoint) -> Result<()> {
+        // Refuses to sign a root conflicting with one previously signed at this index
+        let signed_checkpoint = self.signing_guard.sign(checkpoint).await?;
+
+        info!(signature = ?signed_checkpoint, signer=?self.signing_guard.signer(), "Sign checkpoint");
+
+        self.checkpoint_syncer
+            .write_checkpoint(signed_checkpoint.clone())
+            .await?;
+        self.metrics
+            .latest_checkpoint_processed
+            .set(signed_checkpoint.checkpoint.index as i64);
+        Ok(())
+    }
 }
 
 pub(crate) struct ValidatorSubmitterMetrics {
-    outbox_state: IntGauge,
     latest_checkpoint_observed: IntGauge,
     latest_checkpoint_processed: IntGauge,
+    checkpoint_root_mismatch: IntGauge,
 }
 
 impl ValidatorSubmitterMetrics {
     pub fn new(metrics: &CoreMetrics, outbox_chain: &str) -> Self {
         Self {
-            outbox_state: metrics.outbox_state().with_label_values(&[outbox_chain]),
             latest_checkpoint_observed: metrics
                 .latest_checkpoint()
                 .with_label_values(&["validator_observed", outbox_chain]),
             latest_checkpoint_processed: metrics
                 .latest_checkpoint()
                 .with_label_values(&["validator_processed", outbox_chain]),
+            checkpoint_root_mismatch: metrics
+                .new_int_gauge(
+                    "checkpoint_root_mismatch",
+                    "Set to 1 if an outbox checkpoint root did not match the validator's merkle tree",
+                    &["chain"],
+                )
+                .expect("failed to register checkpoint_root_mismatch metric")
+                .with_label_values(&[outbox_chain]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::core::types::H256;
+    use ethers::signers::LocalWallet;
+
+    use abacus_base::{LocalStorage, OutboxHealthMonitor};
+    use abacus_core::{db::AbacusDB, AbacusMessage, Encode, RawCommittedMessage};
+    use abacus_test::mocks::{indexer::MockAbacusIndexer, MockOutboxContract};
+    use abacus_test::test_utils;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_to_sign_roots_not_backed_by_indexed_messages() {
+        for sign_every_index in [false, true] {
+            test_utils::run_test_db(|db| async move {
+                let dir = tempfile::TempDir::new().unwrap();
+                let abacus_db = AbacusDB::new("outbox_1", db.clone());
+                for leaf_index in 0..2 {
+                    let message = AbacusMessage {
+                        body: vec![leaf_index as u8],
+                        ..Default::default()
+                    };
+                    abacus_db
+                        .store_latest_message(&RawCommittedMessage {
+                            leaf_index,
+                            message: message.to_vec(),
+                        })
+                        .unwrap();
+                }
+
+                let mut mock_outbox = MockOutboxContract::new();
+                mock_outbox
+                    .expect__chain_name()
+                    .return_const("outbox_1".to_owned());
+                mock_outbox.expect__local_domain().return_const(1000u32);
+                mock_outbox.expect__count().returning(|| Ok(2));
+                // The outbox reports a root that doesn't match the messages
+                mock_outbox.expect__latest_checkpoint().returning(|_| {
+                    Ok(Checkpoint {
+                        outbox_domain: 1000,
+                        root: H256::repeat_byte(1),
+                        index: 1,
+                    })
+                });
+                let outbox = Arc::new(CachingOutbox::new(
+                    mock_outbox.into(),
+                    abacus_db,
+                    Arc::new(MockAbacusIndexer::new().into()),
+                ));
+
+                let signer: LocalWallet =
+                    "1111111111111111111111111111111111111111111111111111111111111111"
+                        .parse()
+                        .unwrap();
+                let signing_guard = Arc::new(CheckpointSigningGuard::new(
+                    "outbox_1",
+                    1000,
+                    Arc::new(signer.into()),
+                    db,
+                ));
+                let storage_location = format!("file://{}", dir.path().display());
+                let checkpoint_syncer = Arc::new(CheckpointSyncers::Local(LocalStorage::new(
+                    dir.path().to_str().unwrap(),
+                )));
+                let metrics = CoreMetrics::new("validator_test", None, prometheus::Registry::new())
+                    .expect("could not make metrics");
+                let outbox_health = OutboxHealthMonitor::new(outbox.outbox(), &metrics).subscribe();
+                let submitter = ValidatorSubmitter::new(
+                    1,
+                    0,
+                    outbox,
+                    signing_guard.clone(),
+                    checkpoint_syncer.clone(),
+                    storage_location,
+                    None,
+                    sign_every_index,
+                    outbox_health,
+                    ValidatorSubmitterMetrics::new(&metrics, "outbox_1"),
+                );
+
+                assert!(submitter.sign_checkpoints().await.is_err());
+                assert_eq!(signing_guard.retrieve_latest_signed_index().unwrap(), None);
+                assert_eq!(checkpoint_syncer.latest_index().await.unwrap(), None);
+                for index in 0..2 {
+                    assert!(checkpoint_syncer
+                        .fetch_checkpoint(index)
+                        .await
+                        .unwrap()
+                        .is_none());
+                }
+            })
+            .await
         }
     }
 }
@@ -1,23 +1,32 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use tokio::task::JoinHandle;
 use tracing::instrument::Instrumented;
 
-use abacus_base::{AbacusAgentCore, Agent, CheckpointSyncers};
-use abacus_core::{AbacusContract, Signers};
+use abacus_base::{
+    AbacusAgentCore, Agent, CheckpointSyncers, ContractSyncMetrics, OutboxHealthMonitor,
+};
+use abacus_core::{AbacusCommon, AbacusContract, Signers};
 use eyre::Result;
 
+use crate::checkpointer::{CachePolicy, Checkpointer};
+use crate::signing_guard::CheckpointSigningGuard;
 use crate::submit::ValidatorSubmitterMetrics;
 use crate::{settings::ValidatorSettings as Settings, submit::ValidatorSubmitter};
 
 /// A validator agent
 #[derive(Debug)]
 pub struct Validator {
-    signer: Arc<Signers>,
+    signing_guard: Arc<CheckpointSigningGuard>,
     reorg_period: u64,
     interval: u64,
     checkpoint_syncer: Arc<CheckpointSyncers>,
+    storage_location: String,
+    announcement_syncer: Option<Arc<CheckpointSyncers>>,
+    sign_every_index: bool,
+    cache_policy: Option<CachePolicy>,
     pub(crate) core: AbacusAgentCore,
 }
 
@@ -29,21 +38,46 @@ impl AsRef<AbacusAgentCore> for Validator {
 
 impl Validator {
     /// Instantiate a new validator
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         signer: Signers,
         reorg_period: u64,
         interval: u64,
         checkpoint_syncer: CheckpointSyncers,
+        storage_location: String,
+        announcement_syncer: Option<CheckpointSyncers>,
+        sign_every_index: bool,
+        cache_policy: Option<CachePolicy>,
         core: AbacusAgentCore,
     ) -> Self {
+        let signing_guard = CheckpointSigningGuard::new(
+            core.outbox.chain_name(),
+            core.outbox.local_domain(),
+            Arc::new(signer),
+            core.db.clone(),
+        );
         Self {
-            signer: Arc::new(signer),
+            signing_guard: Arc::new(signing_guard),
             reorg_period,
             interval,
             checkpoint_syncer: Arc::new(checkpoint_syncer),
+            storage_location,
+            announcement_syncer: announcement_syncer.map(Arc::new),
+            sign_every_index,
+            cache_policy,
             core,
         }
     }
+
+    /// Import the signing history from the file at `path`
+    pub fn import_signing_history(&self, path: &str) -> Result<()> {
+        Ok(self.signing_guard.import_history(path)?)
+    }
+
+    /// Export the signing history to the file at `path`
+    pub fn export_signing_history(&self, path: &str) -> Result<()> {
+        Ok(self.signing_guard.export_history(path)?)
+    }
 }
 
 #[async_trait]
@@ -60,32 +94,93 @@ impl Agent for Validator {
         let reorg_period = settings.reorgperiod.parse().expect("invalid uint");
         let interval = settings.interval.parse().expect("invalid uint");
         let checkpoint_syncer = settings.checkpointsyncer.try_into_checkpoint_syncer()?;
+        let storage_location = settings.checkpointsyncer.storage_location();
+        let announcement_syncer = settings
+            .announcementsyncer
+            .as_ref()
+            .map(|conf| conf.try_into_checkpoint_syncer())
+            .transpose()?;
+        let sign_every_index = settings
+            .signeveryindex
+            .as_deref()
+            .map(|s| s.parse().expect("invalid bool"))
+            .unwrap_or(false);
+        let cache_policy = CachePolicy {
+            period: settings
+                .cacheperiod
+                .as_deref()
+                .map(|s| Duration::from_secs(s.parse().expect("invalid uint"))),
+            messages: settings
+                .cachemessages
+                .as_deref()
+                .map(|s| s.parse().expect("invalid uint")),
+        };
+        // Checkpoints are only cached if a policy is configured
+        let cache_policy = if cache_policy.period.is_some() || cache_policy.messages.is_some() {
+            Some(cache_policy)
+        } else {
+            None
+        };
         let core = settings
             .as_ref()
             .try_into_abacus_core(Self::AGENT_NAME, false)
             .await?;
-        Ok(Self::new(
+        let validator = Self::new(
             signer,
             reorg_period,
             interval,
             checkpoint_syncer,
+            storage_location,
+            announcement_syncer,
+            sign_every_index,
+            cache_policy,
             core,
-        ))
+        );
+        if let Some(path) = &settings.signinghistoryimport {
+            validator.import_signing_history(path)?;
+        }
+        Ok(validator)
     }
 }
 
 impl Validator {
+    /// Index the outbox and sign its checkpoints
     pub fn run(&self) -> Instrumented<JoinHandle<Result<()>>> {
+        let outbox_health_monitor =
+            OutboxHealthMonitor::new(self.outbox().outbox(), &self.core.metrics);
         let submit = ValidatorSubmitter::new(
             self.interval,
             self.reorg_period,
             self.outbox(),
-            self.signer.clone(),
+            self.signing_guard.clone(),
             self.checkpoint_syncer.clone(),
+            self.storage_location.clone(),
+            self.announcement_syncer.clone(),
+            self.sign_every_index,
+            outbox_health_monitor.subscribe(),
             ValidatorSubmitterMetrics::new(&self.core.metrics, self.outbox().chain_name()),
         );
 
-        self.run_all(vec![submit.spawn()])
+        // The outbox's messages are indexed to build the merkle tree that
+        // checkpoints are verified against
+        let sync_metrics = ContractSyncMetrics::new(self.metrics());
+        let sync = self
+            .outbox()
+            .sync(self.as_ref().indexer.clone(), sync_metrics);
+
+        let mut tasks = vec![submit.spawn(), sync];
+        if let Some(cache_policy) = self.cache_policy {
+            let checkpointer = Checkpointer::new(
+                self.interval,
+                self.outbox(),
+                cache_policy,
+                outbox_health_monitor.subscribe(),
+            );
+            tasks.push(checkpointer.spawn());
+        }
+        tasks.push(outbox_health_monitor.spawn());
+
+        self.run_all(tasks)
     }
 }
 
@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ethers::types::{Address, H256};
+use eyre::Result;
+use prometheus::{IntCounter, IntCounterVec};
+use tokio::{task::JoinHandle, time::sleep};
+use tracing::{
+    debug, error, info, info_span, instrument, instrument::Instrumented, warn, Instrument,
+};
+
+use abacus_base::{CheckpointSyncer, CheckpointSyncers, CoreMetrics};
+use abacus_core::{accumulator::incremental::IncrementalMerkle, db::AbacusDB, SignedCheckpoint};
+
+use crate::evidence::{Evidence, WatcherDB};
+use crate::notifier::{Notifier, Notifiers};
+
+/// The number of latest observed checkpoints of each validator refetched
+/// every interval by default
+pub(crate) const DEFAULT_REAUDIT_WINDOW: u32 = 100;
+
+/// Audits the signed checkpoints of validators against the outbox's merkle
+/// tree, which is built from the messages indexed into the outbox's db.
+pub(crate) struct CheckpointAuditor {
+    interval: u64,
+    /// The number of latest observed checkpoints of each validator to
+    /// refetch every interval, to find those overwritten with another root
+    reaudit_window: u32,
+    outbox_domain: u32,
+    /// The number of validators whose signatures make a checkpoint valid
+    threshold: usize,
+    outbox_db: AbacusDB,
+    db: WatcherDB,
+    checkpoint_syncers: HashMap<Address, CheckpointSyncers>,
+    notifier: Option<Arc<Notifiers>>,
+    tree: IncrementalMerkle,
+    /// The root of the tree after each leaf was ingested
+    roots: Vec<H256>,
+    metrics: CheckpointAuditorMetrics,
+}
+
+impl CheckpointAuditor {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        interval: u64,
+        reaudit_window: u32,
+        outbox_domain: u32,
+        threshold: usize,
+        outbox_db: AbacusDB,
+        db: WatcherDB,
+        checkpoint_syncers: HashMap<Address, CheckpointSyncers>,
+        notifier: Option<Arc<Notifiers>>,
+        metrics: CheckpointAuditorMetrics,
+    ) -> Self {
+        Self {
+            interval,
+            reaudit_window,
+            outbox_domain,
+            threshold,
+            outbox_db,
+            db,
+            checkpoint_syncers,
+            notifier,
+            tree: IncrementalMerkle::default(),
+            roots: vec![],
+            metrics,
+        }
+    }
+
+    pub(crate) fn spawn(self) -> Instrumented<JoinHandle<Result<()>>> {
+        let span = info_span!("CheckpointAuditor");
+        tokio::spawn(self.main_loop()).instrument(span)
+    }
+
+    async fn main_loop(mut self) -> Result<()> {
+        info!(
+            validators = ?self.checkpoint_syncers.keys().collect::<Vec<_>>(),
+            "Starting CheckpointAuditor"
+        );
+        loop {
+            self.update_tree()?;
+
+            let validators = self.checkpoint_syncers.keys().copied().collect::<Vec<_>>();
+            for validator in validators {
+                // Gracefully handle errors reading a validator's checkpoints
+                if let Err(e) = self.audit_validator(validator).await {
+                    warn!(validator = ?validator, error = %e, "Failed to audit validator");
+                }
+            }
+
+            sleep(Duration::from_secs(self.interval)).await;
+        }
+    }
+
+    /// Ingest all leaves indexed from finalized blocks so far into the tree
+    fn update_tree(&mut self) -> Result<()> {
+        while let Some(leaf) = self
+            .outbox_db
+            .finalized_leaf_by_leaf_index(self.roots.len() as u32)?
+        {
+            self.tree.ingest(leaf);
+            self.roots.push(self.tree.root());
+        }
+        Ok(())
+    }
+
+    /// Audit every checkpoint signed by the validator since the last audit
+    /// whose index has been indexed from the outbox. The latest previously
+    /// observed indices within the re-audit window are refetched too, as
+    /// validators overwrite their checkpoints when signing conflicting roots.
+    #[instrument(err, skip(self))]
+    async fn audit_validator(&self, validator: Address) -> Result<()> {
+        let checkpoint_syncer = &self.checkpoint_syncers[&validator];
+        let latest_index = match checkpoint_syncer.latest_index().await? {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        let mut next_index = self
+            .db
+            .retrieve_next_audit_index(validator)?
+            .unwrap_or_default();
+
+        let mut signed_checkpoints = vec![];
+        for index in next_index.saturating_sub(self.reaudit_window)..next_index {
+            if self
+                .db
+                .retrieve_observed_checkpoint(validator, index)?
+                .is_none()
+            {
+                continue;
+            }
+            if let Some(signed_checkpoint) = checkpoint_syncer.fetch_checkpoint(index).await? {
+                signed_checkpoints.push((index, signed_checkpoint));
+            }
+        }
+        while next_index <= latest_index && (next_index as usize) < self.roots.len() {
+            if let Some(signed_checkpoint) = checkpoint_syncer.fetch_checkpoint(next_index).await? {
+                signed_checkpoints.push((next_index, signed_checkpoint));
+            }
+            next_index += 1;
+        }
+
+        for (index, signed_checkpoint) in signed_checkpoints {
+            self.audit_checkpoint(validator, index, signed_checkpoint)
+                .await?;
+        }
+        self.db.store_next_audit_index(validator, next_index)?;
+        Ok(())
+    }
+
+    async fn audit_checkpoint(
+        &self,
+        validator: Address,
+        index: u32,
+        signed_checkpoint: SignedCheckpoint,
+    ) -> Result<()> {
+        let checkpoint = signed_checkpoint.checkpoint;
+        // Only checkpoints actually signed by the validator are evidence
+        if checkpoint.index != index
+            || checkpoint.outbox_domain != self.outbox_domain
+            || signed_checkpoint.recover()? != validator
+        {
+            debug!(validator = ?validator, index, checkpoint = ?checkpoint, "Ignoring checkpoint not signed by validator");
+            return Ok(());
+        }
+
+        match self.db.retrieve_observed_checkpoint(validator, index)? {
+            Some(first) if first.checkpoint.root != checkpoint.root => {
+                self.report(Evidence::ConflictingCheckpoints {
+                    validator,
+                    first,
+                    second: signed_checkpoint.clone(),
+                })
+                .await?;
+            }
+            Some(_) => {}
+            None => self
+                .db
+                .store_observed_checkpoint(validator, &signed_checkpoint)?,
+        }
+
+        // Checkpoints beyond the indexed leaves can't be audited yet
+        if let Some(expected_root) = self.roots.get(index as usize) {
+            if *expected_root != checkpoint.root {
+                let reported = self
+                    .report(Evidence::FraudulentCheckpoint {
+                        validator,
+                        signed_checkpoint,
+                        expected_root: *expected_root,
+                    })
+                    .await?;
+                if reported {
+                    self.check_fraudulent_quorum(index, checkpoint.root)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Report if enough validators have signed a fraudulent root at `index`
+    /// for it to be accepted by inboxes
+    fn check_fraudulent_quorum(&self, index: u32, root: H256) -> Result<()> {
+        let mut signers = vec![];
+        for validator in self.checkpoint_syncers.keys() {
+            if let Some(Evidence::FraudulentCheckpoint {
+                signed_checkpoint, ..
+            }) = self
+                .db
+                .retrieve_evidence("fraudulent_checkpoint", *validator, index)?
+            {
+                if signed_checkpoint.checkpoint.root == root {
+                    signers.push(*validator);
+                }
+            }
+        }
+        // Each new piece of evidence adds one signer, so this is only hit once
+        if signers.len() == self.threshold {
+            error!(
+                index,
+                root = ?root,
+                signers = ?signers,
+                threshold = self.threshold,
+                "A quorum of validators signed a fraudulent checkpoint! Inboxes may accept messages that were never dispatched."
+            );
+            self.metrics.fraudulent_quorums.inc();
+        }
+        Ok(())
+    }
+
+    /// Store the evidence and, unless it was already known, report it through
+    /// logs, metrics and the notifier. Returns whether it was newly reported.
+    async fn report(&self, evidence: Evidence) -> Result<bool> {
+        if !self.db.store_evidence(&evidence)? {
+            return Ok(false);
+        }
+        error!(evidence = ?evidence, "Found evidence of validator misbehavior!");
+        self.metrics
+            .evidence
+            .with_label_values(&[&format!("{:?}", evidence.validator()), evidence.kind()])
+            .inc();
+        if let Some(notifier) = &self.notifier {
+            // Evidence is already stored, so a failed notification is not fatal
+            if let Err(e) = notifier.notify(&evidence).await {
+                warn!(error = %e, "Failed to notify of evidence");
+            }
+        }
+        Ok(true)
+    }
+}
+
+pub(crate) struct CheckpointAuditorMetrics {
+    evidence: IntCounterVec,
+    fraudulent_quorums: IntCounter,
+}
+
+impl CheckpointAuditorMetrics {
+    pub fn new(metrics: &CoreMetrics) -> Self {
+        Self {
+            evidence: metrics
+                .new_int_counter(
+                    "watcher_evidence",
+                    "Number of pieces of evidence of validator misbehavior found",
+                    &["validator", "evidence_type"],
+                )
+                .expect("failed to register watcher_evidence metric"),
+            fraudulent_quorums: metrics
+                .new_int_counter(
+                    "watcher_fraudulent_quorums",
+                    "Number of fraudulent checkpoints signed by a quorum of validators",
+                    &[],
+                )
+                .expect("failed to register watcher_fraudulent_quorums metric")
+                .with_label_values(&[]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ethers::signers::{LocalWallet, Signer};
+    use tempfile::TempDir;
+
+    use abacus_base::LocalStorage;
+    use abacus_core::{AbacusMessage, Checkpoint, Encode, RawCommittedMessage};
+    use abacus_test::test_utils;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_fraudulent_and_conflicting_checkpoints() {
+        test_utils::run_test_db(|db| async move {
+            let outbox_db = AbacusDB::new("outbox_1", db.clone());
+            for leaf_index in 0..4 {
+                let message = AbacusMessage {
+                    origin: 1000,
+                    sender: H256::from_low_u64_be(4),
+                    destination: 2000,
+                    recipient: H256::from_low_u64_be(5),
+                    body: vec![leaf_index as u8],
+                };
+                outbox_db
+                    .store_raw_committed_message(&RawCommittedMessage {
+                        leaf_index,
+                        message: message.to_vec(),
+                    })
+                    .unwrap();
+            }
+
+            let signer: LocalWallet =
+                "1111111111111111111111111111111111111111111111111111111111111111"
+                    .parse()
+                    .unwrap();
+            let validator = signer.address();
+            let checkpoints_dir = TempDir::new().unwrap();
+            let checkpoint_syncer = CheckpointSyncers::Local(LocalStorage::new(
+                checkpoints_dir.path().to_str().unwrap(),
+            ));
+
+            let metrics = Arc::new(
+                CoreMetrics::new("watcher_test", None, prometheus::Registry::new())
+                    .expect("could not make metrics"),
+            );
+            let mut auditor = CheckpointAuditor::new(
+                1,
+                DEFAULT_REAUDIT_WINDOW,
+                1000,
+                1,
+                outbox_db,
+                WatcherDB::new("outbox_1", db),
+                HashMap::from([(validator, checkpoint_syncer.clone())]),
+                None,
+                CheckpointAuditorMetrics::new(&metrics),
+            );
+            auditor.update_tree().unwrap();
+            let evidence_count = |kind: &str| {
+                auditor
+                    .metrics
+                    .evidence
+                    .with_label_values(&[&format!("{:?}", validator), kind])
+                    .get()
+            };
+
+            // A checkpoint with a bogus root is fraudulent
+            let fraudulent = Checkpoint {
+                outbox_domain: 1000,
+                root: H256::repeat_byte(1),
+                index: 1,
+            };
+            checkpoint_syncer
+                .write_checkpoint(fraudulent.sign_with(&signer).await.unwrap())
+                .await
+                .unwrap();
+            auditor.audit_validator(validator).await.unwrap();
+            assert_eq!(evidence_count("fraudulent_checkpoint"), 1);
+            assert_eq!(evidence_count("conflicting_checkpoints"), 0);
+            // With a threshold of 1, the fraudulent root reached a quorum
+            assert_eq!(auditor.metrics.fraudulent_quorums.get(), 1);
+
+            // Overwriting it with the true root conflicts with the observed checkpoint
+            let honest = Checkpoint {
+                root: auditor.roots[1],
+                ..fraudulent
+            };
+            checkpoint_syncer
+                .write_checkpoint(honest.sign_with(&signer).await.unwrap())
+                .await
+                .unwrap();
+            auditor.audit_validator(validator).await.unwrap();
+            assert_eq!(evidence_count("fraudulent_checkpoint"), 1);
+            assert_eq!(evidence_count("conflicting_checkpoints"), 1);
+
+            // Conflicts are found at any observed index, not just the latest
+            for index in 2..4 {
+                let honest = Checkpoint {
+                    root: auditor.roots[index as usize],
+                    index,
+                    ..fraudulent
+                };
+                checkpoint_syncer
+                    .write_checkpoint(honest.sign_with(&signer).await.unwrap())
+                    .await
+                    .unwrap();
+            }
+            auditor.audit_validator(validator).await.unwrap();
+            assert_eq!(evidence_count("conflicting_checkpoints"), 1);
+            let overwritten = Checkpoint {
+                index: 2,
+                ..fraudulent
+            };
+            checkpoint_syncer
+                .write_checkpoint(overwritten.sign_with(&signer).await.unwrap())
+                .await
+                .unwrap();
+            assert_eq!(checkpoint_syncer.latest_index().await.unwrap(), Some(3));
+            auditor.audit_validator(validator).await.unwrap();
+            assert_eq!(evidence_count("fraudulent_checkpoint"), 2);
+            assert_eq!(evidence_count("conflicting_checkpoints"), 2);
+            assert_eq!(auditor.metrics.fraudulent_quorums.get(), 2);
+
+            // Only indices within the re-audit window are refetched
+            auditor.reaudit_window = 1;
+            let overwritten = Checkpoint {
+                root: H256::repeat_byte(2),
+                ..overwritten
+            };
+            checkpoint_syncer
+                .write_checkpoint(overwritten.sign_with(&signer).await.unwrap())
+                .await
+                .unwrap();
+            auditor.audit_validator(validator).await.unwrap();
+            assert_eq!(evidence_count("fraudulent_checkpoint"), 2);
+            assert_eq!(evidence_count("conflicting_checkpoints"), 2);
+        })
+        .await
+    }
+}
@@ -0,0 +1,183 @@
+use ethers::types::{Address, H256};
+use serde::{Deserialize, Serialize};
+
+use abacus_core::db::{DbError, TypedDB, DB};
+use abacus_core::{AbacusError, Decode, Encode, SignedCheckpoint};
+
+static OBSERVED_CHECKPOINT: &str = "observed_checkpoint_";
+static NEXT_AUDIT_INDEX: &str = "next_audit_index_";
+static EVIDENCE: &str = "evidence_";
+
+/// Evidence of a validator signing checkpoints it should not have
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Evidence {
+    /// A checkpoint whose root does not match the outbox's merkle tree at its
+    /// index
+    FraudulentCheckpoint {
+        /// The validator that signed the checkpoint
+        validator: Address,
+        /// The fraudulent signed checkpoint
+        signed_checkpoint: SignedCheckpoint,
+        /// The root of the outbox's merkle tree at the checkpoint's index
+        expected_root: H256,
+    },
+    /// Two checkpoints with different roots signed by a validator at the same
+    /// index
+    ConflictingCheckpoints {
+        /// The validator that signed both checkpoints
+        validator: Address,
+        /// The checkpoint that was observed first
+        first: SignedCheckpoint,
+        /// The conflicting checkpoint
+        second: SignedCheckpoint,
+    },
+}
+
+impl Evidence {
+    /// A short name for the kind of evidence, used in metrics and db keys
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Evidence::FraudulentCheckpoint { .. } => "fraudulent_checkpoint",
+            Evidence::ConflictingCheckpoints { .. } => "conflicting_checkpoints",
+        }
+    }
+
+    /// The misbehaving validator
+    pub fn validator(&self) -> Address {
+        match self {
+            Evidence::FraudulentCheckpoint { validator, .. } => *validator,
+            Evidence::ConflictingCheckpoints { validator, .. } => *validator,
+        }
+    }
+
+    /// The checkpoint index the evidence concerns
+    pub fn index(&self) -> u32 {
+        match self {
+            Evidence::FraudulentCheckpoint {
+                signed_checkpoint, ..
+            } => signed_checkpoint.checkpoint.index,
+            Evidence::ConflictingCheckpoints { first, .. } => first.checkpoint.index,
+        }
+    }
+}
+
+impl Encode for Evidence {
+    fn write_to<W>(&self, writer: &mut W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+    {
+        let serialized = serde_json::to_vec(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        writer.write_all(&serialized)?;
+        Ok(serialized.len())
+    }
+}
+
+impl Decode for Evidence {
+    fn read_from<R>(reader: &mut R) -> Result<Self, AbacusError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        serde_json::from_reader(reader)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
+    }
+}
+
+/// DB handle for the watcher's observations and collected evidence.
+///
+/// Key structure: ```<outbox_name>_watcher_<prefix>_<validator>[_<index>]```
+#[derive(Debug, Clone)]
+pub(crate) struct WatcherDB(TypedDB);
+
+impl WatcherDB {
+    pub(crate) fn new(outbox_name: &str, db: DB) -> Self {
+        Self(TypedDB::new(format!("{}_watcher", outbox_name), db))
+    }
+
+    fn validator_index_key(validator: Address, index: u32) -> Vec<u8> {
+        let mut key = validator.as_bytes().to_vec();
+        key.extend(index.to_be_bytes());
+        key
+    }
+
+    /// Store the first signed checkpoint observed for a validator at an index
+    pub(crate) fn store_observed_checkpoint(
+        &self,
+        validator: Address,
+        signed_checkpoint: &SignedCheckpoint,
+    ) -> Result<(), DbError> {
+        self.0.store_encodable(
+            OBSERVED_CHECKPOINT,
+            Self::validator_index_key(validator, signed_checkpoint.checkpoint.index),
+            signed_checkpoint,
+        )
+    }
+
+    /// Retrieve the signed checkpoint observed for a validator at an index
+    pub(crate) fn retrieve_observed_checkpoint(
+        &self,
+        validator: Address,
+        index: u32,
+    ) -> Result<Option<SignedCheckpoint>, DbError> {
+        self.0.retrieve_decodable(
+            OBSERVED_CHECKPOINT,
+            Self::validator_index_key(validator, index),
+        )
+    }
+
+    /// Store the next checkpoint index to audit for a validator
+    pub(crate) fn store_next_audit_index(
+        &self,
+        validator: Address,
+        index: u32,
+    ) -> Result<(), DbError> {
+        self.0
+            .store_encodable(NEXT_AUDIT_INDEX, validator.as_bytes(), &index)
+    }
+
+    /// Retrieve the next checkpoint index to audit for a validator
+    pub(crate) fn retrieve_next_audit_index(
+        &self,
+        validator: Address,
+    ) -> Result<Option<u32>, DbError> {
+        self.0
+            .retrieve_decodable(NEXT_AUDIT_INDEX, validator.as_bytes())
+    }
+
+    fn evidence_prefix(kind: &str) -> String {
+        let mut prefix = EVIDENCE.to_owned();
+        prefix.push_str(kind);
+        prefix
+    }
+
+    /// Store evidence of validator misbehavior. Returns false if the same
+    /// kind of evidence was already stored for the validator and index.
+    pub(crate) fn store_evidence(&self, evidence: &Evidence) -> Result<bool, DbError> {
+        let prefix = Self::evidence_prefix(evidence.kind());
+        let key = Self::validator_index_key(evidence.validator(), evidence.index());
+        if self
+            .0
+            .retrieve_decodable::<Evidence>(&prefix, &key)?
+            .is_some()
+        {
+            return Ok(false);
+        }
+        self.0.store_encodable(&prefix, &key, evidence)?;
+        Ok(true)
+    }
+
+    /// Retrieve the evidence of a kind stored for a validator at an index
+    pub(crate) fn retrieve_evidence(
+        &self,
+        kind: &str,
+        validator: Address,
+        index: u32,
+    ) -> Result<Option<Evidence>, DbError> {
+        self.0.retrieve_decodable(
+            &Self::evidence_prefix(kind),
+            Self::validator_index_key(validator, index),
+        )
+    }
+}
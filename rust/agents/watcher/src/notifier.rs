@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+use eyre::Result;
+
+use crate::evidence::Evidence;
+
+/// Notifier configuration
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum NotifierConf {
+    /// POST evidence as JSON to a webhook
+    Webhook {
+        /// The webhook URL
+        url: String,
+    },
+}
+
+impl NotifierConf {
+    /// Turn conf into a Notifier
+    pub fn build(&self) -> Notifiers {
+        match self {
+            NotifierConf::Webhook { url } => Notifiers::Webhook(WebhookNotifier::new(url)),
+        }
+    }
+}
+
+/// A hook to report evidence of validator misbehavior to
+#[async_trait]
+pub trait Notifier {
+    /// Report the evidence
+    async fn notify(&self, evidence: &Evidence) -> Result<()>;
+}
+
+/// Notifiers
+#[derive(Debug, Clone)]
+pub enum Notifiers {
+    /// A webhook notifier
+    Webhook(WebhookNotifier),
+}
+
+#[async_trait]
+impl Notifier for Notifiers {
+    async fn notify(&self, evidence: &Evidence) -> Result<()> {
+        match self {
+            Notifiers::Webhook(notifier) => notifier.notify(evidence).await,
+        }
+    }
+}
+
+/// Posts evidence as JSON to a webhook URL
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    /// Constructor
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, evidence: &Evidence) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(evidence)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,21 @@
+//! Configuration
+
+use abacus_base::decl_settings;
+
+use crate::notifier::NotifierConf;
+
+decl_settings!(Watcher {
+    /// The checkpoint syncers of the validators to watch. A quorum of
+    /// `threshold` validators signing the same fraudulent root is reported in
+    /// addition to each validator's evidence.
+    multisigcheckpointsyncer: abacus_base::MultisigCheckpointSyncerConf,
+    /// How frequently to audit the validators' checkpoints in seconds
+    interval: String,
+    /// Optional. The number of latest observed checkpoints of each validator
+    /// to refetch every interval, to find checkpoints overwritten with a
+    /// conflicting root. Defaults to 100.
+    reauditwindow: Option<String>,
+    /// This is optional. Where to report evidence of validator misbehavior
+    /// to, in addition to logs and metrics.
+    notifier: Option<NotifierConf>,
+});
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use eyre::Result;
+use tokio::task::JoinHandle;
+use tracing::instrument::Instrumented;
+
+use abacus_base::{AbacusAgentCore, Agent, ContractSyncMetrics, MultisigCheckpointSyncer};
+use abacus_core::{AbacusCommon, AbacusContract};
+
+use crate::auditor::{CheckpointAuditor, CheckpointAuditorMetrics, DEFAULT_REAUDIT_WINDOW};
+use crate::evidence::WatcherDB;
+use crate::notifier::Notifiers;
+use crate::settings::WatcherSettings as Settings;
+
+/// A watcher agent
+#[derive(Debug)]
+pub struct Watcher {
+    interval: u64,
+    reaudit_window: u32,
+    multisig_checkpoint_syncer: MultisigCheckpointSyncer,
+    notifier: Option<Arc<Notifiers>>,
+    pub(crate) core: AbacusAgentCore,
+}
+
+impl AsRef<AbacusAgentCore> for Watcher {
+    fn as_ref(&self) -> &AbacusAgentCore {
+        &self.core
+    }
+}
+
+#[async_trait]
+impl Agent for Watcher {
+    const AGENT_NAME: &'static str = "watcher";
+
+    type Settings = Settings;
+
+    async fn from_settings(settings: Self::Settings) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let core = settings
+            .as_ref()
+            .try_into_abacus_core(Self::AGENT_NAME, false)
+            .await?;
+        let multisig_checkpoint_syncer = settings
+            .multisigcheckpointsyncer
+            .try_into_multisig_checkpoint_syncer(core.outbox.local_domain())
+            .await?;
+        Ok(Self {
+            interval: settings.interval.parse().expect("invalid uint"),
+            reaudit_window: settings
+                .reauditwindow
+                .as_deref()
+                .map_or(DEFAULT_REAUDIT_WINDOW, |s| s.parse().expect("invalid uint")),
+            multisig_checkpoint_syncer,
+            notifier: settings
+                .notifier
+                .as_ref()
+                .map(|conf| Arc::new(conf.build())),
+            core,
+        })
+    }
+}
+
+impl Watcher {
+    pub fn run(&self) -> Instrumented<JoinHandle<Result<()>>> {
+        let outbox = self.outbox();
+        let auditor = CheckpointAuditor::new(
+            self.interval,
+            self.reaudit_window,
+            outbox.local_domain(),
+            self.multisig_checkpoint_syncer.threshold(),
+            outbox.db(),
+            WatcherDB::new(outbox.chain_name(), self.db()),
+            self.multisig_checkpoint_syncer.checkpoint_syncers().clone(),
+            self.notifier.clone(),
+            CheckpointAuditorMetrics::new(&self.core.metrics),
+        );
+
+        let sync_metrics = ContractSyncMetrics::new(self.metrics());
+        let sync = outbox.sync(self.as_ref().indexer.clone(), sync_metrics);
+
+        self.run_all(vec![auditor.spawn(), sync])
+    }
+}
@@ -0,0 +1,307 @@
+use std::{sync::Arc, time::Duration};
+
+use ethers::providers::{Middleware, Provider, PubsubClient};
+use futures::{future, StreamExt};
+use tokio::{
+    select,
+    sync::{watch, Notify},
+    time::{sleep, timeout},
+};
+use tracing::{debug, warn};
+
+/// How long to wait for a new head before giving up, so that callers fall
+/// back to polling if the subscription has stalled
+const NEW_HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+/// Backoff before the first attempt to resubscribe
+const RESUBSCRIBE_BASE_BACKOFF: Duration = Duration::from_millis(100);
+/// Maximum backoff between attempts to resubscribe
+const RESUBSCRIBE_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Tracks the latest block number of a chain with a `newHeads` subscription,
+/// so that indexers can react to new blocks instead of polling for them.
+///
+/// Nothing is subscribed to until a clone first waits for a block, and the
+/// subscription ends once every clone has been dropped.
+#[derive(Debug, Clone)]
+pub struct NewHeads {
+    latest: watch::Receiver<Option<u64>>,
+    start: Arc<Notify>,
+}
+
+impl NewHeads {
+    /// Track the new heads pushed by `client`
+    pub fn new<P>(client: P) -> Self
+    where
+        P: PubsubClient + 'static,
+    {
+        let (sender, latest) = watch::channel(None);
+        let start = Arc::new(Notify::new());
+        tokio::spawn(track_new_heads(
+            Provider::new(client),
+            sender,
+            start.clone(),
+        ));
+        Self { latest, start }
+    }
+
+    /// The number of the latest block seen, if any
+    pub fn latest(&self) -> Option<u64> {
+        *self.latest.borrow()
+    }
+
+    /// Wait until a block after `block` has been seen, or 10 seconds pass
+    pub async fn wait_for_block_after(&self, block: u64) {
+        self.start.notify_one();
+        let mut latest = self.latest.clone();
+        let _ = timeout(NEW_HEAD_TIMEOUT, async move {
+            loop {
+                let seen = *latest.borrow();
+                if seen.map_or(false, |number| number > block) {
+                    return;
+                }
+                if latest.changed().await.is_err() {
+                    // Nothing more will be seen, so wait out the timeout
+                    future::pending::<()>().await;
+                }
+            }
+        })
+        .await;
+    }
+}
+
+/// Publish the number of each new head to `sender`, resubscribing with
+/// backoff whenever the subscription fails or ends
+async fn track_new_heads<P>(
+    provider: Provider<P>,
+    sender: watch::Sender<Option<u64>>,
+    start: Arc<Notify>,
+) where
+    P: PubsubClient,
+{
+    select! {
+        _ = start.notified() => {}
+        _ = sender.closed() => return,
+    }
+
+    let mut backoff = RESUBSCRIBE_BASE_BACKOFF;
+    loop {
+        match provider.subscribe_blocks().await {
+            Ok(mut heads) => {
+                loop {
+                    let head = select! {
+                        head = heads.next() => head,
+                        _ = sender.closed() => return,
+                    };
+                    match head {
+                        Some(head) => {
+                            backoff = RESUBSCRIBE_BASE_BACKOFF;
+                            if let Some(number) = head.number {
+                                debug!(number = number.as_u64(), "Received new head");
+                                sender.send_replace(Some(number.as_u64()));
+                            }
+                        }
+                        None => break,
+                    }
+                }
+                warn!(?backoff, "New heads subscription ended, resubscribing");
+            }
+            Err(error) => {
+                warn!(
+                    ?error,
+                    ?backoff,
+                    "Failed to subscribe to new heads, retrying"
+                );
+            }
+        }
+
+        select! {
+            _ = sleep(backoff) => {}
+            _ = sender.closed() => return,
+        }
+        backoff = (backoff * 2).min(RESUBSCRIBE_MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fmt::Debug, sync::Mutex};
+
+    use abacus_core::{ContractLocator, Indexer};
+    use async_trait::async_trait;
+    use ethers::providers::{JsonRpcClient, ProviderError};
+    use ethers::types::{Address, Block, TxHash, U256};
+    use futures::channel::mpsc;
+    use serde::{de::DeserializeOwned, Serialize};
+    use serde_json::{json, Value};
+    use thiserror::Error;
+    use tokio::time::Instant;
+
+    use crate::EthereumOutboxIndexer;
+
+    use super::*;
+
+    #[derive(Debug, Error)]
+    #[error("Mock subscription failed")]
+    struct MockError;
+
+    impl From<MockError> for ProviderError {
+        fn from(src: MockError) -> Self {
+            ProviderError::JsonRpcClientError(Box::new(src))
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct MockState {
+        failing: bool,
+        subscribes: usize,
+        heads: Option<mpsc::UnboundedSender<Value>>,
+    }
+
+    /// A client whose `newHeads` subscriptions are driven by the test
+    #[derive(Debug, Clone, Default)]
+    struct MockHeads(Arc<Mutex<MockState>>);
+
+    impl MockHeads {
+        fn fail_subscriptions(&self, failing: bool) {
+            self.0.lock().unwrap().failing = failing;
+        }
+
+        fn subscribes(&self) -> usize {
+            self.0.lock().unwrap().subscribes
+        }
+
+        /// End the current subscription, as if the connection was lost
+        fn drop_subscription(&self) {
+            self.0.lock().unwrap().heads = None;
+        }
+
+        /// Wait for a subscription and push a head with `number` to it
+        async fn push_head(&self, number: u64) {
+            let head = serde_json::to_value(Block::<TxHash> {
+                number: Some(number.into()),
+                ..Default::default()
+            })
+            .unwrap();
+            loop {
+                if let Some(heads) = &self.0.lock().unwrap().heads {
+                    heads.unbounded_send(head).unwrap();
+                    return;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        }
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for MockHeads {
+        type Error = MockError;
+
+        async fn request<T, R>(&self, method: &str, _params: T) -> Result<R, Self::Error>
+        where
+            T: Debug + Serialize + Send + Sync,
+            R: DeserializeOwned,
+        {
+            let result = match method {
+                "eth_subscribe" => {
+                    let mut state = self.0.lock().unwrap();
+                    if state.failing {
+                        return Err(MockError);
+                    }
+                    state.subscribes += 1;
+                    json!(U256::from(state.subscribes))
+                }
+                "eth_unsubscribe" => json!(true),
+                _ => return Err(MockError),
+            };
+            serde_json::from_value(result).map_err(|_| MockError)
+        }
+    }
+
+    impl PubsubClient for MockHeads {
+        type NotificationStream = mpsc::UnboundedReceiver<Value>;
+
+        fn subscribe<T: Into<U256>>(&self, _id: T) -> Result<Self::NotificationStream, MockError> {
+            let (sender, heads) = mpsc::unbounded();
+            self.0.lock().unwrap().heads = Some(sender);
+            Ok(heads)
+        }
+
+        fn unsubscribe<T: Into<U256>>(&self, _id: T) -> Result<(), MockError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_tracks_new_heads_across_dropped_subscriptions() {
+        let client = MockHeads::default();
+        let new_heads = NewHeads::new(client.clone());
+
+        // Nothing is subscribed to until a block is waited for
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(client.subscribes(), 0);
+
+        let waiter = new_heads.clone();
+        let mut wait = tokio::spawn(async move { waiter.wait_for_block_after(5).await });
+        client.push_head(5).await;
+        assert!(timeout(Duration::from_millis(10), &mut wait).await.is_err());
+        assert_eq!(new_heads.latest(), Some(5));
+        client.push_head(6).await;
+        wait.await.unwrap();
+        assert_eq!(client.subscribes(), 1);
+
+        // A dropped subscription is replaced, retrying failed attempts
+        client.fail_subscriptions(true);
+        client.drop_subscription();
+        sleep(RESUBSCRIBE_BASE_BACKOFF * 4).await;
+        assert_eq!(client.subscribes(), 1);
+        client.fail_subscriptions(false);
+        let start = Instant::now();
+        client.push_head(7).await;
+        assert!(start.elapsed() <= RESUBSCRIBE_BASE_BACKOFF * 8);
+        assert_eq!(client.subscribes(), 2);
+        new_heads.wait_for_block_after(6).await;
+        assert_eq!(new_heads.latest(), Some(7));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_waits_for_finality_or_falls_back_to_polling() {
+        let client = MockHeads::default();
+        let locator = ContractLocator {
+            chain_name: "heads-test".into(),
+            domain: 1,
+            address: Address::zero().into(),
+        };
+        let indexer =
+            EthereumOutboxIndexer::new(Arc::new(Provider::new(client.clone())), &locator, 0, 0, 2)
+                .with_new_heads(NewHeads::new(client.clone()));
+
+        // Block 3 is finalized once block 6 has been seen
+        let start = Instant::now();
+        let pusher = client.clone();
+        let pushes = tokio::spawn(async move {
+            for number in 4..=6 {
+                sleep(Duration::from_secs(1)).await;
+                pusher.push_head(number).await;
+            }
+        });
+        indexer.wait_for_finalized_block_after(3).await;
+        assert!(start.elapsed() >= Duration::from_secs(3));
+        assert!(start.elapsed() < NEW_HEAD_TIMEOUT);
+        pushes.await.unwrap();
+
+        // Without new heads, the wait times out so that the caller polls
+        client.fail_subscriptions(true);
+        client.drop_subscription();
+        let start = Instant::now();
+        indexer.wait_for_finalized_block_after(4).await;
+        assert!(start.elapsed() >= NEW_HEAD_TIMEOUT);
+
+        // Indexers without a subscription poll every second
+        let indexer =
+            EthereumOutboxIndexer::new(Arc::new(Provider::new(client)), &locator, 0, 0, 2);
+        let start = Instant::now();
+        indexer.wait_for_finalized_block_after(4).await;
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        assert!(start.elapsed() < NEW_HEAD_TIMEOUT);
+    }
+}
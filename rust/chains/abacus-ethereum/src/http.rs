@@ -0,0 +1,189 @@
+use std::{
+    fmt::Debug,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, ProviderError};
+use reqwest::{header::RETRY_AFTER, Client, StatusCode, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+/// JSON-RPC error codes providers use to signal rate limiting
+const RATE_LIMIT_CODES: &[i64] = &[429, -32005];
+
+/// An error response to a JSON-RPC request
+#[derive(Debug, Clone, Deserialize, Error)]
+#[error("(code: {code}, message: {message}, data: {data:?})")]
+pub struct JsonRpcError {
+    /// The error code
+    pub code: i64,
+    /// The error message
+    pub message: String,
+    /// Additional data
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    fn is_rate_limit(&self) -> bool {
+        let message = self.message.to_lowercase();
+        RATE_LIMIT_CODES.contains(&self.code)
+            || message.contains("rate limit")
+            || message.contains("too many requests")
+    }
+
+    /// The backoff some providers (e.g. Infura) suggest in the error data
+    fn suggested_backoff(&self) -> Option<Duration> {
+        self.data
+            .as_ref()?
+            .get("rate")?
+            .get("backoff_seconds")?
+            .as_f64()
+            .map(Duration::from_secs_f64)
+    }
+}
+
+/// Error type for the HttpTransport
+#[derive(Error, Debug)]
+pub enum HttpTransportError {
+    /// The request could not be sent or the response could not be read
+    #[error(transparent)]
+    ReqwestError(#[from] reqwest::Error),
+    /// The node returned an error
+    #[error(transparent)]
+    JsonRpcError(JsonRpcError),
+    /// The response was not valid JSON-RPC
+    #[error("Deserialization Error: {err}. Response: {text}")]
+    SerdeJson {
+        /// The deserialization error
+        err: serde_json::Error,
+        /// The response that could not be deserialized
+        text: String,
+    },
+    /// The provider is rate limiting us
+    #[error("Rate limited: {message}")]
+    RateLimited {
+        /// How long the provider asked us to wait before retrying
+        retry_after: Option<Duration>,
+        /// The response
+        message: String,
+    },
+}
+
+impl From<HttpTransportError> for ProviderError {
+    fn from(src: HttpTransportError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(src))
+    }
+}
+
+#[derive(Serialize)]
+struct Request<'a, T> {
+    id: u64,
+    jsonrpc: &'a str,
+    method: &'a str,
+    params: T,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Response {
+    Error { error: JsonRpcError },
+    Success { result: Value },
+}
+
+/// A JSON-RPC client over HTTP. Unlike `ethers::providers::Http` it
+/// recognises rate limiting responses, including the `Retry-After` header, so
+/// they can be backed off from appropriately.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    id: Arc<AtomicU64>,
+    client: Client,
+    url: Url,
+}
+
+impl HttpTransport {
+    /// Create a transport sending requests to `url`
+    pub fn new(url: Url) -> Self {
+        Self::new_with_client(url, Client::new())
+    }
+
+    /// Create a transport sending requests to `url` with a preconfigured
+    /// client
+    pub fn new_with_client(url: Url, client: Client) -> Self {
+        Self {
+            id: Arc::new(AtomicU64::new(1)),
+            client,
+            url,
+        }
+    }
+}
+
+impl FromStr for HttpTransport {
+    type Err = url::ParseError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(Url::parse(src)?))
+    }
+}
+
+/// Parse a `Retry-After` header given in seconds. The HTTP-date form is not
+/// used by RPC providers and is ignored.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+#[async_trait]
+impl JsonRpcClient for HttpTransport {
+    type Error = HttpTransportError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let request = Request {
+            id: self.id.fetch_add(1, Ordering::SeqCst),
+            jsonrpc: "2.0",
+            method,
+            params,
+        };
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(&request)
+            .send()
+            .await?;
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+        let text = response.text().await?;
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(HttpTransportError::RateLimited {
+                retry_after,
+                message: text,
+            });
+        }
+        match serde_json::from_str::<Response>(&text) {
+            Ok(Response::Success { result }) => serde_json::from_value(result)
+                .map_err(|err| HttpTransportError::SerdeJson { err, text }),
+            Ok(Response::Error { error }) if error.is_rate_limit() => {
+                Err(HttpTransportError::RateLimited {
+                    retry_after: retry_after.or_else(|| error.suggested_backoff()),
+                    message: error.message,
+                })
+            }
+            Ok(Response::Error { error }) => Err(HttpTransportError::JsonRpcError(error)),
+            Err(err) => Err(HttpTransportError::SerdeJson { err, text }),
+        }
+    }
+}
@@ -3,19 +3,23 @@
 
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::time::Duration;
 use std::{error::Error as StdError, sync::Arc};
 
 use async_trait::async_trait;
 use ethers::prelude::*;
 use eyre::Result;
+use tokio::time::sleep;
+use tracing::instrument;
 
 use abacus_core::{
     AbacusAbi, AbacusCommon, AbacusContract, ChainCommunicationError, ContractLocator, Inbox,
-    MessageStatus, TxOutcome,
+    InboxIndexer, Indexer, MessageStatus, TxOutcome,
 };
 
 use crate::contracts::inbox::{Inbox as EthereumInboxInternal, INBOX_ABI};
 use crate::trait_builder::MakeableWithProvider;
+use crate::NewHeads;
 
 impl<M> Display for EthereumInboxInternal<M>
 where
@@ -26,6 +30,165 @@ where
     }
 }
 
+pub struct InboxIndexerBuilder {
+    pub from_height: u32,
+    pub chunk_size: u32,
+    pub finality_blocks: u32,
+}
+
+impl MakeableWithProvider for InboxIndexerBuilder {
+    type Output = Box<dyn InboxIndexer>;
+
+    fn make_with_provider<M: Middleware + 'static>(
+        &self,
+        provider: M,
+        locator: &ContractLocator,
+    ) -> Self::Output {
+        self.make_with_provider_and_heads(provider, locator, None)
+    }
+
+    fn make_with_provider_and_heads<M: Middleware + 'static>(
+        &self,
+        provider: M,
+        locator: &ContractLocator,
+        new_heads: Option<NewHeads>,
+    ) -> Self::Output {
+        let indexer = EthereumInboxIndexer::new(
+            Arc::new(provider),
+            locator,
+            self.from_height,
+            self.chunk_size,
+            self.finality_blocks,
+        );
+        Box::new(match new_heads {
+            Some(new_heads) => indexer.with_new_heads(new_heads),
+            None => indexer,
+        })
+    }
+}
+
+#[derive(Debug)]
+/// Struct that retrieves event data for an Ethereum inbox
+pub struct EthereumInboxIndexer<M>
+where
+    M: Middleware,
+{
+    contract: Arc<EthereumInboxInternal<M>>,
+    provider: Arc<M>,
+    #[allow(unused)]
+    from_height: u32,
+    #[allow(unused)]
+    chunk_size: u32,
+    finality_blocks: u32,
+    new_heads: Option<NewHeads>,
+}
+
+impl<M> EthereumInboxIndexer<M>
+where
+    M: Middleware + 'static,
+{
+    /// Create new EthereumInboxIndexer
+    pub fn new(
+        provider: Arc<M>,
+        locator: &ContractLocator,
+        from_height: u32,
+        chunk_size: u32,
+        finality_blocks: u32,
+    ) -> Self {
+        Self {
+            contract: Arc::new(EthereumInboxInternal::new(
+                &locator.address,
+                provider.clone(),
+            )),
+            provider,
+            from_height,
+            chunk_size,
+            finality_blocks,
+            new_heads: None,
+        }
+    }
+
+    /// Wait for new blocks pushed by `new_heads` rather than polling for them
+    pub fn with_new_heads(mut self, new_heads: NewHeads) -> Self {
+        self.new_heads = Some(new_heads);
+        self
+    }
+}
+
+#[async_trait]
+impl<M> Indexer for EthereumInboxIndexer<M>
+where
+    M: Middleware + 'static,
+{
+    #[instrument(err, skip(self))]
+    async fn get_finalized_block_number(&self) -> Result<u32> {
+        Ok(self
+            .get_latest_block_number()
+            .await?
+            .saturating_sub(self.finality_blocks))
+    }
+
+    #[instrument(err, skip(self))]
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        let mut tip = self.provider.get_block_number().await?.as_u64();
+        // The node serving the request may lag behind the latest head we
+        // were notified of
+        if let Some(latest) = self.new_heads.as_ref().and_then(NewHeads::latest) {
+            tip = tip.max(latest);
+        }
+        Ok(tip as u32)
+    }
+
+    #[instrument(err, skip(self))]
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        Ok(self
+            .provider
+            .get_block(BlockNumber::Number(block_number.into()))
+            .await?
+            .and_then(|block| block.hash))
+    }
+
+    async fn wait_for_finalized_block_after(&self, block: u32) {
+        match &self.new_heads {
+            Some(new_heads) => {
+                new_heads
+                    .wait_for_block_after(block as u64 + self.finality_blocks as u64)
+                    .await
+            }
+            None => sleep(Duration::from_secs(1)).await,
+        }
+    }
+
+    async fn wait_for_block_after(&self, block: u32) {
+        match &self.new_heads {
+            Some(new_heads) => new_heads.wait_for_block_after(block as u64).await,
+            None => sleep(Duration::from_secs(1)).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<M> InboxIndexer for EthereumInboxIndexer<M>
+where
+    M: Middleware + 'static,
+{
+    #[instrument(err, skip(self))]
+    async fn fetch_processed_messages(&self, from: u32, to: u32) -> Result<Vec<H256>> {
+        let events = self
+            .contract
+            .process_filter()
+            .from_block(from)
+            .to_block(to)
+            .query()
+            .await?;
+
+        Ok(events
+            .into_iter()
+            .map(|event| H256::from(event.message_hash))
+            .collect())
+    }
+}
+
 pub struct InboxBuilder {}
 
 impl MakeableWithProvider for InboxBuilder {
@@ -3,10 +3,12 @@
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use ethers::prelude::*;
 use eyre::Result;
+use tokio::time::sleep;
 use tracing::instrument;
 
 use abacus_core::{
@@ -19,6 +21,7 @@ use crate::contracts::interchain_gas_paymaster::{
     InterchainGasPaymaster as EthereumInterchainGasPaymasterInternal, INTERCHAINGASPAYMASTER_ABI,
 };
 use crate::trait_builder::MakeableWithProvider;
+use crate::NewHeads;
 
 impl<M> Display for EthereumInterchainGasPaymasterInternal<M>
 where
@@ -44,14 +47,27 @@ impl MakeableWithProvider for InterchainGasPaymasterIndexerBuilder {
         provider: M,
         locator: &ContractLocator,
     ) -> Self::Output {
-        Box::new(EthereumInterchainGasPaymasterIndexer::new(
+        self.make_with_provider_and_heads(provider, locator, None)
+    }
+
+    fn make_with_provider_and_heads<M: Middleware + 'static>(
+        &self,
+        provider: M,
+        locator: &ContractLocator,
+        new_heads: Option<NewHeads>,
+    ) -> Self::Output {
+        let indexer = EthereumInterchainGasPaymasterIndexer::new(
             Arc::new(provider),
             locator,
             self.outbox_address,
             self.from_height,
             self.chunk_size,
             self.finality_blocks,
-        ))
+        );
+        Box::new(match new_heads {
+            Some(new_heads) => indexer.with_new_heads(new_heads),
+            None => indexer,
+        })
     }
 }
 
@@ -69,6 +85,7 @@ where
     #[allow(unused)]
     chunk_size: u32,
     finality_blocks: u32,
+    new_heads: Option<NewHeads>,
 }
 
 impl<M> EthereumInterchainGasPaymasterIndexer<M>
@@ -94,8 +111,15 @@ where
             from_height,
             chunk_size,
             finality_blocks,
+            new_heads: None,
         }
     }
+
+    /// Wait for new blocks pushed by `new_heads` rather than polling for them
+    pub fn with_new_heads(mut self, new_heads: NewHeads) -> Self {
+        self.new_heads = Some(new_heads);
+        self
+    }
 }
 
 #[async_trait]
@@ -106,12 +130,48 @@ where
     #[instrument(err, skip(self))]
     async fn get_finalized_block_number(&self) -> Result<u32> {
         Ok(self
-            .provider
-            .get_block_number()
+            .get_latest_block_number()
             .await?
-            .as_u32()
             .saturating_sub(self.finality_blocks))
     }
+
+    #[instrument(err, skip(self))]
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        let mut tip = self.provider.get_block_number().await?.as_u64();
+        // The node serving the request may lag behind the latest head we
+        // were notified of
+        if let Some(latest) = self.new_heads.as_ref().and_then(NewHeads::latest) {
+            tip = tip.max(latest);
+        }
+        Ok(tip as u32)
+    }
+
+    #[instrument(err, skip(self))]
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        Ok(self
+            .provider
+            .get_block(BlockNumber::Number(block_number.into()))
+            .await?
+            .and_then(|block| block.hash))
+    }
+
+    async fn wait_for_finalized_block_after(&self, block: u32) {
+        match &self.new_heads {
+            Some(new_heads) => {
+                new_heads
+                    .wait_for_block_after(block as u64 + self.finality_blocks as u64)
+                    .await
+            }
+            None => sleep(Duration::from_secs(1)).await,
+        }
+    }
+
+    async fn wait_for_block_after(&self, block: u32) {
+        match &self.new_heads {
+            Some(new_heads) => new_heads.wait_for_block_after(block as u64).await,
+            None => sleep(Duration::from_secs(1)).await,
+        }
+    }
 }
 
 #[async_trait]
@@ -10,7 +10,13 @@ use num::Num;
 use std::collections::HashMap;
 
 use abacus_core::*;
-pub use retrying::{RetryingProvider, RetryingProviderError};
+pub use fallback::{FallbackProvider, FallbackProviderError};
+pub use heads::NewHeads;
+pub use http::{HttpTransport, HttpTransportError, JsonRpcError};
+pub use retrying::{
+    CircuitBreaker, RateLimiter, RetryingProvider, RetryingProviderConf, RetryingProviderError,
+};
+pub use ws::{ReconnectingWs, ReconnectingWsError};
 
 use crate::abi::FunctionExt;
 #[cfg(not(doctest))]
@@ -45,6 +51,18 @@ mod contracts;
 /// Retrying Provider
 mod retrying;
 
+/// HTTP transport
+mod http;
+
+/// Fallback and quorum Provider
+mod fallback;
+
+/// Reconnecting websocket transport
+mod ws;
+
+/// New block notifications
+mod heads;
+
 /// Ethereum connection configuration
 #[derive(Debug, serde::Deserialize, Clone)]
 #[serde(tag = "type", rename_all = "camelCase")]
@@ -53,11 +71,31 @@ pub enum Connection {
     Http {
         /// Fully qualified string to connect to
         url: String,
+        /// Retry, rate limiting and circuit breaking configuration
+        #[serde(default)]
+        retry: RetryingProviderConf,
+    },
+    /// HTTP connection to several providers, failing over between them
+    HttpFallback {
+        /// Comma separated list of fully qualified strings to connect to, in
+        /// order of preference
+        urls: String,
+        /// Number of providers which must agree on `eth_blockNumber` and
+        /// `eth_getLogs` results. Unset to read from a single provider.
+        #[serde(default)]
+        quorum: Option<String>,
+        /// Retry, rate limiting and circuit breaking configuration, applied
+        /// to each url separately
+        #[serde(default)]
+        retry: RetryingProviderConf,
     },
     /// Websocket connection details
     Ws {
         /// Fully qualified string to connect to
         url: String,
+        /// Retry, rate limiting and circuit breaking configuration
+        #[serde(default)]
+        retry: RetryingProviderConf,
     },
 }
 
@@ -65,6 +103,7 @@ impl Default for Connection {
     fn default() -> Self {
         Self::Http {
             url: Default::default(),
+            retry: Default::default(),
         }
     }
 }
@@ -2,22 +2,24 @@
 #![allow(missing_docs)]
 
 use std::collections::HashMap;
-use std::{error::Error as StdError, sync::Arc};
+use std::{error::Error as StdError, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use ethers::prelude::*;
 use eyre::Result;
+use tokio::time::sleep;
 use tracing::instrument;
 
 use abacus_core::{
     AbacusAbi, AbacusCommon, AbacusContract, ChainCommunicationError, Checkpoint, CheckpointMeta,
-    CheckpointWithMeta, ContractLocator, Indexer, Message, Outbox, OutboxIndexer, OutboxState,
-    RawCommittedMessage, TxOutcome,
+    CheckpointWithMeta, ContractLocator, Indexer, Message, MessageDispatchMeta, Outbox,
+    OutboxIndexer, OutboxState, RawCommittedMessage, RawCommittedMessageWithMeta, TxOutcome,
 };
 
-use crate::contracts::outbox::{Outbox as EthereumOutboxInternal, OUTBOX_ABI};
+use crate::contracts::outbox::{DispatchFilter, Outbox as EthereumOutboxInternal, OUTBOX_ABI};
 use crate::trait_builder::MakeableWithProvider;
 use crate::tx::report_tx;
+use crate::NewHeads;
 
 impl<M> std::fmt::Display for EthereumOutboxInternal<M>
 where
@@ -42,13 +44,26 @@ impl MakeableWithProvider for OutboxIndexerBuilder {
         provider: M,
         locator: &ContractLocator,
     ) -> Self::Output {
-        Box::new(EthereumOutboxIndexer::new(
+        self.make_with_provider_and_heads(provider, locator, None)
+    }
+
+    fn make_with_provider_and_heads<M: Middleware + 'static>(
+        &self,
+        provider: M,
+        locator: &ContractLocator,
+        new_heads: Option<NewHeads>,
+    ) -> Self::Output {
+        let indexer = EthereumOutboxIndexer::new(
             Arc::new(provider),
             locator,
             self.from_height,
             self.chunk_size,
             self.finality_blocks,
-        ))
+        );
+        Box::new(match new_heads {
+            Some(new_heads) => indexer.with_new_heads(new_heads),
+            None => indexer,
+        })
     }
 }
 
@@ -65,6 +80,7 @@ where
     #[allow(unused)]
     chunk_size: u32,
     finality_blocks: u32,
+    new_heads: Option<NewHeads>,
 }
 
 impl<M> EthereumOutboxIndexer<M>
@@ -88,8 +104,56 @@ where
             from_height,
             chunk_size,
             finality_blocks,
+            new_heads: None,
         }
     }
+
+    /// Wait for new blocks pushed by `new_heads` rather than polling for them
+    pub fn with_new_heads(mut self, new_heads: NewHeads) -> Self {
+        self.new_heads = Some(new_heads);
+        self
+    }
+
+    /// Sort Dispatch events by leaf index and attach metadata about their
+    /// dispatch
+    async fn with_dispatch_meta(
+        &self,
+        mut events: Vec<(DispatchFilter, LogMeta)>,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
+        events.sort_by(|a, b| a.0.leaf_index.cmp(&b.0.leaf_index));
+
+        // Logs don't include the time of their block, so fetch each block
+        // the messages were dispatched in once
+        let mut timestamps = HashMap::new();
+        for (_, meta) in events.iter() {
+            if timestamps.contains_key(&meta.block_hash) {
+                continue;
+            }
+            let block = self
+                .provider
+                .get_block(meta.block_hash)
+                .await?
+                .ok_or_else(|| eyre::eyre!("Block {:?} not found", meta.block_hash))?;
+            timestamps.insert(meta.block_hash, block.timestamp.as_u64());
+        }
+
+        Ok(events
+            .into_iter()
+            .map(|(event, meta)| RawCommittedMessageWithMeta {
+                message: RawCommittedMessage {
+                    leaf_index: event.leaf_index.as_u32(),
+                    message: event.message.to_vec(),
+                },
+                meta: MessageDispatchMeta {
+                    block_number: meta.block_number.as_u64(),
+                    block_hash: meta.block_hash,
+                    timestamp: timestamps[&meta.block_hash],
+                    transaction_hash: meta.transaction_hash,
+                    log_index: meta.log_index,
+                },
+            })
+            .collect())
+    }
 }
 
 #[async_trait]
@@ -100,12 +164,48 @@ where
     #[instrument(err, skip(self))]
     async fn get_finalized_block_number(&self) -> Result<u32> {
         Ok(self
-            .provider
-            .get_block_number()
+            .get_latest_block_number()
             .await?
-            .as_u32()
             .saturating_sub(self.finality_blocks))
     }
+
+    #[instrument(err, skip(self))]
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        let mut tip = self.provider.get_block_number().await?.as_u64();
+        // The node serving the request may lag behind the latest head we
+        // were notified of
+        if let Some(latest) = self.new_heads.as_ref().and_then(NewHeads::latest) {
+            tip = tip.max(latest);
+        }
+        Ok(tip as u32)
+    }
+
+    #[instrument(err, skip(self))]
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        Ok(self
+            .provider
+            .get_block(BlockNumber::Number(block_number.into()))
+            .await?
+            .and_then(|block| block.hash))
+    }
+
+    async fn wait_for_finalized_block_after(&self, block: u32) {
+        match &self.new_heads {
+            Some(new_heads) => {
+                new_heads
+                    .wait_for_block_after(block as u64 + self.finality_blocks as u64)
+                    .await
+            }
+            None => sleep(Duration::from_secs(1)).await,
+        }
+    }
+
+    async fn wait_for_block_after(&self, block: u32) {
+        match &self.new_heads {
+            Some(new_heads) => new_heads.wait_for_block_after(block as u64).await,
+            None => sleep(Duration::from_secs(1)).await,
+        }
+    }
 }
 
 #[async_trait]
@@ -114,24 +214,45 @@ where
     M: Middleware + 'static,
 {
     #[instrument(err, skip(self))]
-    async fn fetch_sorted_messages(&self, from: u32, to: u32) -> Result<Vec<RawCommittedMessage>> {
-        let mut events = self
+    async fn fetch_sorted_messages(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
+        let events = self
             .contract
             .dispatch_filter()
             .from_block(from)
             .to_block(to)
-            .query()
+            .query_with_meta()
             .await?;
 
-        events.sort_by(|a, b| a.leaf_index.cmp(&b.leaf_index));
+        self.with_dispatch_meta(events).await
+    }
 
-        Ok(events
-            .into_iter()
-            .map(|f| RawCommittedMessage {
-                leaf_index: f.leaf_index.as_u32(),
-                message: f.message.to_vec(),
-            })
-            .collect())
+    #[instrument(err, skip(self))]
+    async fn fetch_messages_by_leaf_index(
+        &self,
+        leaf_indices: &[u32],
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
+        // The leaf index is an indexed event parameter, so the node can
+        // filter by it
+        let topics: Vec<H256> = leaf_indices
+            .iter()
+            .map(|&leaf_index| H256::from_low_u64_be(leaf_index.into()))
+            .collect();
+        let events = self
+            .contract
+            .dispatch_filter()
+            .topic1(topics)
+            .from_block(from)
+            .to_block(to)
+            .query_with_meta()
+            .await?;
+
+        self.with_dispatch_meta(events).await
     }
 
     #[instrument(err, skip(self))]
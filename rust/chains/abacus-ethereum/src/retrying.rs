@@ -1,14 +1,24 @@
-use std::{fmt::Debug, str::FromStr, time::Duration};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::Debug,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
-use ethers::providers::{Http, JsonRpcClient, ProviderError};
-use serde::{de::DeserializeOwned, Serialize};
+use ethers::providers::{JsonRpcClient, ProviderError, PubsubClient};
+use ethers::types::U256;
+use eyre::ensure;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
 use tokio::time::sleep;
 use tracing::{debug, info, instrument, trace, warn};
 
-use crate::HttpClientError;
+use crate::{HttpTransport, HttpTransportError, ReconnectingWs, ReconnectingWsError};
 
 const METHODS_TO_NOT_RETRY: &[&str] = &[
     "eth_estimateGas",
@@ -16,12 +26,256 @@ const METHODS_TO_NOT_RETRY: &[&str] = &[
     "eth_sendRawTransaction",
 ];
 
-/// An HTTP Provider with a simple naive exponential backoff built-in
+const DEFAULT_MAX_REQUESTS: u32 = 6;
+const DEFAULT_BASE_RETRY_MS: u64 = 50;
+const DEFAULT_MAX_RETRY_MS: u64 = 10_000;
+const DEFAULT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Rate limiters shared by every provider connecting to the same url
+static RATE_LIMITERS: Lazy<Mutex<HashMap<String, Arc<RateLimiter>>>> = Lazy::new(Default::default);
+/// Circuit breakers shared by every provider connecting to the same url
+static CIRCUIT_BREAKERS: Lazy<Mutex<HashMap<String, Arc<CircuitBreaker>>>> =
+    Lazy::new(Default::default);
+
+/// Retry, rate limiting and circuit breaking configuration for a connection
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RetryingProviderConf {
+    /// Maximum number of attempts per request. Defaults to 6.
+    pub maxrequests: Option<String>,
+    /// Backoff before the first retry in ms, doubled on every further retry
+    /// and jittered. Defaults to 50.
+    pub baseretryms: Option<String>,
+    /// Maximum backoff between retries in ms, including waits the provider
+    /// asks for. Defaults to 10000.
+    pub maxretryms: Option<String>,
+    /// Maximum sustained requests per second to the url. Unlimited if unset.
+    pub ratelimit: Option<String>,
+    /// Requests which may be made at once before the rate limit applies.
+    /// Defaults to one second's worth of requests.
+    pub rateburst: Option<String>,
+    /// Number of consecutive failed requests after which requests to the url
+    /// fail fast. Never fails fast if unset.
+    pub breakerthreshold: Option<String>,
+    /// Seconds to fail fast for before trying the url again. Defaults to 30.
+    pub breakercooldownsecs: Option<String>,
+}
+
+impl RetryingProviderConf {
+    /// Wrap `inner`, which connects to `url`, in a RetryingProvider. The rate
+    /// limit and circuit breaker are shared with every other provider for
+    /// the same url, and building fails if they were configured differently.
+    pub fn build<P>(
+        &self,
+        inner: P,
+        url: &str,
+        default_max_requests: Option<u32>,
+    ) -> eyre::Result<RetryingProvider<P>> {
+        let max_requests = match &self.maxrequests {
+            Some(v) => v.parse()?,
+            None => default_max_requests.unwrap_or(DEFAULT_MAX_REQUESTS),
+        };
+        ensure!(max_requests >= 1, "maxrequests must be at least 1");
+        let base_retry_ms = match &self.baseretryms {
+            Some(v) => v.parse()?,
+            None => DEFAULT_BASE_RETRY_MS,
+        };
+        ensure!(base_retry_ms >= 1, "baseretryms must be at least 1");
+        let mut provider = RetryingProvider::new(inner, max_requests, base_retry_ms);
+        if let Some(max_retry_ms) = &self.maxretryms {
+            let max_retry_ms: u64 = max_retry_ms.parse()?;
+            ensure!(
+                max_retry_ms >= base_retry_ms,
+                "maxretryms must be at least baseretryms ({})",
+                base_retry_ms
+            );
+            provider.set_max_retry_ms(max_retry_ms);
+        }
+
+        if let Some(rate) = &self.ratelimit {
+            let rate: f64 = rate.parse()?;
+            let burst = match &self.rateburst {
+                Some(v) => v.parse()?,
+                None => rate.max(1.0),
+            };
+            let limiter = Arc::new(RateLimiter::new(rate, burst)?);
+            let limiter = match RATE_LIMITERS.lock().unwrap().entry(url.to_owned()) {
+                Entry::Occupied(entry) => {
+                    let existing = entry.get();
+                    ensure!(
+                        existing.rate == limiter.rate && existing.burst == limiter.burst,
+                        "Conflicting rate limits configured for the same url"
+                    );
+                    existing.clone()
+                }
+                Entry::Vacant(entry) => entry.insert(limiter).clone(),
+            };
+            provider = provider.with_rate_limiter(limiter);
+        }
+
+        if let Some(threshold) = &self.breakerthreshold {
+            let threshold: u32 = threshold.parse()?;
+            let cooldown = match &self.breakercooldownsecs {
+                Some(v) => Duration::from_secs(v.parse()?),
+                None => DEFAULT_BREAKER_COOLDOWN,
+            };
+            let breaker = Arc::new(CircuitBreaker::new(threshold, cooldown)?);
+            let breaker = match CIRCUIT_BREAKERS.lock().unwrap().entry(url.to_owned()) {
+                Entry::Occupied(entry) => {
+                    let existing = entry.get();
+                    ensure!(
+                        existing.threshold == breaker.threshold
+                            && existing.cooldown == breaker.cooldown,
+                        "Conflicting circuit breakers configured for the same url"
+                    );
+                    existing.clone()
+                }
+                Entry::Vacant(entry) => entry.insert(breaker).clone(),
+            };
+            provider = provider.with_circuit_breaker(breaker);
+        }
+        Ok(provider)
+    }
+}
+
+/// A client-side token bucket rate limit
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// Maximum tokens in the bucket
+    burst: f64,
+    /// Tokens in the bucket as of the instant
+    bucket: tokio::sync::Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// Allow `rate` requests per second on average, and up to `burst` at once
+    pub fn new(rate: f64, burst: f64) -> eyre::Result<Self> {
+        ensure!(
+            rate.is_finite() && rate > 0.0,
+            "ratelimit must be a positive number"
+        );
+        ensure!(
+            burst.is_finite() && burst >= 1.0,
+            "rateburst must be at least 1"
+        );
+        Ok(Self {
+            rate,
+            burst,
+            bucket: tokio::sync::Mutex::new((burst, Instant::now())),
+        })
+    }
+
+    /// Wait until a request may be made. Waiters are served in order.
+    pub async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+        loop {
+            let (tokens, at) = *bucket;
+            let now = Instant::now();
+            let tokens = (tokens + (now - at).as_secs_f64() * self.rate).min(self.burst);
+            if tokens >= 1.0 {
+                *bucket = (tokens - 1.0, now);
+                return;
+            }
+            *bucket = (tokens, now);
+            sleep(Duration::from_secs_f64((1.0 - tokens) / self.rate)).await;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// When the trial request let through after the cooldown was made, until
+    /// its outcome is recorded
+    trial_since: Option<Instant>,
+}
+
+/// Fails requests fast once a provider has failed `threshold` consecutive
+/// requests, until `cooldown` has passed. A single trial request is then let
+/// through, which closes the breaker if it succeeds and opens it again if it
+/// fails.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    /// Open after `threshold` consecutive failures, for `cooldown`
+    pub fn new(threshold: u32, cooldown: Duration) -> eyre::Result<Self> {
+        ensure!(threshold >= 1, "breakerthreshold must be at least 1");
+        Ok(Self {
+            threshold,
+            cooldown,
+            state: Default::default(),
+        })
+    }
+
+    /// Whether a request may be made
+    pub fn allows_request(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match state.open_until {
+            None => true,
+            Some(until) if now < until => false,
+            // Only one trial request at a time, unless the outcome of the
+            // last one was never recorded, e.g. because it was cancelled
+            Some(_) => {
+                let trial_pending = state
+                    .trial_since
+                    .map_or(false, |since| now < since + self.cooldown);
+                if !trial_pending {
+                    state.trial_since = Some(now);
+                }
+                !trial_pending
+            }
+        }
+    }
+
+    /// Record that a request failed without telling whether the provider is
+    /// healthy, e.g. because it was rate limited, so that another trial
+    /// request may be made.
+    pub fn record_inconclusive(&self) {
+        self.state.lock().unwrap().trial_since = None;
+    }
+
+    /// Record the outcome of a request. Errors returned by the node, such as
+    /// reverts, are not failures of the provider.
+    pub fn record(&self, healthy: bool) {
+        let mut state = self.state.lock().unwrap();
+        if healthy {
+            *state = Default::default();
+            return;
+        }
+        state.consecutive_failures += 1;
+        let half_open = state.open_until.is_some();
+        if half_open || state.consecutive_failures >= self.threshold {
+            if !half_open {
+                warn!(
+                    failures = state.consecutive_failures,
+                    cooldown = ?self.cooldown,
+                    "Circuit breaker opened; failing requests fast"
+                );
+            }
+            state.open_until = Some(Instant::now() + self.cooldown);
+            state.trial_since = None;
+        }
+    }
+}
+
+/// A Provider with jittered exponential backoff, optional client-side rate
+/// limiting and an optional circuit breaker built-in
 #[derive(Debug, Clone)]
 pub struct RetryingProvider<P> {
     inner: P,
     max_requests: u32,
     base_retry_ms: u64,
+    max_retry_ms: u64,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
 }
 
 impl<P> RetryingProvider<P> {
@@ -31,12 +285,27 @@ impl<P> RetryingProvider<P> {
             inner,
             max_requests: 0,
             base_retry_ms: 0,
+            max_retry_ms: DEFAULT_MAX_RETRY_MS,
+            rate_limiter: None,
+            circuit_breaker: None,
         };
         zelf.set_max_requests(max_requests);
         zelf.set_base_retry_ms(base_retry_ms);
         zelf
     }
 
+    /// Limit the rate of requests, including retries.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Fail requests fast while the breaker is open.
+    pub fn with_circuit_breaker(mut self, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
     /// Set the max_requests (and by extension the total time a request can take).
     pub fn set_max_requests(&mut self, max_requests: u32) {
         assert!(max_requests >= 1);
@@ -49,6 +318,12 @@ impl<P> RetryingProvider<P> {
         self.base_retry_ms = base_retry_ms;
     }
 
+    /// Set the maximum backoff time, including waits a provider asks for.
+    pub fn set_max_retry_ms(&mut self, max_retry_ms: u64) {
+        assert!(max_retry_ms >= self.base_retry_ms);
+        self.max_retry_ms = max_retry_ms;
+    }
+
     /// Get the max_requests
     pub fn max_requests(&self) -> u32 {
         self.max_requests
@@ -58,6 +333,47 @@ impl<P> RetryingProvider<P> {
     pub fn base_retry_ms(&self) -> u64 {
         self.base_retry_ms
     }
+
+    /// Get the max retry duration in ms.
+    pub fn max_retry_ms(&self) -> u64 {
+        self.max_retry_ms
+    }
+
+    /// Wait until the attempt may be made, or return false if the circuit
+    /// breaker is open.
+    async fn before_attempt(&self) -> bool {
+        if let Some(breaker) = &self.circuit_breaker {
+            if !breaker.allows_request() {
+                return false;
+            }
+        }
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire().await;
+        }
+        true
+    }
+
+    fn record(&self, healthy: Option<bool>) {
+        if let Some(breaker) = &self.circuit_breaker {
+            match healthy {
+                Some(healthy) => breaker.record(healthy),
+                None => breaker.record_inconclusive(),
+            }
+        }
+    }
+
+    /// How long to wait after the `attempt`th failed attempt: jittered
+    /// exponential backoff, or longer if the provider asked us to wait, but
+    /// never longer than `max_retry_ms`.
+    fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let max_retry = Duration::from_millis(self.max_retry_ms);
+        let backoff_ms = self
+            .base_retry_ms
+            .saturating_mul(2u64.saturating_pow(attempt - 1))
+            .min(self.max_retry_ms);
+        let jittered_ms = rand::thread_rng().gen_range(backoff_ms / 2..=backoff_ms);
+        Duration::from_millis(jittered_ms).max(retry_after.unwrap_or_default().min(max_retry))
+    }
 }
 
 /// Error type for the RetryingProvider
@@ -72,6 +388,9 @@ where
     /// Hit max requests
     #[error("Hit max requests")]
     MaxRequests(P::Error),
+    /// The circuit breaker is open after sustained errors
+    #[error("Circuit breaker is open; failing fast")]
+    CircuitBreakerOpen,
 }
 
 impl<P> From<RetryingProviderError<P>> for ProviderError
@@ -84,22 +403,91 @@ where
     }
 }
 
-#[async_trait]
-impl JsonRpcClient for RetryingProvider<Http> {
-    type Error = RetryingProviderError<Http>;
+/// How the retrying provider handles a failed attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HandleMethod {
+    /// Retry after backing off, for at least the given duration if any
+    Retry(Option<Duration>),
+    /// Return the error without retrying
+    Halt,
+}
 
-    #[instrument(level = "error", skip_all, fields(method = %method))]
-    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+/// Classification of a transport's errors for the retrying provider
+trait RetryableError: std::error::Error {
+    /// Whether the provider was healthy despite the error, as recorded by the
+    /// circuit breaker. None if the error says nothing about its health.
+    fn provider_healthy(&self) -> Option<bool>;
+
+    /// How to handle the error for a request to `method`
+    fn handle_method(&self, method: &str) -> HandleMethod;
+}
+
+impl RetryableError for HttpTransportError {
+    fn provider_healthy(&self) -> Option<bool> {
+        match self {
+            // The provider is up, but the request was not executed
+            HttpTransportError::RateLimited { .. } => None,
+            HttpTransportError::JsonRpcError(_) => Some(true),
+            HttpTransportError::ReqwestError(_) | HttpTransportError::SerdeJson { .. } => {
+                Some(false)
+            }
+        }
+    }
+
+    fn handle_method(&self, method: &str) -> HandleMethod {
+        match self {
+            HttpTransportError::RateLimited { retry_after, .. } => {
+                HandleMethod::Retry(*retry_after)
+            }
+            // We don't want to retry errors that are probably not going to work if we keep
+            // retrying them or that indicate an error in higher-order logic and not
+            // transient provider (connection or other) errors.
+            HttpTransportError::JsonRpcError(_) if METHODS_TO_NOT_RETRY.contains(&method) => {
+                HandleMethod::Halt
+            }
+            _ => HandleMethod::Retry(None),
+        }
+    }
+}
+
+impl RetryableError for ReconnectingWsError {
+    fn provider_healthy(&self) -> Option<bool> {
+        Some(!self.is_connection_error())
+    }
+
+    fn handle_method(&self, method: &str) -> HandleMethod {
+        // A request that was sent but whose response was lost may still have
+        // been executed, so these are not retried even when the connection
+        // dropped.
+        if METHODS_TO_NOT_RETRY.contains(&method) {
+            HandleMethod::Halt
+        } else {
+            HandleMethod::Retry(None)
+        }
+    }
+}
+
+impl<P> RetryingProvider<P>
+where
+    P: JsonRpcClient,
+    P::Error: RetryableError,
+{
+    /// Make a request to the inner provider, retrying failed attempts with
+    /// backoff as the transport's errors allow.
+    async fn request_with_retry<R>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<R, RetryingProviderError<P>>
     where
-        T: Debug + Serialize + Send + Sync,
         R: DeserializeOwned,
     {
-        let params = serde_json::to_value(params).expect("valid");
-
-        let mut last_err;
         let mut i = 1;
         loop {
-            let backoff_ms = self.base_retry_ms * 2u64.pow(i - 1);
+            if !self.before_attempt().await {
+                warn!("Circuit breaker open in retrying provider; failing fast.");
+                return Err(RetryingProviderError::CircuitBreakerOpen);
+            }
             trace!(params = %serde_json::to_string(&params).unwrap_or_default(), "Dispatching request with params");
             debug!(attempt = i, "Dispatching request");
 
@@ -108,52 +496,92 @@ impl JsonRpcClient for RetryingProvider<Http> {
                 _ => self.inner.request(method, &params),
             };
 
-            match fut.await {
-                Ok(res) => return Ok(res),
-                Err(HttpClientError::ReqwestError(e)) => {
-                    info!(
-                        backoff_ms,
-                        retries_remaining = self.max_requests - i,
-                        error = %e,
-                        "ReqwestError in retrying provider.",
-                    );
-                    last_err = HttpClientError::ReqwestError(e);
+            let retry_after = match fut.await {
+                Ok(res) => {
+                    self.record(Some(true));
+                    return Ok(res);
                 }
-                Err(HttpClientError::JsonRpcError(e)) => {
-                    // We don't want to retry errors that are probably not going to work if we keep
-                    // retrying them or that indicate an error in higher-order logic and not
-                    // transient provider (connection or other) errors.
-                    if METHODS_TO_NOT_RETRY.contains(&method) {
-                        warn!(error = %e, "JsonRpcError in retrying provider; not retrying.");
-                        return Err(RetryingProviderError::JsonRpcClientError(
-                            HttpClientError::JsonRpcError(e),
-                        ));
-                    } else {
-                        info!(error = %e, "JsonRpcError in retrying provider.");
-                        last_err = HttpClientError::JsonRpcError(e);
+                Err(err) => {
+                    self.record(err.provider_healthy());
+                    match err.handle_method(method) {
+                        HandleMethod::Halt => {
+                            warn!(error = %err, "Error in retrying provider; not retrying.");
+                            return Err(RetryingProviderError::JsonRpcClientError(err));
+                        }
+                        HandleMethod::Retry(_) if i >= self.max_requests => {
+                            trace!(
+                                requests_made = self.max_requests,
+                                "Retrying provider reached max requests."
+                            );
+                            return Err(RetryingProviderError::MaxRequests(err));
+                        }
+                        HandleMethod::Retry(retry_after) => {
+                            info!(
+                                retries_remaining = self.max_requests - i,
+                                ?retry_after,
+                                error = %err,
+                                "Error in retrying provider.",
+                            );
+                            retry_after
+                        }
                     }
                 }
-                Err(HttpClientError::SerdeJson { err, text }) => {
-                    info!(error = %err, "SerdeJson error in retrying provider");
-                    last_err = HttpClientError::SerdeJson { err, text };
-                }
-            }
+            };
 
+            let backoff = self.backoff(i, retry_after);
+            trace!(?backoff, "Retrying provider going to sleep.");
+            sleep(backoff).await;
             i += 1;
-            if i <= self.max_requests {
-                trace!(backoff_ms, "Retrying provider going to sleep.");
-                sleep(Duration::from_millis(backoff_ms)).await;
-            } else {
-                trace!(
-                    requests_made = self.max_requests,
-                    "Retrying provider reached max requests."
-                );
-                return Err(RetryingProviderError::MaxRequests(last_err));
-            }
         }
     }
 }
 
+#[async_trait]
+impl JsonRpcClient for RetryingProvider<HttpTransport> {
+    type Error = RetryingProviderError<HttpTransport>;
+
+    #[instrument(level = "error", skip_all, fields(method = %method))]
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params).expect("valid");
+        self.request_with_retry(method, params).await
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for RetryingProvider<ReconnectingWs> {
+    type Error = RetryingProviderError<ReconnectingWs>;
+
+    #[instrument(level = "error", skip_all, fields(method = %method))]
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params).expect("valid");
+        self.request_with_retry(method, params).await
+    }
+}
+
+impl PubsubClient for RetryingProvider<ReconnectingWs> {
+    type NotificationStream = <ReconnectingWs as PubsubClient>::NotificationStream;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, Self::Error> {
+        self.inner
+            .subscribe(id)
+            .map_err(RetryingProviderError::JsonRpcClientError)
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), Self::Error> {
+        self.inner
+            .unsubscribe(id)
+            .map_err(RetryingProviderError::JsonRpcClientError)
+    }
+}
+
 impl<P> FromStr for RetryingProvider<P>
 where
     P: JsonRpcClient + FromStr,
@@ -161,6 +589,210 @@ where
     type Err = <P as FromStr>::Err;
 
     fn from_str(src: &str) -> Result<Self, Self::Err> {
-        Ok(Self::new(src.parse()?, 6, 50))
+        Ok(Self::new(
+            src.parse()?,
+            DEFAULT_MAX_REQUESTS,
+            DEFAULT_BASE_RETRY_MS,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use ethers::types::U64;
+    use warp::http::{Response, StatusCode};
+    use warp::Filter;
+
+    use super::*;
+
+    /// Serve `responses` in order, then `0x1` forever, counting requests
+    fn spawn_server(
+        responses: Vec<(StatusCode, Option<&'static str>, &'static str)>,
+    ) -> (String, Arc<AtomicU32>) {
+        let count = Arc::new(AtomicU32::new(0));
+        let route_count = count.clone();
+        let route = warp::post().map(move || {
+            let n = route_count.fetch_add(1, Ordering::SeqCst) as usize;
+            let (status, retry_after, body) = responses.get(n).cloned().unwrap_or((
+                StatusCode::OK,
+                None,
+                r#"{"jsonrpc":"2.0","id":1,"result":"0x1"}"#,
+            ));
+            let mut response = Response::builder().status(status);
+            if let Some(retry_after) = retry_after {
+                response = response.header("Retry-After", retry_after);
+            }
+            response.body(body).unwrap()
+        });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{}", addr), count)
+    }
+
+    #[tokio::test]
+    async fn it_respects_retry_after() {
+        let (url, count) = spawn_server(vec![
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                Some("1"),
+                "Too Many Requests",
+            ),
+            (
+                StatusCode::OK,
+                None,
+                r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32005,"message":"request rate exceeded"}}"#,
+            ),
+        ]);
+        let provider = RetryingProvider::new(url.parse::<HttpTransport>().unwrap(), 3, 1);
+
+        let start = Instant::now();
+        let res: U64 = provider.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(res, U64::from(1));
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn it_does_not_retry_node_errors_for_transactions() {
+        let (url, count) = spawn_server(vec![(
+            StatusCode::OK,
+            None,
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32000,"message":"nonce too low"}}"#,
+        )]);
+        let provider = RetryingProvider::new(url.parse::<HttpTransport>().unwrap(), 3, 1);
+
+        let res: Result<U64, _> = provider.request("eth_sendRawTransaction", ["0x00"]).await;
+        assert!(matches!(
+            res,
+            Err(RetryingProviderError::JsonRpcClientError(
+                HttpTransportError::JsonRpcError(_)
+            ))
+        ));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn it_fails_fast_when_the_breaker_opens() {
+        let (url, count) = spawn_server(vec![
+            (StatusCode::BAD_GATEWAY, None, "Bad Gateway"),
+            (StatusCode::BAD_GATEWAY, None, "Bad Gateway"),
+        ]);
+        let breaker = Arc::new(CircuitBreaker::new(2, Duration::from_millis(200)).unwrap());
+        let provider = RetryingProvider::new(url.parse::<HttpTransport>().unwrap(), 1, 1)
+            .with_circuit_breaker(breaker);
+
+        for _ in 0..2 {
+            let res: Result<U64, _> = provider.request("eth_blockNumber", ()).await;
+            assert!(matches!(res, Err(RetryingProviderError::MaxRequests(_))));
+        }
+        let res: Result<U64, _> = provider.request("eth_blockNumber", ()).await;
+        assert!(matches!(
+            res,
+            Err(RetryingProviderError::CircuitBreakerOpen)
+        ));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // After the cooldown a request is let through and closes the breaker
+        sleep(Duration::from_millis(200)).await;
+        let res: U64 = provider.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(res, U64::from(1));
+    }
+
+    #[tokio::test]
+    async fn it_caps_retry_after_at_the_max_backoff() {
+        let (url, count) = spawn_server(vec![(
+            StatusCode::TOO_MANY_REQUESTS,
+            Some("86400"),
+            "Too Many Requests",
+        )]);
+        let mut provider = RetryingProvider::new(url.parse::<HttpTransport>().unwrap(), 2, 1);
+        provider.set_max_retry_ms(100);
+
+        let start = Instant::now();
+        let res: U64 = provider.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(res, U64::from(1));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn it_lets_a_single_trial_request_through_when_half_open() {
+        let cooldown = Duration::from_millis(50);
+        let breaker = CircuitBreaker::new(1, cooldown).unwrap();
+        breaker.record(false);
+        assert!(!breaker.allows_request());
+
+        // Only one request is let through after the cooldown
+        std::thread::sleep(cooldown);
+        assert!(breaker.allows_request());
+        assert!(!breaker.allows_request());
+
+        // A failed trial opens the breaker again
+        breaker.record(false);
+        assert!(!breaker.allows_request());
+        std::thread::sleep(cooldown);
+        assert!(breaker.allows_request());
+        assert!(!breaker.allows_request());
+
+        // An inconclusive trial lets another one through
+        breaker.record_inconclusive();
+        assert!(breaker.allows_request());
+        assert!(!breaker.allows_request());
+
+        // A successful trial closes the breaker
+        breaker.record(true);
+        assert!(breaker.allows_request());
+        assert!(breaker.allows_request());
+    }
+
+    #[tokio::test]
+    async fn it_limits_the_request_rate() {
+        let limiter = RateLimiter::new(20.0, 2.0).unwrap();
+        let start = Instant::now();
+        for _ in 0..4 {
+            limiter.acquire().await;
+        }
+        // Two requests are allowed immediately, the rest at 20/s
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn it_rejects_invalid_settings() {
+        let conf = |f: fn(&mut RetryingProviderConf)| {
+            let mut conf = RetryingProviderConf::default();
+            f(&mut conf);
+            conf.build((), "http://invalid.settings", None)
+        };
+        assert!(conf(|c| c.maxrequests = Some("0".into())).is_err());
+        assert!(conf(|c| c.baseretryms = Some("0".into())).is_err());
+        assert!(conf(|c| c.maxretryms = Some("10".into())).is_err());
+        assert!(conf(|c| c.ratelimit = Some("0".into())).is_err());
+        assert!(conf(|c| c.ratelimit = Some("NaN".into())).is_err());
+        assert!(conf(|c| {
+            c.ratelimit = Some("5".into());
+            c.rateburst = Some("0.5".into());
+        })
+        .is_err());
+        assert!(conf(|c| c.breakerthreshold = Some("0".into())).is_err());
+    }
+
+    #[test]
+    fn it_rejects_conflicting_settings_for_a_url() {
+        let url = "http://conflicting.settings";
+        let conf = |ratelimit: &str, breakerthreshold: &str| RetryingProviderConf {
+            ratelimit: Some(ratelimit.into()),
+            breakerthreshold: Some(breakerthreshold.into()),
+            ..Default::default()
+        };
+        let first = conf("10", "3").build((), url, None).unwrap();
+        let second = conf("10", "3").build((), url, None).unwrap();
+        assert!(Arc::ptr_eq(
+            first.rate_limiter.as_ref().unwrap(),
+            second.rate_limiter.as_ref().unwrap()
+        ));
+        assert!(conf("20", "3").build((), url, None).is_err());
+        assert!(conf("10", "5").build((), url, None).is_err());
     }
 }
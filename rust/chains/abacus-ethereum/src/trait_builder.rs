@@ -6,11 +6,16 @@ use std::time::Duration;
 use abacus_core::{ContractLocator, Signers};
 use ethers_prometheus::{PrometheusMiddleware, PrometheusMiddlewareConf, ProviderMetrics};
 
-use crate::{Connection, RetryingProvider};
+use crate::fallback::endpoint_name;
+use crate::{Connection, FallbackProvider, HttpTransport, NewHeads, ReconnectingWs};
 
 // This should be whatever the prometheus scrape interval is
 const METRICS_SCRAPE_INTERVAL: Duration = Duration::from_secs(60);
 
+// Requests to each provider of a fallback connection are retried fewer times
+// by default so that failing over to the next provider is quick
+const FALLBACK_MAX_REQUESTS: u32 = 2;
+
 /// A trait for dynamic trait creation with provider initialization.
 #[async_trait]
 pub trait MakeableWithProvider {
@@ -27,14 +32,54 @@ pub trait MakeableWithProvider {
         metrics: Option<(ProviderMetrics, PrometheusMiddlewareConf)>,
     ) -> eyre::Result<Self::Output> {
         Ok(match conn {
-            Connection::Http { url } => {
-                let http = url.parse::<RetryingProvider<Http>>()?;
-                self.wrap_with_metrics(http, locator, signer, metrics)
+            Connection::Http { url, retry } => {
+                let http = retry.build(url.parse::<HttpTransport>()?, &url, None)?;
+                self.wrap_with_metrics(http, locator, signer, metrics, None)
+                    .await?
+            }
+            Connection::HttpFallback {
+                urls,
+                quorum,
+                retry,
+            } => {
+                let mut providers = vec![];
+                for url in urls.split(',').map(str::trim).filter(|url| !url.is_empty()) {
+                    let http = retry.build(
+                        url.parse::<HttpTransport>()?,
+                        url,
+                        Some(FALLBACK_MAX_REQUESTS),
+                    )?;
+                    providers.push((endpoint_name(url), http));
+                }
+                if providers.is_empty() {
+                    eyre::bail!("No urls provided for fallback connection");
+                }
+                let provider_count = providers.len();
+                let mut fallback = FallbackProvider::new(providers);
+                if let Some(quorum) = quorum {
+                    let quorum: usize = quorum.parse()?;
+                    if !(1..=provider_count).contains(&quorum) {
+                        eyre::bail!(
+                            "Quorum of {} is not between 1 and the {} urls provided",
+                            quorum,
+                            provider_count
+                        );
+                    }
+                    fallback = fallback.with_quorum(quorum);
+                }
+                if let Some(histogram) =
+                    metrics.as_ref().and_then(|(m, _)| m.rpc_duration_seconds())
+                {
+                    fallback = fallback.with_metrics(histogram.clone(), locator.chain_name.clone());
+                }
+                self.wrap_with_metrics(fallback, locator, signer, metrics, None)
                     .await?
             }
-            Connection::Ws { url } => {
-                let ws = Ws::connect(url).await?;
-                self.wrap_with_metrics(ws, locator, signer, metrics).await?
+            Connection::Ws { url, retry } => {
+                let ws = retry.build(ReconnectingWs::connect(url.as_str()).await?, &url, None)?;
+                let new_heads = NewHeads::new(ws.clone());
+                self.wrap_with_metrics(ws, locator, signer, metrics, Some(new_heads))
+                    .await?
             }
         })
     }
@@ -46,6 +91,7 @@ pub trait MakeableWithProvider {
         locator: &ContractLocator,
         signer: Option<Signers>,
         metrics: Option<(ProviderMetrics, PrometheusMiddlewareConf)>,
+        new_heads: Option<NewHeads>,
     ) -> eyre::Result<Self::Output>
     where
         P: JsonRpcClient + 'static,
@@ -54,9 +100,11 @@ pub trait MakeableWithProvider {
         Ok(if let Some(metrics) = metrics {
             let provider = Arc::new(PrometheusMiddleware::new(provider, metrics.0, metrics.1));
             tokio::spawn(provider.start_updating_on_interval(METRICS_SCRAPE_INTERVAL));
-            self.wrap_with_signer(provider, locator, signer).await?
+            self.wrap_with_signer(provider, locator, signer, new_heads)
+                .await?
         } else {
-            self.wrap_with_signer(provider, locator, signer).await?
+            self.wrap_with_signer(provider, locator, signer, new_heads)
+                .await?
         })
     }
 
@@ -66,18 +114,35 @@ pub trait MakeableWithProvider {
         provider: M,
         locator: &ContractLocator,
         signer: Option<Signers>,
+        new_heads: Option<NewHeads>,
     ) -> eyre::Result<Self::Output>
     where
         M: Middleware + 'static,
     {
         Ok(if let Some(signer) = signer {
             let signing_provider = make_signing_provider(provider, signer).await?;
-            self.make_with_provider(signing_provider, locator)
+            self.make_with_provider_and_heads(signing_provider, locator, new_heads)
         } else {
-            self.make_with_provider(provider, locator)
+            self.make_with_provider_and_heads(provider, locator, new_heads)
         })
     }
 
+    /// Construct a new instance of the associated trait using a provider,
+    /// along with notifications of new blocks if the connection can push
+    /// them. Only indexers need to hear of new blocks, so by default the
+    /// notifications are ignored.
+    fn make_with_provider_and_heads<M>(
+        &self,
+        provider: M,
+        locator: &ContractLocator,
+        _new_heads: Option<NewHeads>,
+    ) -> Self::Output
+    where
+        M: Middleware + 'static,
+    {
+        self.make_with_provider(provider, locator)
+    }
+
     /// Construct a new instance of the associated trait using a provider.
     fn make_with_provider<M>(&self, provider: M, locator: &ContractLocator) -> Self::Output
     where
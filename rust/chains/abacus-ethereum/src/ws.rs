@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, ProviderError, PubsubClient, Ws, WsClientError};
+use ethers::types::U256;
+use futures::{channel::mpsc, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::time::sleep;
+use tracing::{debug, info, instrument, warn};
+
+/// Initial delay between attempts to re-establish a dropped subscription.
+const RESUBSCRIBE_BASE_BACKOFF: Duration = Duration::from_millis(100);
+/// Maximum delay between attempts to re-establish a dropped subscription.
+const RESUBSCRIBE_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Error type for the ReconnectingWs
+#[derive(Error, Debug)]
+pub enum ReconnectingWsError {
+    /// An error from the underlying websocket client
+    #[error(transparent)]
+    WsClientError(#[from] WsClientError),
+    /// The subscription id is not known to this client
+    #[error("Unknown subscription {0}")]
+    UnknownSubscription(U256),
+    /// A request or response could not be (de)serialized
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+impl ReconnectingWsError {
+    /// Whether the error was caused by the connection rather than a response
+    /// from the node.
+    pub fn is_connection_error(&self) -> bool {
+        matches!(
+            self,
+            ReconnectingWsError::WsClientError(e)
+                if !matches!(e, WsClientError::JsonRpcError(_) | WsClientError::JsonError(_))
+        )
+    }
+}
+
+impl From<ReconnectingWsError> for ProviderError {
+    fn from(src: ReconnectingWsError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(src))
+    }
+}
+
+#[derive(Debug)]
+struct Subscription {
+    /// Params of the `eth_subscribe` request, to re-subscribe with
+    params: Value,
+    /// The id the node assigned the subscription on the current connection
+    server_id: U256,
+    /// Where notifications are forwarded to once someone listens
+    sink: Option<mpsc::UnboundedSender<Value>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    url: String,
+    /// The current connection and how many times it has been replaced
+    ws: RwLock<(Ws, u64)>,
+    /// Held while reconnecting so only one reconnection happens at a time
+    reconnecting: tokio::sync::Mutex<()>,
+    /// Subscriptions by the id handed out to callers, which is stable across
+    /// reconnections
+    subscriptions: Mutex<HashMap<U256, Subscription>>,
+    next_subscription_id: Mutex<U256>,
+}
+
+/// A websocket transport which transparently reconnects when the connection
+/// drops.
+///
+/// A request which fails because the connection dropped reconnects before
+/// returning the error, so retrying it (see `RetryingProvider`) uses the new
+/// connection. Subscriptions are re-established on every new connection and
+/// keep delivering notifications through the same stream, though
+/// notifications emitted while disconnected are lost.
+#[derive(Debug, Clone)]
+pub struct ReconnectingWs {
+    shared: Arc<Shared>,
+}
+
+impl ReconnectingWs {
+    /// Connect to the websocket at `url`
+    pub async fn connect(url: impl Into<String>) -> Result<Self, ReconnectingWsError> {
+        let url = url.into();
+        let ws = Ws::connect(url.as_str()).await?;
+        Ok(Self {
+            shared: Arc::new(Shared {
+                url,
+                ws: RwLock::new((ws, 0)),
+                reconnecting: Default::default(),
+                subscriptions: Default::default(),
+                next_subscription_id: Mutex::new(U256::one()),
+            }),
+        })
+    }
+
+    async fn request_with_reconnect<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &Value,
+    ) -> Result<R, ReconnectingWsError> {
+        let (ws, generation) = self.shared.current();
+        match ws.request(method, params).await {
+            Ok(res) => Ok(res),
+            Err(e) => {
+                let e = ReconnectingWsError::from(e);
+                if e.is_connection_error() {
+                    if let Err(reconnect_err) = self.shared.reconnect(generation).await {
+                        warn!(error = %reconnect_err, "Failed to reconnect websocket");
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn subscribe_request(&self, params: Value) -> Result<U256, ReconnectingWsError> {
+        let server_id: U256 = self
+            .request_with_reconnect("eth_subscribe", &params)
+            .await?;
+        let id = {
+            let mut next = self.shared.next_subscription_id.lock().unwrap();
+            let id = *next;
+            *next += U256::one();
+            id
+        };
+        self.shared.subscriptions.lock().unwrap().insert(
+            id,
+            Subscription {
+                params,
+                server_id,
+                sink: None,
+            },
+        );
+        Ok(id)
+    }
+
+    async fn unsubscribe_request(&self, params: Value) -> Result<bool, ReconnectingWsError> {
+        let id: U256 = serde_json::from_value(params[0].clone())?;
+        let server_id = self
+            .shared
+            .subscriptions
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .ok_or(ReconnectingWsError::UnknownSubscription(id))?
+            .server_id;
+        self.request_with_reconnect("eth_unsubscribe", &serde_json::json!([server_id]))
+            .await
+    }
+}
+
+impl Shared {
+    fn current(&self) -> (Ws, u64) {
+        self.ws.read().unwrap().clone()
+    }
+
+    /// Replace the connection, unless it was already replaced since
+    /// `failed_generation`, and re-establish every subscription on the new
+    /// connection.
+    async fn reconnect(
+        self: &Arc<Self>,
+        failed_generation: u64,
+    ) -> Result<(), ReconnectingWsError> {
+        let _guard = self.reconnecting.lock().await;
+        if self.current().1 != failed_generation {
+            return Ok(());
+        }
+
+        info!(url = %self.url, "Reconnecting websocket");
+        let ws = Ws::connect(self.url.as_str()).await?;
+
+        // Only swap in the new connection once every subscription has been
+        // re-established on it, so a failure here is retried in full.
+        let subscriptions: Vec<(U256, Value)> = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, sub)| (*id, sub.params.clone()))
+            .collect();
+        let mut resubscribed = vec![];
+        for (id, params) in subscriptions {
+            let server_id: U256 = ws.request("eth_subscribe", &params).await?;
+            let stream = ws.subscribe(server_id)?;
+            resubscribed.push((id, server_id, stream));
+        }
+
+        let generation = failed_generation + 1;
+        *self.ws.write().unwrap() = (ws, generation);
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        for (id, server_id, stream) in resubscribed {
+            // The subscription may have been dropped while reconnecting
+            if let Some(sub) = subscriptions.get_mut(&id) {
+                sub.server_id = server_id;
+                if let Some(sink) = &sub.sink {
+                    spawn_forwarder(self, id, generation, stream, sink.clone());
+                }
+            }
+        }
+        debug!(generation, "Websocket reconnected");
+        Ok(())
+    }
+}
+
+/// Forward notifications from `stream` to `sink` until the connection drops,
+/// then reconnect.
+fn spawn_forwarder(
+    shared: &Arc<Shared>,
+    id: U256,
+    generation: u64,
+    mut stream: <Ws as PubsubClient>::NotificationStream,
+    sink: mpsc::UnboundedSender<Value>,
+) {
+    let shared: Weak<Shared> = Arc::downgrade(shared);
+    tokio::spawn(async move {
+        while let Some(notification) = stream.next().await {
+            if sink.unbounded_send(notification).is_err() {
+                // Nobody is listening anymore
+                return;
+            }
+        }
+
+        let mut backoff = RESUBSCRIBE_BASE_BACKOFF;
+        loop {
+            let shared = match shared.upgrade() {
+                Some(shared) => shared,
+                None => return,
+            };
+            if !shared.subscriptions.lock().unwrap().contains_key(&id) {
+                return;
+            }
+            match shared.reconnect(generation).await {
+                // The new connection forwards the subscription
+                Ok(()) => return,
+                Err(e) => {
+                    warn!(error = %e, ?backoff, "Failed to reconnect websocket subscription");
+                }
+            }
+            drop(shared);
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(RESUBSCRIBE_MAX_BACKOFF);
+        }
+    });
+}
+
+#[async_trait]
+impl JsonRpcClient for ReconnectingWs {
+    type Error = ReconnectingWsError;
+
+    #[instrument(level = "debug", skip_all, fields(method = %method))]
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params)?;
+        match method {
+            "eth_subscribe" => {
+                let id = self.subscribe_request(params).await?;
+                Ok(serde_json::from_value(serde_json::to_value(id)?)?)
+            }
+            "eth_unsubscribe" => {
+                let res = self.unsubscribe_request(params).await?;
+                Ok(serde_json::from_value(Value::Bool(res))?)
+            }
+            _ => self.request_with_reconnect(method, &params).await,
+        }
+    }
+}
+
+impl PubsubClient for ReconnectingWs {
+    type NotificationStream = mpsc::UnboundedReceiver<Value>;
+
+    fn subscribe<T: Into<U256>>(&self, id: T) -> Result<Self::NotificationStream, Self::Error> {
+        let id = id.into();
+        let (ws, generation) = self.shared.current();
+        let mut subscriptions = self.shared.subscriptions.lock().unwrap();
+        let sub = subscriptions
+            .get_mut(&id)
+            .ok_or(ReconnectingWsError::UnknownSubscription(id))?;
+        let stream = ws.subscribe(sub.server_id)?;
+        let (sink, notifications) = mpsc::unbounded();
+        sub.sink = Some(sink.clone());
+        spawn_forwarder(&self.shared, id, generation, stream, sink);
+        Ok(notifications)
+    }
+
+    fn unsubscribe<T: Into<U256>>(&self, id: T) -> Result<(), Self::Error> {
+        let id = id.into();
+        let sub = self.shared.subscriptions.lock().unwrap().remove(&id);
+        match sub {
+            Some(sub) => Ok(self.shared.current().0.unsubscribe(sub.server_id)?),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    use ethers::types::U64;
+    use futures::SinkExt;
+    use serde_json::json;
+    use warp::ws::{Message, WebSocket};
+    use warp::Filter;
+
+    use super::*;
+    use crate::RetryingProvider;
+
+    #[derive(Default)]
+    struct ServerState {
+        connections: AtomicU64,
+        /// Close the connection instead of answering the next request
+        drop_next: AtomicBool,
+    }
+
+    /// Answer every request with the number of the connection it was made on,
+    /// and `test_notify` with a notification on subscription `0x1` too.
+    async fn serve(socket: WebSocket, state: Arc<ServerState>) {
+        let connection = state.connections.fetch_add(1, Ordering::SeqCst) + 1;
+        let (mut tx, mut rx) = socket.split();
+        while let Some(Ok(msg)) = rx.next().await {
+            let request: Value = match msg.to_str() {
+                Ok(text) => serde_json::from_str(text).unwrap(),
+                Err(_) => continue,
+            };
+            if state.drop_next.swap(false, Ordering::SeqCst) {
+                return;
+            }
+            let result = match request["method"].as_str().unwrap() {
+                "eth_subscribe" => json!("0x1"),
+                "eth_unsubscribe" => json!(true),
+                "test_notify" => {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "eth_subscription",
+                        "params": {"subscription": "0x1", "result": connection},
+                    });
+                    tx.send(Message::text(notification.to_string()))
+                        .await
+                        .unwrap();
+                    json!(true)
+                }
+                _ => json!(U64::from(connection)),
+            };
+            let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+            tx.send(Message::text(response.to_string())).await.unwrap();
+        }
+    }
+
+    fn spawn_server() -> (String, Arc<ServerState>) {
+        let state = Arc::new(ServerState::default());
+        let route_state = state.clone();
+        let route = warp::ws().map(move |ws: warp::ws::Ws| {
+            let state = route_state.clone();
+            ws.on_upgrade(move |socket| serve(socket, state))
+        });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("ws://{}", addr), state)
+    }
+
+    #[tokio::test]
+    async fn it_reconnects_and_resubscribes() {
+        let (url, state) = spawn_server();
+        let client = RetryingProvider::new(ReconnectingWs::connect(url).await.unwrap(), 3, 10);
+
+        let connection: U64 = client.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(connection, U64::from(1));
+
+        let id: U256 = client.request("eth_subscribe", ["newHeads"]).await.unwrap();
+        let mut notifications = client.subscribe(id).unwrap();
+        let _: bool = client.request("test_notify", ()).await.unwrap();
+        assert_eq!(notifications.next().await.unwrap(), json!(1));
+
+        // The dropped request is retried on a new connection
+        state.drop_next.store(true, Ordering::SeqCst);
+        let connection: U64 = client.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(connection, U64::from(2));
+
+        // The subscription was re-established on the new connection
+        let _: bool = client.request("test_notify", ()).await.unwrap();
+        assert_eq!(notifications.next().await.unwrap(), json!(2));
+
+        // Unsafe methods are not retried, but the connection is still replaced
+        state.drop_next.store(true, Ordering::SeqCst);
+        let res: Result<Value, _> = client.request("eth_sendRawTransaction", ["0x00"]).await;
+        assert!(res.is_err());
+        let connection: U64 = client.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(connection, U64::from(3));
+    }
+}
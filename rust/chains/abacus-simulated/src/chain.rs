@@ -0,0 +1,495 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use abacus_core::{
+    accumulator::{incremental::IncrementalMerkle, merkle::Proof},
+    AbacusMessage, Checkpoint, CheckpointMeta, CheckpointWithMeta, Encode, InterchainGasPayment,
+    InterchainGasPaymentMeta, InterchainGasPaymentWithMeta, Message, MessageDispatchMeta,
+    MessageStatus, MultisigSignedCheckpoint, OutboxState, RawCommittedMessage,
+    RawCommittedMessageWithMeta, SignedCheckpoint, TxOutcome,
+};
+use ethers::core::types::{Address, H256, U256};
+use ethers::core::utils::keccak256;
+
+use crate::{SimulatedChainError, SimulatedClock};
+
+/// A dispatched message and the transaction and block it was dispatched in
+#[derive(Debug)]
+struct DispatchedMessage {
+    message: RawCommittedMessage,
+    transaction_hash: H256,
+    block_number: u64,
+    timestamp: u64,
+}
+
+/// A gas payment and the transaction and block it was made in
+#[derive(Debug)]
+struct GasPayment {
+    leaf_index: u32,
+    amount: U256,
+    transaction_hash: H256,
+    block_number: u64,
+}
+
+#[derive(Debug)]
+struct OutboxStorage {
+    state: OutboxState,
+    tree: IncrementalMerkle,
+    messages: Vec<DispatchedMessage>,
+    /// The root of the tree after each message was dispatched
+    roots: Vec<H256>,
+    cached_checkpoints: Vec<CheckpointWithMeta>,
+}
+
+impl Default for OutboxStorage {
+    fn default() -> Self {
+        Self {
+            state: OutboxState::Active,
+            tree: Default::default(),
+            messages: vec![],
+            roots: vec![],
+            cached_checkpoints: vec![],
+        }
+    }
+}
+
+#[derive(Debug)]
+struct InboxStorage {
+    remote_domain: u32,
+    /// The leaves of the processed messages and the blocks they were
+    /// processed in
+    processed: HashMap<H256, u64>,
+}
+
+#[derive(Debug)]
+struct ValidatorManagerStorage {
+    inbox: Address,
+    validators: HashSet<Address>,
+    threshold: usize,
+}
+
+#[derive(Debug, Default)]
+struct ChainState {
+    outbox: OutboxStorage,
+    inboxes: HashMap<Address, InboxStorage>,
+    validator_managers: HashMap<Address, ValidatorManagerStorage>,
+    gas_payments: Vec<GasPayment>,
+    txs: HashMap<H256, TxOutcome>,
+    nonce: u64,
+}
+
+/// The state of a simulated chain: a single outbox and interchain gas
+/// paymaster, and any number of inboxes and inbox validator managers.
+#[derive(Debug)]
+pub struct SimulatedChain {
+    domain: u32,
+    clock: Arc<SimulatedClock>,
+    state: Mutex<ChainState>,
+}
+
+impl SimulatedChain {
+    pub(crate) fn new(domain: u32, clock: Arc<SimulatedClock>) -> Self {
+        Self {
+            domain,
+            clock,
+            state: Default::default(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<ChainState> {
+        self.state.lock().expect("simulated chain state poisoned")
+    }
+
+    /// The chain's domain
+    pub fn domain(&self) -> u32 {
+        self.domain
+    }
+
+    /// The current block number
+    pub fn block_number(&self) -> u64 {
+        self.clock.block_number()
+    }
+
+    /// The hash of the block at `block_number`, if it has been mined.
+    /// Simulated chains never reorg, so hashes are derived from the number.
+    pub fn block_hash(&self, block_number: u64) -> Option<H256> {
+        if block_number > self.block_number() {
+            return None;
+        }
+        let mut preimage = self.domain.to_be_bytes().to_vec();
+        preimage.extend(block_number.to_be_bytes());
+        Some(keccak256(preimage).into())
+    }
+
+    /// Record an executed transaction and return its outcome
+    fn record_tx(&self, state: &mut ChainState) -> TxOutcome {
+        state.nonce += 1;
+        let mut preimage = self.domain.to_be_bytes().to_vec();
+        preimage.extend(state.nonce.to_be_bytes());
+        let outcome = TxOutcome {
+            txid: keccak256(preimage).into(),
+            executed: true,
+        };
+        state.txs.insert(outcome.txid, outcome);
+        outcome
+    }
+
+    /// Deterministically derive the address of the next deployed contract
+    fn next_address(state: &mut ChainState) -> Address {
+        state.nonce += 1;
+        Address::from_low_u64_be(state.nonce)
+    }
+
+    /// Deploy an inbox for messages from `remote_domain`
+    pub fn deploy_inbox(&self, remote_domain: u32) -> Address {
+        let mut state = self.state();
+        let address = Self::next_address(&mut state);
+        state.inboxes.insert(
+            address,
+            InboxStorage {
+                remote_domain,
+                processed: Default::default(),
+            },
+        );
+        address
+    }
+
+    /// Deploy a validator manager for `inbox` that requires signatures from
+    /// `threshold` of `validators`
+    pub fn deploy_inbox_validator_manager(
+        &self,
+        inbox: Address,
+        validators: impl IntoIterator<Item = Address>,
+        threshold: usize,
+    ) -> Address {
+        let mut state = self.state();
+        let address = Self::next_address(&mut state);
+        state.validator_managers.insert(
+            address,
+            ValidatorManagerStorage {
+                inbox,
+                validators: validators.into_iter().collect(),
+                threshold,
+            },
+        );
+        address
+    }
+
+    /// Put the outbox into the failed state, as if fraud was proven
+    pub fn fail_outbox(&self) {
+        self.state().outbox.state = OutboxState::Failed;
+    }
+
+    /// Pay for the gas of the message at `leaf_index`
+    pub fn pay_for_gas(&self, leaf_index: u32, amount: U256) -> TxOutcome {
+        let mut state = self.state();
+        let outcome = self.record_tx(&mut state);
+        state.gas_payments.push(GasPayment {
+            leaf_index,
+            amount,
+            transaction_hash: outcome.txid,
+            block_number: self.block_number(),
+        });
+        outcome
+    }
+
+    /// The outcome of a transaction, if it was executed
+    pub(crate) fn tx_status(&self, txid: H256) -> Option<TxOutcome> {
+        self.state().txs.get(&txid).copied()
+    }
+
+    pub(crate) fn outbox_state(&self) -> OutboxState {
+        self.state().outbox.state
+    }
+
+    pub(crate) fn outbox_count(&self) -> u32 {
+        self.state().outbox.tree.count() as u32
+    }
+
+    pub(crate) fn dispatch(
+        &self,
+        sender: H256,
+        message: &Message,
+    ) -> Result<TxOutcome, SimulatedChainError> {
+        let mut state = self.state();
+        if state.outbox.state == OutboxState::Failed {
+            return Err(SimulatedChainError::OutboxFailed);
+        }
+        let leaf_index = state.outbox.tree.count() as u32;
+        let message = AbacusMessage {
+            origin: self.domain,
+            sender,
+            destination: message.destination,
+            recipient: message.recipient,
+            body: message.body.clone(),
+        };
+        state.outbox.tree.ingest(message.to_leaf(leaf_index));
+        let root = state.outbox.tree.root();
+        state.outbox.roots.push(root);
+        let outcome = self.record_tx(&mut state);
+        state.outbox.messages.push(DispatchedMessage {
+            message: RawCommittedMessage {
+                leaf_index,
+                message: message.to_vec(),
+            },
+            transaction_hash: outcome.txid,
+            block_number: self.block_number(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+        });
+        Ok(outcome)
+    }
+
+    /// The latest checkpoint as of `block_number`
+    pub(crate) fn checkpoint_at(
+        &self,
+        block_number: u64,
+    ) -> Result<Checkpoint, SimulatedChainError> {
+        let state = self.state();
+        let count = state
+            .outbox
+            .messages
+            .iter()
+            .take_while(|m| m.block_number <= block_number)
+            .count();
+        if count == 0 {
+            return Err(SimulatedChainError::EmptyOutbox);
+        }
+        Ok(Checkpoint {
+            outbox_domain: self.domain,
+            root: state.outbox.roots[count - 1],
+            index: count as u32 - 1,
+        })
+    }
+
+    pub(crate) fn cache_checkpoint(&self) -> Result<TxOutcome, SimulatedChainError> {
+        let block_number = self.block_number();
+        let checkpoint = self.checkpoint_at(block_number)?;
+        let mut state = self.state();
+        state.outbox.cached_checkpoints.push(CheckpointWithMeta {
+            checkpoint,
+            metadata: CheckpointMeta { block_number },
+        });
+        Ok(self.record_tx(&mut state))
+    }
+
+    pub(crate) fn latest_cached_checkpoint(&self) -> Checkpoint {
+        self.state()
+            .outbox
+            .cached_checkpoints
+            .last()
+            .map(|c| c.checkpoint)
+            .unwrap_or(Checkpoint {
+                outbox_domain: self.domain,
+                root: H256::zero(),
+                index: 0,
+            })
+    }
+
+    pub(crate) fn messages_in_blocks(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Vec<RawCommittedMessageWithMeta> {
+        self.state()
+            .outbox
+            .messages
+            .iter()
+            .filter(|m| (from..=to).contains(&m.block_number))
+            .map(|m| RawCommittedMessageWithMeta {
+                message: m.message.clone(),
+                meta: MessageDispatchMeta {
+                    block_number: m.block_number,
+                    block_hash: self.block_hash(m.block_number).unwrap_or_default(),
+                    timestamp: m.timestamp,
+                    transaction_hash: m.transaction_hash,
+                    log_index: U256::zero(),
+                },
+            })
+            .collect()
+    }
+
+    pub(crate) fn cached_checkpoints_in_blocks(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Vec<CheckpointWithMeta> {
+        self.state()
+            .outbox
+            .cached_checkpoints
+            .iter()
+            .filter(|c| (from..=to).contains(&c.metadata.block_number))
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn gas_payments_in_blocks(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Vec<InterchainGasPaymentWithMeta> {
+        self.state()
+            .gas_payments
+            .iter()
+            .filter(|p| (from..=to).contains(&p.block_number))
+            .map(|p| InterchainGasPaymentWithMeta {
+                payment: InterchainGasPayment {
+                    leaf_index: p.leaf_index,
+                    amount: p.amount,
+                },
+                meta: InterchainGasPaymentMeta {
+                    transaction_hash: p.transaction_hash,
+                    log_index: U256::zero(),
+                },
+            })
+            .collect()
+    }
+
+    pub(crate) fn inbox_remote_domain(&self, inbox: Address) -> Result<u32, SimulatedChainError> {
+        self.state()
+            .inboxes
+            .get(&inbox)
+            .map(|i| i.remote_domain)
+            .ok_or(SimulatedChainError::UnknownContract {
+                kind: "inbox",
+                address: inbox,
+            })
+    }
+
+    pub(crate) fn message_status(
+        &self,
+        inbox: Address,
+        leaf: H256,
+    ) -> Result<MessageStatus, SimulatedChainError> {
+        let state = self.state();
+        let inbox = state
+            .inboxes
+            .get(&inbox)
+            .ok_or(SimulatedChainError::UnknownContract {
+                kind: "inbox",
+                address: inbox,
+            })?;
+        Ok(if inbox.processed.contains_key(&leaf) {
+            MessageStatus::Processed
+        } else {
+            MessageStatus::None
+        })
+    }
+
+    /// The leaves of the messages processed by `inbox` between blocks `from`
+    /// and `to`, inclusive, ordered by the block they were processed in
+    pub(crate) fn processed_in_blocks(
+        &self,
+        inbox: Address,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<H256>, SimulatedChainError> {
+        let state = self.state();
+        let inbox = state
+            .inboxes
+            .get(&inbox)
+            .ok_or(SimulatedChainError::UnknownContract {
+                kind: "inbox",
+                address: inbox,
+            })?;
+        let mut processed = inbox
+            .processed
+            .iter()
+            .filter(|(_, block_number)| (from..=to).contains(*block_number))
+            .map(|(leaf, block_number)| (*block_number, *leaf))
+            .collect::<Vec<_>>();
+        processed.sort();
+        Ok(processed.into_iter().map(|(_, leaf)| leaf).collect())
+    }
+
+    /// The inbox a validator manager is deployed for
+    pub(crate) fn validator_manager_inbox(
+        &self,
+        validator_manager: Address,
+    ) -> Result<Address, SimulatedChainError> {
+        self.state()
+            .validator_managers
+            .get(&validator_manager)
+            .map(|v| v.inbox)
+            .ok_or(SimulatedChainError::UnknownContract {
+                kind: "inbox validator manager",
+                address: validator_manager,
+            })
+    }
+
+    /// Verify the checkpoint's signatures and the message's proof against it,
+    /// then mark the message as processed
+    pub(crate) fn process(
+        &self,
+        validator_manager: Address,
+        multisig_signed_checkpoint: &MultisigSignedCheckpoint,
+        message: &AbacusMessage,
+        proof: &Proof,
+    ) -> Result<TxOutcome, SimulatedChainError> {
+        let mut state = self.state();
+        let manager = state.validator_managers.get(&validator_manager).ok_or(
+            SimulatedChainError::UnknownContract {
+                kind: "inbox validator manager",
+                address: validator_manager,
+            },
+        )?;
+        let inbox_address = manager.inbox;
+
+        let checkpoint = multisig_signed_checkpoint.checkpoint;
+        let signers = multisig_signed_checkpoint
+            .signatures
+            .iter()
+            .filter_map(|signature| {
+                SignedCheckpoint {
+                    checkpoint,
+                    signature: *signature,
+                }
+                .recover()
+                .ok()
+            })
+            .filter(|signer| manager.validators.contains(signer))
+            .collect::<HashSet<_>>()
+            .len();
+        if signers < manager.threshold {
+            return Err(SimulatedChainError::InsufficientSignatures {
+                signers,
+                threshold: manager.threshold,
+            });
+        }
+
+        let inbox =
+            state
+                .inboxes
+                .get(&inbox_address)
+                .ok_or(SimulatedChainError::UnknownContract {
+                    kind: "inbox",
+                    address: inbox_address,
+                })?;
+        if message.destination != self.domain {
+            return Err(SimulatedChainError::WrongDestination(message.destination));
+        }
+        if checkpoint.outbox_domain != inbox.remote_domain || message.origin != inbox.remote_domain
+        {
+            return Err(SimulatedChainError::WrongOrigin(checkpoint.outbox_domain));
+        }
+        let leaf = message.to_leaf(proof.index as u32);
+        if proof.leaf != leaf || proof.root() != checkpoint.root {
+            return Err(SimulatedChainError::InvalidProof(leaf));
+        }
+        if inbox.processed.contains_key(&leaf) {
+            return Err(SimulatedChainError::AlreadyProcessed(leaf));
+        }
+
+        let block_number = self.block_number();
+        state
+            .inboxes
+            .get_mut(&inbox_address)
+            .expect("inbox exists")
+            .processed
+            .insert(leaf, block_number);
+        Ok(self.record_tx(&mut state))
+    }
+}
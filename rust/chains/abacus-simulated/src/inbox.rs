@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::core::types::{Address, H256};
+use eyre::Result;
+
+use abacus_core::{
+    AbacusCommon, AbacusContract, ChainCommunicationError, ContractLocator, Inbox, InboxIndexer,
+    Indexer, MessageStatus, TxOutcome,
+};
+
+use crate::{SimulatedChain, SimulatedNetwork};
+
+/// An inbox deployed on a simulated chain
+#[derive(Debug)]
+pub struct SimulatedInbox {
+    chain: Arc<SimulatedChain>,
+    chain_name: String,
+    address: Address,
+    validator_manager: Address,
+}
+
+impl SimulatedInbox {
+    /// Create a reference to the inbox at `locator.address` on the simulated
+    /// chain at `locator.domain`
+    pub fn new(
+        network: &SimulatedNetwork,
+        locator: &ContractLocator,
+        validator_manager: Address,
+    ) -> Self {
+        Self {
+            chain: network.chain(locator.domain),
+            chain_name: locator.chain_name.clone(),
+            address: (&locator.address).into(),
+            validator_manager,
+        }
+    }
+}
+
+impl AbacusContract for SimulatedInbox {
+    fn chain_name(&self) -> &str {
+        &self.chain_name
+    }
+}
+
+#[async_trait]
+impl AbacusCommon for SimulatedInbox {
+    fn local_domain(&self) -> u32 {
+        self.chain.domain()
+    }
+
+    async fn status(&self, txid: H256) -> Result<Option<TxOutcome>, ChainCommunicationError> {
+        Ok(self.chain.tx_status(txid))
+    }
+
+    async fn validator_manager(&self) -> Result<H256, ChainCommunicationError> {
+        Ok(self.validator_manager.into())
+    }
+}
+
+#[async_trait]
+impl Inbox for SimulatedInbox {
+    async fn remote_domain(&self) -> Result<u32, ChainCommunicationError> {
+        Ok(self.chain.inbox_remote_domain(self.address)?)
+    }
+
+    async fn message_status(&self, leaf: H256) -> Result<MessageStatus, ChainCommunicationError> {
+        Ok(self.chain.message_status(self.address, leaf)?)
+    }
+}
+
+/// Indexes the messages processed by an inbox deployed on a simulated chain
+#[derive(Debug)]
+pub struct SimulatedInboxIndexer {
+    chain: Arc<SimulatedChain>,
+    address: Address,
+    finality_blocks: u32,
+}
+
+impl SimulatedInboxIndexer {
+    /// Create an indexer for the inbox at `locator.address` on the simulated
+    /// chain at `locator.domain`
+    pub fn new(
+        network: &SimulatedNetwork,
+        locator: &ContractLocator,
+        finality_blocks: u32,
+    ) -> Self {
+        Self {
+            chain: network.chain(locator.domain),
+            address: (&locator.address).into(),
+            finality_blocks,
+        }
+    }
+}
+
+#[async_trait]
+impl Indexer for SimulatedInboxIndexer {
+    async fn get_finalized_block_number(&self) -> Result<u32> {
+        Ok((self.chain.block_number() as u32).saturating_sub(self.finality_blocks))
+    }
+
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        Ok(self.chain.block_number() as u32)
+    }
+
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        Ok(self.chain.block_hash(block_number as u64))
+    }
+}
+
+#[async_trait]
+impl InboxIndexer for SimulatedInboxIndexer {
+    async fn fetch_processed_messages(&self, from: u32, to: u32) -> Result<Vec<H256>> {
+        Ok(self
+            .chain
+            .processed_in_blocks(self.address, from as u64, to as u64)?)
+    }
+}
@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::core::types::H256;
+use eyre::Result;
+
+use abacus_core::{
+    AbacusContract, ContractLocator, Indexer, InterchainGasPaymaster,
+    InterchainGasPaymasterIndexer, InterchainGasPaymentWithMeta,
+};
+
+use crate::{SimulatedChain, SimulatedNetwork};
+
+/// The interchain gas paymaster of a simulated chain
+#[derive(Debug)]
+pub struct SimulatedInterchainGasPaymaster {
+    chain_name: String,
+}
+
+impl SimulatedInterchainGasPaymaster {
+    /// Create a reference to the interchain gas paymaster of the simulated
+    /// chain at `locator.domain`. Payments are made with
+    /// `SimulatedChain::pay_for_gas`.
+    pub fn new(locator: &ContractLocator) -> Self {
+        Self {
+            chain_name: locator.chain_name.clone(),
+        }
+    }
+}
+
+impl AbacusContract for SimulatedInterchainGasPaymaster {
+    fn chain_name(&self) -> &str {
+        &self.chain_name
+    }
+}
+
+impl InterchainGasPaymaster for SimulatedInterchainGasPaymaster {}
+
+/// Indexes the gas payments of a simulated interchain gas paymaster
+#[derive(Debug)]
+pub struct SimulatedInterchainGasPaymasterIndexer {
+    chain: Arc<SimulatedChain>,
+    finality_blocks: u32,
+}
+
+impl SimulatedInterchainGasPaymasterIndexer {
+    /// Create an indexer for the interchain gas paymaster of the simulated
+    /// chain at `locator.domain`
+    pub fn new(
+        network: &SimulatedNetwork,
+        locator: &ContractLocator,
+        finality_blocks: u32,
+    ) -> Self {
+        Self {
+            chain: network.chain(locator.domain),
+            finality_blocks,
+        }
+    }
+}
+
+#[async_trait]
+impl Indexer for SimulatedInterchainGasPaymasterIndexer {
+    async fn get_finalized_block_number(&self) -> Result<u32> {
+        Ok((self.chain.block_number() as u32).saturating_sub(self.finality_blocks))
+    }
+
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        Ok(self.chain.block_number() as u32)
+    }
+
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        Ok(self.chain.block_hash(block_number as u64))
+    }
+}
+
+#[async_trait]
+impl InterchainGasPaymasterIndexer for SimulatedInterchainGasPaymasterIndexer {
+    async fn fetch_gas_payments(
+        &self,
+        from_block: u32,
+        to_block: u32,
+    ) -> Result<Vec<InterchainGasPaymentWithMeta>> {
+        Ok(self
+            .chain
+            .gas_payments_in_blocks(from_block as u64, to_block as u64))
+    }
+}
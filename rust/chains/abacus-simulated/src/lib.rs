@@ -0,0 +1,250 @@
+//! In-memory simulated chains, for running agents end-to-end without a node.
+//!
+//! A `SimulatedNetwork` is a named, process-wide set of chains sharing a
+//! clock. Contracts built from a `SimulatedConnection` with the same network
+//! name observe and mutate the same state, so multiple agents in one process
+//! interact with each other just as they would through real chains.
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+#![warn(unused_extern_crates)]
+
+use abacus_core::ChainCommunicationError;
+use ethers::core::types::{Address, H256};
+
+pub use crate::{
+    chain::*, inbox::*, interchain_gas::*, network::*, outbox::*, validator_manager::*,
+};
+
+/// Simulated chain state and deployments
+mod chain;
+
+/// Shared clock and network registry
+mod network;
+
+/// Outbox and outbox indexer
+mod outbox;
+
+/// Inbox
+mod inbox;
+
+/// InboxValidatorManager
+mod validator_manager;
+
+/// InterchainGasPaymaster and its indexer
+mod interchain_gas;
+
+/// Simulated chain connection configuration
+#[derive(Debug, serde::Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedConnection {
+    /// The name of the simulated network the chain belongs to
+    pub network: String,
+}
+
+/// Errors returned by simulated contracts
+#[derive(Debug, thiserror::Error)]
+pub enum SimulatedChainError {
+    /// The outbox has failed and no longer accepts messages
+    #[error("Outbox has failed")]
+    OutboxFailed,
+    /// No contract of the expected kind is deployed at the address
+    #[error("No simulated {kind} deployed at {address:?}")]
+    UnknownContract {
+        /// The kind of contract
+        kind: &'static str,
+        /// The address of the contract
+        address: Address,
+    },
+    /// The outbox has no messages to checkpoint
+    #[error("Outbox has no messages")]
+    EmptyOutbox,
+    /// The message is not destined for this chain
+    #[error("Message is destined for domain {0}")]
+    WrongDestination(u32),
+    /// The checkpoint is not for the inbox's remote domain
+    #[error("Checkpoint is for domain {0}")]
+    WrongOrigin(u32),
+    /// Fewer than the threshold of validators signed the checkpoint
+    #[error("Checkpoint has {signers} of {threshold} required validator signatures")]
+    InsufficientSignatures {
+        /// Number of distinct validators that signed
+        signers: usize,
+        /// Number of validator signatures required
+        threshold: usize,
+    },
+    /// The proof does not prove the message against the checkpoint
+    #[error("Invalid proof for leaf {0:?}")]
+    InvalidProof(H256),
+    /// The message was already processed
+    #[error("Message {0:?} was already processed")]
+    AlreadyProcessed(H256),
+}
+
+impl From<SimulatedChainError> for ChainCommunicationError {
+    fn from(e: SimulatedChainError) -> Self {
+        ChainCommunicationError::CustomError(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use abacus_core::{
+        accumulator::{
+            merkle::{MerkleTree, Proof},
+            TREE_DEPTH,
+        },
+        AbacusMessage, ContractLocator, Decode, Inbox, InboxIndexer, InboxValidatorManager,
+        Indexer, Message, MessageStatus, MultisigSignedCheckpoint, Outbox, OutboxIndexer,
+    };
+    use ethers::signers::{LocalWallet, Signer};
+
+    use super::*;
+
+    fn locator(domain: u32, address: Address) -> ContractLocator {
+        ContractLocator {
+            chain_name: format!("chain-{}", domain),
+            domain,
+            address: address.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_relays_a_message_between_chains() {
+        let network = SimulatedNetwork::create("relay-test", std::time::Duration::from_secs(3600));
+        let validators: Vec<LocalWallet> = [
+            "1111111111111111111111111111111111111111111111111111111111111111",
+            "2222222222222222222222222222222222222222222222222222222222222222",
+        ]
+        .iter()
+        .map(|key| key.parse().unwrap())
+        .collect();
+
+        let destination = network.chain(2000);
+        let inbox_address = destination.deploy_inbox(1000);
+        let validator_manager_address = destination.deploy_inbox_validator_manager(
+            inbox_address,
+            validators.iter().map(|v| v.address()),
+            2,
+        );
+
+        let outbox = SimulatedOutbox::new(&network, &locator(1000, Address::zero()), None);
+        let indexer = SimulatedOutboxIndexer::new(&network, &locator(1000, Address::zero()), 1);
+        let inbox = SimulatedInbox::new(
+            &network,
+            &locator(2000, inbox_address),
+            validator_manager_address,
+        );
+        let inbox_indexer = SimulatedInboxIndexer::new(&network, &locator(2000, inbox_address), 1);
+        let validator_manager = SimulatedInboxValidatorManager::new(
+            &network,
+            &locator(2000, validator_manager_address),
+        );
+
+        network.clock().mine(5);
+        assert!(outbox.latest_checkpoint(None).await.is_err());
+        for body in [b"first".to_vec(), b"second".to_vec()] {
+            outbox
+                .dispatch(&Message {
+                    destination: 2000,
+                    recipient: H256::repeat_byte(1),
+                    body,
+                })
+                .await
+                .unwrap();
+        }
+        assert_eq!(outbox.count().await.unwrap(), 2);
+
+        // Messages are only indexed once final
+        let block = network.clock().block_number() as u32;
+        let finalized = indexer.get_finalized_block_number().await.unwrap();
+        assert!(finalized < block);
+        assert!(indexer
+            .fetch_sorted_messages(0, finalized)
+            .await
+            .unwrap()
+            .is_empty());
+        network.clock().mine(1);
+        assert_eq!(indexer.get_finalized_block_number().await.unwrap(), block);
+        let raw_messages = indexer.fetch_sorted_messages(0, block).await.unwrap();
+        assert_eq!(raw_messages.len(), 2);
+        assert!(raw_messages
+            .iter()
+            .all(|m| m.meta.block_number <= block as u64));
+
+        let checkpoint = outbox.latest_checkpoint(None).await.unwrap();
+        assert_eq!(checkpoint.index, 1);
+        let mut signatures = vec![];
+        for validator in &validators {
+            signatures.push(checkpoint.sign_with(validator).await.unwrap().signature);
+        }
+
+        let messages: Vec<AbacusMessage> = raw_messages
+            .iter()
+            .map(|m| AbacusMessage::read_from(&mut m.message.message.as_slice()).unwrap())
+            .collect();
+        let leaves: Vec<H256> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, m)| m.to_leaf(i as u32))
+            .collect();
+        let tree = MerkleTree::create(&leaves, TREE_DEPTH);
+        let (leaf, path) = tree.generate_proof(0, TREE_DEPTH);
+        let proof = Proof {
+            leaf,
+            index: 0,
+            path: path.try_into().unwrap(),
+        };
+        assert_eq!(proof.root(), checkpoint.root);
+
+        // One signature is below the threshold
+        let under_signed = MultisigSignedCheckpoint {
+            checkpoint,
+            signatures: signatures[..1].to_vec(),
+        };
+        assert!(validator_manager
+            .process(&under_signed, &messages[0], &proof)
+            .await
+            .is_err());
+
+        let signed = MultisigSignedCheckpoint {
+            checkpoint,
+            signatures,
+        };
+        let outcome = validator_manager
+            .process(&signed, &messages[0], &proof)
+            .await
+            .unwrap();
+        assert!(outcome.executed);
+        assert_eq!(
+            inbox.message_status(leaf).await.unwrap(),
+            MessageStatus::Processed
+        );
+        assert_eq!(
+            inbox.message_status(leaves[1]).await.unwrap(),
+            MessageStatus::None
+        );
+        let block = network.clock().block_number() as u32;
+        assert_eq!(
+            inbox_indexer
+                .fetch_processed_messages(0, block)
+                .await
+                .unwrap(),
+            vec![leaf]
+        );
+        assert!(validator_manager
+            .process(&signed, &messages[0], &proof)
+            .await
+            .is_err());
+
+        network.chain(1000).fail_outbox();
+        assert!(outbox
+            .dispatch(&Message {
+                destination: 2000,
+                recipient: H256::repeat_byte(1),
+                body: vec![],
+            })
+            .await
+            .is_err());
+    }
+}
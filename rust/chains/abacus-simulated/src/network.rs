@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+use crate::SimulatedChain;
+
+/// Every simulated network in the process, by name
+static NETWORKS: Lazy<Mutex<HashMap<String, Arc<SimulatedNetwork>>>> = Lazy::new(Default::default);
+
+/// Block time of networks created implicitly from a connection
+pub const DEFAULT_BLOCK_TIME: Duration = Duration::from_secs(1);
+
+/// A clock shared by every chain of a network. A block is produced every
+/// `block_time`, and more can be mined on demand to fast-forward finality.
+#[derive(Debug)]
+pub struct SimulatedClock {
+    start: Instant,
+    block_time: Duration,
+    mined: AtomicU64,
+}
+
+impl SimulatedClock {
+    fn new(block_time: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            block_time,
+            mined: AtomicU64::new(0),
+        }
+    }
+
+    /// The current block number
+    pub fn block_number(&self) -> u64 {
+        let produced = self.start.elapsed().as_millis() / self.block_time.as_millis().max(1);
+        produced as u64 + self.mined.load(Ordering::SeqCst)
+    }
+
+    /// Immediately produce `blocks` blocks
+    pub fn mine(&self, blocks: u64) {
+        self.mined.fetch_add(blocks, Ordering::SeqCst);
+    }
+}
+
+/// A named set of simulated chains sharing a clock
+#[derive(Debug)]
+pub struct SimulatedNetwork {
+    name: String,
+    clock: Arc<SimulatedClock>,
+    chains: Mutex<HashMap<u32, Arc<SimulatedChain>>>,
+}
+
+impl SimulatedNetwork {
+    /// Create a network, replacing any existing network with the same name
+    pub fn create(name: &str, block_time: Duration) -> Arc<Self> {
+        let network = Arc::new(Self {
+            name: name.to_owned(),
+            clock: Arc::new(SimulatedClock::new(block_time)),
+            chains: Default::default(),
+        });
+        NETWORKS
+            .lock()
+            .expect("simulated network registry poisoned")
+            .insert(name.to_owned(), network.clone());
+        network
+    }
+
+    /// Get the network with `name`, creating it with the default block time
+    /// if it does not exist
+    pub fn get_or_create(name: &str) -> Arc<Self> {
+        let mut networks = NETWORKS
+            .lock()
+            .expect("simulated network registry poisoned");
+        networks
+            .entry(name.to_owned())
+            .or_insert_with(|| {
+                Arc::new(Self {
+                    name: name.to_owned(),
+                    clock: Arc::new(SimulatedClock::new(DEFAULT_BLOCK_TIME)),
+                    chains: Default::default(),
+                })
+            })
+            .clone()
+    }
+
+    /// The network's name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The network's clock
+    pub fn clock(&self) -> &SimulatedClock {
+        &self.clock
+    }
+
+    /// Get the chain with `domain`, creating it if it does not exist
+    pub fn chain(&self, domain: u32) -> Arc<SimulatedChain> {
+        self.chains
+            .lock()
+            .expect("simulated chains poisoned")
+            .entry(domain)
+            .or_insert_with(|| Arc::new(SimulatedChain::new(domain, self.clock.clone())))
+            .clone()
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::core::types::{Address, H256};
+use ethers::signers::Signer;
+use eyre::Result;
+
+use abacus_core::{
+    AbacusCommon, AbacusContract, ChainCommunicationError, Checkpoint, CheckpointWithMeta,
+    ContractLocator, Indexer, Message, Outbox, OutboxIndexer, OutboxState,
+    RawCommittedMessageWithMeta, Signers, TxOutcome,
+};
+
+use crate::{SimulatedChain, SimulatedNetwork};
+
+/// The outbox of a simulated chain
+#[derive(Debug)]
+pub struct SimulatedOutbox {
+    chain: Arc<SimulatedChain>,
+    chain_name: String,
+    sender: Address,
+}
+
+impl SimulatedOutbox {
+    /// Create a reference to the outbox of the simulated chain at
+    /// `locator.domain`. Messages are dispatched by the signer, if any.
+    pub fn new(
+        network: &SimulatedNetwork,
+        locator: &ContractLocator,
+        signer: Option<Signers>,
+    ) -> Self {
+        Self {
+            chain: network.chain(locator.domain),
+            chain_name: locator.chain_name.clone(),
+            sender: signer.map(|s| s.address()).unwrap_or_default(),
+        }
+    }
+}
+
+impl AbacusContract for SimulatedOutbox {
+    fn chain_name(&self) -> &str {
+        &self.chain_name
+    }
+}
+
+#[async_trait]
+impl AbacusCommon for SimulatedOutbox {
+    fn local_domain(&self) -> u32 {
+        self.chain.domain()
+    }
+
+    async fn status(&self, txid: H256) -> Result<Option<TxOutcome>, ChainCommunicationError> {
+        Ok(self.chain.tx_status(txid))
+    }
+
+    async fn validator_manager(&self) -> Result<H256, ChainCommunicationError> {
+        Ok(H256::zero())
+    }
+}
+
+#[async_trait]
+impl Outbox for SimulatedOutbox {
+    async fn state(&self) -> Result<OutboxState, ChainCommunicationError> {
+        Ok(self.chain.outbox_state())
+    }
+
+    async fn count(&self) -> Result<u32, ChainCommunicationError> {
+        Ok(self.chain.outbox_count())
+    }
+
+    async fn dispatch(&self, message: &Message) -> Result<TxOutcome, ChainCommunicationError> {
+        Ok(self.chain.dispatch(self.sender.into(), message)?)
+    }
+
+    async fn cache_checkpoint(&self) -> Result<TxOutcome, ChainCommunicationError> {
+        Ok(self.chain.cache_checkpoint()?)
+    }
+
+    async fn latest_cached_root(&self) -> Result<H256, ChainCommunicationError> {
+        Ok(self.chain.latest_cached_checkpoint().root)
+    }
+
+    async fn latest_cached_checkpoint(&self) -> Result<Checkpoint, ChainCommunicationError> {
+        Ok(self.chain.latest_cached_checkpoint())
+    }
+
+    async fn latest_checkpoint(
+        &self,
+        lag: Option<u64>,
+    ) -> Result<Checkpoint, ChainCommunicationError> {
+        let block_number = self
+            .chain
+            .block_number()
+            .saturating_sub(lag.unwrap_or_default());
+        Ok(self.chain.checkpoint_at(block_number)?)
+    }
+}
+
+/// Indexes the messages and cached checkpoints of a simulated outbox
+#[derive(Debug)]
+pub struct SimulatedOutboxIndexer {
+    chain: Arc<SimulatedChain>,
+    finality_blocks: u32,
+}
+
+impl SimulatedOutboxIndexer {
+    /// Create an indexer for the outbox of the simulated chain at
+    /// `locator.domain`
+    pub fn new(
+        network: &SimulatedNetwork,
+        locator: &ContractLocator,
+        finality_blocks: u32,
+    ) -> Self {
+        Self {
+            chain: network.chain(locator.domain),
+            finality_blocks,
+        }
+    }
+}
+
+#[async_trait]
+impl Indexer for SimulatedOutboxIndexer {
+    async fn get_finalized_block_number(&self) -> Result<u32> {
+        Ok((self.chain.block_number() as u32).saturating_sub(self.finality_blocks))
+    }
+
+    async fn get_latest_block_number(&self) -> Result<u32> {
+        Ok(self.chain.block_number() as u32)
+    }
+
+    async fn get_block_hash(&self, block_number: u32) -> Result<Option<H256>> {
+        Ok(self.chain.block_hash(block_number as u64))
+    }
+}
+
+#[async_trait]
+impl OutboxIndexer for SimulatedOutboxIndexer {
+    async fn fetch_sorted_messages(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
+        Ok(self.chain.messages_in_blocks(from as u64, to as u64))
+    }
+
+    async fn fetch_messages_by_leaf_index(
+        &self,
+        leaf_indices: &[u32],
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<RawCommittedMessageWithMeta>> {
+        Ok(self
+            .chain
+            .messages_in_blocks(from as u64, to as u64)
+            .into_iter()
+            .filter(|m| leaf_indices.contains(&m.message.leaf_index))
+            .collect())
+    }
+
+    async fn fetch_sorted_cached_checkpoints(
+        &self,
+        from: u32,
+        to: u32,
+    ) -> Result<Vec<CheckpointWithMeta>> {
+        Ok(self
+            .chain
+            .cached_checkpoints_in_blocks(from as u64, to as u64))
+    }
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::core::types::Address;
+use eyre::Result;
+
+use abacus_core::{
+    accumulator::merkle::Proof, AbacusMessage, ChainCommunicationError, ContractLocator,
+    InboxValidatorManager, MultisigSignedCheckpoint, TxOutcome,
+};
+
+use crate::{SimulatedChain, SimulatedNetwork};
+
+/// An inbox validator manager deployed on a simulated chain
+#[derive(Debug)]
+pub struct SimulatedInboxValidatorManager {
+    chain: Arc<SimulatedChain>,
+    address: Address,
+}
+
+impl SimulatedInboxValidatorManager {
+    /// Create a reference to the validator manager at `locator.address` on
+    /// the simulated chain at `locator.domain`
+    pub fn new(network: &SimulatedNetwork, locator: &ContractLocator) -> Self {
+        Self {
+            chain: network.chain(locator.domain),
+            address: (&locator.address).into(),
+        }
+    }
+}
+
+#[async_trait]
+impl InboxValidatorManager for SimulatedInboxValidatorManager {
+    async fn process(
+        &self,
+        multisig_signed_checkpoint: &MultisigSignedCheckpoint,
+        message: &AbacusMessage,
+        proof: &Proof,
+    ) -> Result<TxOutcome, ChainCommunicationError> {
+        Ok(self
+            .chain
+            .process(self.address, multisig_signed_checkpoint, message, proof)?)
+    }
+}
@@ -124,6 +124,12 @@ pub const TRANSACTION_SEND_TOTAL_LABELS: &[&str] =
 /// Help string for the metric.
 pub const TRANSACTION_SEND_TOTAL_HELP: &str = "Number of transactions sent";
 
+/// Expected label names for the `rpc_duration_seconds` metric.
+pub const RPC_DURATION_SECONDS_LABELS: &[&str] = &["chain", "method", "provider", "status"];
+/// Help string for the metric.
+pub const RPC_DURATION_SECONDS_HELP: &str =
+    "Duration from dispatch to receipt-of-response for RPC calls";
+
 /// Expected label names for the `wallet_balance` metric.
 pub const WALLET_BALANCE_LABELS: &[&str] = &[
     "chain",
@@ -197,6 +203,23 @@ pub struct ProviderMetrics {
     /// - `token_name`: Full name of the token.
     #[builder(setter(into, strip_option), default)]
     wallet_balance: Option<GaugeVec>,
+
+    /// Duration of JSON-RPC requests. This is recorded by the JSON-RPC client
+    /// rather than the middleware, so it is only available to clients that
+    /// choose between several providers.
+    /// - `chain`: the chain name (or chain ID if the name is unknown) of the chain the request was to.
+    /// - `method`: the JSON-RPC method.
+    /// - `provider`: the host of the provider the request was sent to.
+    /// - `status`: `success` or `failure`.
+    #[builder(setter(into, strip_option), default)]
+    rpc_duration_seconds: Option<HistogramVec>,
+}
+
+impl ProviderMetrics {
+    /// The histogram JSON-RPC clients should record request durations in.
+    pub fn rpc_duration_seconds(&self) -> Option<&HistogramVec> {
+        self.rpc_duration_seconds.as_ref()
+    }
 }
 
 /// An ethers-rs middleware that instruments calls with prometheus metrics. To make this is flexible
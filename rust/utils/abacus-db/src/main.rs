@@ -0,0 +1,433 @@
+//! Inspect and maintain the DB of an Abacus agent.
+//!
+//! Run this from the abacus-monorepo/rust directory using
+//! `cargo run -p abacus-db -- --db <path> --entity <name> <command>`.
+//!
+//! The DB is opened read-only as a RocksDB secondary instance, so it can be
+//! inspected while an agent is running. The exceptions are
+//! `reset-processed` and `import`, which write to the DB and so need the
+//! agent using it to be stopped first.
+
+use std::{env, fs, ops::Range, path::Path, process::ExitCode, str::FromStr};
+
+use ethers::types::H256;
+use eyre::{bail, eyre, Result};
+use tempfile::tempdir;
+
+use abacus_base::{sync_cursors, Snapshot};
+use abacus_core::{
+    db::{AbacusDB, MigrationOptions, DB},
+    Checkpoint, CommittedMessage, Decode, Encode,
+};
+
+const USAGE: &str = "\
+Usage: abacus-db --db <path> --entity <name> <command>
+
+Options:
+    --db <path>          The path of the agent's DB
+    --entity <name>      The name of the outbox chain the data is scoped to,
+                         or `<name>_inbox` for the data indexed from an inbox
+
+Commands:
+    messages [<from> <to>]
+        List the messages with leaf indices in the range, by default every
+        message
+    destination <domain> [<from> <to>]
+        List the messages to the destination domain
+    status <leaf index>
+        Show the processing and gas payment status of a leaf
+    proofs [<from> <to>]
+        Dump the proofs of the leaves with indices in the range
+    cursors
+        Print the cursors syncing resumes from
+    reset-processed <from> <to>
+        Clear the processed flag of the leaves with indices in the range, so
+        the relayer processes them again once restarted. Leaves which have
+        been pruned can't be reset. The agent using the DB must be stopped
+        first.
+    export <file>
+        Write a snapshot of the messages, gas payments and sync cursors
+        indexed from finalized blocks to the file
+    import <file> <outbox domain> <checkpoint index> <checkpoint root>
+        Verify a snapshot against a checkpoint it covers and restore it into
+        a DB without any messages. Take the checkpoint from the outbox or its
+        validators rather than the snapshot's source, e.g. one the outbox
+        cached. The agent using the DB must be stopped first.
+
+Ranges are inclusive.
+";
+
+/// A command and its arguments
+#[derive(Debug, PartialEq)]
+enum Command {
+    Messages(Option<(u32, u32)>),
+    Destination(u32, Option<(u32, u32)>),
+    Status(u32),
+    Proofs(Option<(u32, u32)>),
+    Cursors,
+    ResetProcessed(u32, u32),
+    Export(String),
+    Import(String, Checkpoint),
+}
+
+#[derive(Debug)]
+struct Args {
+    db: String,
+    entity: String,
+    command: Command,
+}
+
+fn parse_number(arg: Option<String>, name: &str) -> Result<u32> {
+    let arg = arg.ok_or_else(|| eyre!("Missing {}", name))?;
+    arg.parse().map_err(|_| eyre!("Invalid {}: {}", name, arg))
+}
+
+fn parse_root(arg: Option<String>) -> Result<H256> {
+    let arg = arg.ok_or_else(|| eyre!("Missing checkpoint root"))?;
+    H256::from_str(&arg).map_err(|_| eyre!("Invalid checkpoint root: {}", arg))
+}
+
+/// Parse an optional inclusive range of leaf indices
+fn parse_range(args: &mut impl Iterator<Item = String>) -> Result<Option<(u32, u32)>> {
+    let from = match args.next() {
+        Some(from) => parse_number(Some(from), "range start")?,
+        None => return Ok(None),
+    };
+    let to = parse_number(args.next(), "range end")?;
+    if to < from {
+        bail!("Range end {} precedes range start {}", to, from);
+    }
+    Ok(Some((from, to)))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args> {
+    let mut db = None;
+    let mut entity = None;
+    let mut command = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--db" => db = args.next(),
+            "--entity" => entity = args.next(),
+            _ => {
+                command = Some(arg);
+                break;
+            }
+        }
+    }
+
+    let command = match command.as_deref() {
+        Some("messages") => Command::Messages(parse_range(&mut args)?),
+        Some("destination") => {
+            let destination = parse_number(args.next(), "destination domain")?;
+            Command::Destination(destination, parse_range(&mut args)?)
+        }
+        Some("status") => Command::Status(parse_number(args.next(), "leaf index")?),
+        Some("proofs") => Command::Proofs(parse_range(&mut args)?),
+        Some("cursors") => Command::Cursors,
+        Some("reset-processed") => match parse_range(&mut args)? {
+            Some((from, to)) => Command::ResetProcessed(from, to),
+            None => bail!("Missing range of leaves to reset"),
+        },
+        Some("export") => Command::Export(args.next().ok_or_else(|| eyre!("Missing file"))?),
+        Some("import") => {
+            let file = args.next().ok_or_else(|| eyre!("Missing file"))?;
+            let checkpoint = Checkpoint {
+                outbox_domain: parse_number(args.next(), "outbox domain")?,
+                index: parse_number(args.next(), "checkpoint index")?,
+                root: parse_root(args.next())?,
+            };
+            Command::Import(file, checkpoint)
+        }
+        Some(command) => bail!("Unknown command: {}", command),
+        None => bail!("Missing command"),
+    };
+    if let Some(arg) = args.next() {
+        bail!("Unexpected argument: {}", arg);
+    }
+
+    Ok(Args {
+        db: db.ok_or_else(|| eyre!("Missing --db"))?,
+        entity: entity.ok_or_else(|| eyre!("Missing --entity"))?,
+        command,
+    })
+}
+
+/// The leaf indices in `range`, by default every stored leaf
+fn leaf_indices(db: &AbacusDB, range: Option<(u32, u32)>) -> Result<Range<u32>> {
+    Ok(match range {
+        Some((from, to)) => from..to.saturating_add(1),
+        None => {
+            0..db
+                .retrieve_latest_leaf_index()?
+                .map_or(0, |latest| latest + 1)
+        }
+    })
+}
+
+fn print_message(db: &AbacusDB, leaf_index: u32, destination: Option<u32>) -> Result<()> {
+    let raw = match db.message_by_leaf_index(leaf_index)? {
+        Some(raw) => raw,
+        None => {
+            if destination.is_none() {
+                match db.leaf_by_leaf_index(leaf_index)? {
+                    Some(leaf) => println!("{}: leaf {:?}, message pruned", leaf_index, leaf),
+                    None => println!("{}: missing", leaf_index),
+                }
+            }
+            return Ok(());
+        }
+    };
+    let leaf = raw.leaf();
+    let committed = CommittedMessage::try_from(&raw)?;
+    let message = committed.message;
+    if destination.map_or(false, |destination| destination != message.destination) {
+        return Ok(());
+    }
+
+    println!(
+        "{}: leaf {:?}, {} -> {}, sender {:?}, recipient {:?}, {} byte body",
+        leaf_index,
+        leaf,
+        message.origin,
+        message.destination,
+        message.sender,
+        message.recipient,
+        message.body.len(),
+    );
+    if let Some(meta) = db.message_dispatch_meta_by_leaf_index(leaf_index)? {
+        println!(
+            "    dispatched in block {} ({:?}) at {}, tx {:?}, log {}",
+            meta.block_number,
+            meta.block_hash,
+            meta.timestamp,
+            meta.transaction_hash,
+            meta.log_index,
+        );
+    }
+    Ok(())
+}
+
+fn print_status(db: &AbacusDB, leaf_index: u32) -> Result<()> {
+    let leaf = match db.leaf_by_leaf_index(leaf_index)? {
+        Some(leaf) => leaf,
+        None => bail!("No leaf with index {} has been indexed", leaf_index),
+    };
+    let processed = db
+        .retrieve_leaf_processing_status(leaf_index)?
+        .unwrap_or_default();
+    println!("leaf:           {:?}", leaf);
+    println!("processed:      {}", processed);
+    println!(
+        "finalized:      {}",
+        db.finalized_leaf_by_leaf_index(leaf_index)?.is_some()
+    );
+    println!(
+        "gas payment:    {}",
+        db.retrieve_gas_payment_for_leaf(leaf_index)?
+    );
+    Ok(())
+}
+
+/// Open the DB to write to it, refusing DBs which don't exist yet or would
+/// need migrating
+fn open_writable(args: &Args) -> Result<AbacusDB> {
+    if !Path::new(&args.db).is_dir() {
+        bail!("No DB at {}", args.db);
+    }
+    let migration = MigrationOptions {
+        dry_run: true,
+        backup_dir: None,
+    };
+    Ok(AbacusDB::new(
+        &args.entity,
+        DB::from_path(&args.db, &migration)?,
+    ))
+}
+
+fn run(args: Args) -> Result<()> {
+    match &args.command {
+        Command::ResetProcessed(from, to) => {
+            let db = open_writable(&args)?;
+            // Pruned messages can't be processed again, and the relayer would
+            // wait on them forever
+            if let Some(pruned_through) = db.retrieve_pruned_through_leaf_index()? {
+                if *from <= pruned_through {
+                    bail!("Leaves through {} have been pruned", pruned_through);
+                }
+            }
+            for leaf_index in *from..=*to {
+                if let Some(leaf) = db.leaf_by_leaf_index(leaf_index)? {
+                    if db.message_by_leaf(leaf)?.is_none() {
+                        bail!("Leaf {} has been pruned", leaf_index);
+                    }
+                }
+            }
+            for leaf_index in *from..=*to {
+                db.unmark_leaf_as_processed(leaf_index)?;
+            }
+            println!("Reset the processed flag of leaves {}...{}", from, to);
+            return Ok(());
+        }
+        Command::Import(file, checkpoint) => {
+            let snapshot = Snapshot::read_from(&mut fs::read(file)?.as_slice())?;
+            snapshot.import(&open_writable(&args)?, checkpoint)?;
+            println!(
+                "Imported {} leaves with root {:?}",
+                snapshot.leaves.len(),
+                snapshot.root
+            );
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let secondary_dir = tempdir()?;
+    let secondary_path = secondary_dir
+        .path()
+        .to_str()
+        .ok_or_else(|| eyre!("Invalid temporary directory"))?;
+    let db = AbacusDB::new(&args.entity, DB::open_secondary(&args.db, secondary_path)?);
+
+    match args.command {
+        Command::Messages(range) => {
+            for leaf_index in leaf_indices(&db, range)? {
+                print_message(&db, leaf_index, None)?;
+            }
+        }
+        Command::Destination(destination, range) => {
+            for leaf_index in leaf_indices(&db, range)? {
+                print_message(&db, leaf_index, Some(destination))?;
+            }
+        }
+        Command::Status(leaf_index) => print_status(&db, leaf_index)?,
+        Command::Proofs(range) => {
+            for leaf_index in leaf_indices(&db, range)? {
+                match db.proof_by_leaf_index(leaf_index)? {
+                    Some(proof) => println!("{}: {:?}", leaf_index, proof),
+                    None => println!("{}: missing", leaf_index),
+                }
+            }
+        }
+        Command::Cursors => {
+            for (name, block) in sync_cursors(&db)? {
+                println!("{}: {:?}", name, block);
+            }
+            println!(
+                "latest_known_leaf_index: {:?}",
+                db.retrieve_latest_leaf_index()?
+            );
+            println!(
+                "lowest_unfinalized_leaf_index: {:?}",
+                db.retrieve_lowest_unfinalized_leaf_index()?
+            );
+        }
+        Command::Export(file) => {
+            let snapshot = Snapshot::export(&db)?;
+            fs::write(&file, snapshot.to_vec())?;
+            println!(
+                "Exported {} leaves with root {:?} to {}",
+                snapshot.leaves.len(),
+                snapshot.root,
+                file
+            );
+        }
+        Command::ResetProcessed(..) | Command::Import(..) => unreachable!("handled above"),
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{:?}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args> {
+        parse_args(args.split_whitespace().map(str::to_owned))
+    }
+
+    fn parse_command(command: &str) -> Result<Command> {
+        parse(&format!("--db db --entity outbox {}", command)).map(|args| args.command)
+    }
+
+    #[test]
+    fn it_parses_ranges() {
+        let range = |args: &str| parse_range(&mut args.split_whitespace().map(str::to_owned));
+        assert_eq!(range("").unwrap(), None);
+        assert_eq!(range("3 5").unwrap(), Some((3, 5)));
+        assert_eq!(range("5 5").unwrap(), Some((5, 5)));
+        assert!(range("3").is_err());
+        assert!(range("5 3").is_err());
+        assert!(range("3 x").is_err());
+        assert!(range("-1 3").is_err());
+    }
+
+    #[test]
+    fn it_parses_args() {
+        let args = parse("--entity outbox --db /data/db messages").unwrap();
+        assert_eq!(args.db, "/data/db");
+        assert_eq!(args.entity, "outbox");
+        assert_eq!(args.command, Command::Messages(None));
+
+        assert_eq!(
+            parse_command("destination 2000 1 4").unwrap(),
+            Command::Destination(2000, Some((1, 4)))
+        );
+        assert_eq!(parse_command("status 7").unwrap(), Command::Status(7));
+        assert_eq!(
+            parse_command("reset-processed 1 4").unwrap(),
+            Command::ResetProcessed(1, 4)
+        );
+        let root = H256::repeat_byte(1);
+        assert_eq!(
+            parse_command(&format!("import snapshot 1000 7 {:?}", root)).unwrap(),
+            Command::Import(
+                "snapshot".to_owned(),
+                Checkpoint {
+                    outbox_domain: 1000,
+                    index: 7,
+                    root,
+                }
+            )
+        );
+        assert!(parse_command("import snapshot 1000 7").is_err());
+        assert!(parse_command("import snapshot 1000 7 0x01").is_err());
+
+        assert!(parse_command("reset-processed").is_err());
+        assert!(parse_command("status").is_err());
+        assert!(parse_command("export").is_err());
+        assert!(parse_command("cursors extra").is_err());
+        assert!(parse_command("unknown").is_err());
+        assert!(parse("--db db --entity outbox").is_err());
+        assert!(parse("--entity outbox cursors").is_err());
+        assert!(parse("--db db cursors").is_err());
+    }
+
+    #[test]
+    fn it_does_not_create_missing_dbs() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("missing");
+        let args = parse(&format!(
+            "--db {} --entity outbox reset-processed 0 1",
+            path.display()
+        ))
+        .unwrap();
+        assert!(open_writable(&args).is_err());
+        assert!(!path.exists());
+    }
+}